@@ -0,0 +1,79 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::hint::black_box;
+
+use telegram_pin_fetcher::config::ExportConfig;
+use telegram_pin_fetcher::export::{Envelope, ExportFile, Field, Partition};
+use telegram_pin_fetcher::redact::{RedactConfig, Redactor};
+use telegram_pin_fetcher::run::RunId;
+use telegram_pin_fetcher::stats::Stats;
+use telegram_pin_fetcher::units::ByteSize;
+use telegram_pin_fetcher::{synthetic, translate, ExporterRegistry};
+
+const PINS: usize = 10_000;
+
+fn fetching(c: &mut Criterion) {
+    let dialogs = synthetic::dialogs(PINS);
+    c.bench_function("fetch_mock", |b| {
+        b.iter(|| synthetic::fetch(black_box(&dialogs)).unwrap())
+    });
+}
+
+fn filtering(c: &mut Criterion) {
+    let messages = synthetic::messages(PINS);
+    let redactor = Redactor::new(&RedactConfig {
+        phones: true,
+        emails: true,
+        cards: true,
+        rules: Vec::new(),
+    })
+    .unwrap();
+
+    c.bench_function("redact", |b| {
+        b.iter_batched_ref(
+            || messages.clone(),
+            |messages| redactor.apply(messages),
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("detect_languages", |b| {
+        b.iter_batched_ref(
+            || messages.clone(),
+            |messages| translate::detect_languages(messages),
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("stats", |b| {
+        b.iter(|| Stats::from_messages(black_box(&messages)))
+    });
+}
+
+fn serialization(c: &mut Criterion) {
+    let messages = synthetic::messages(PINS);
+    let fields = Field::defaults();
+    let envelope = Envelope::new(RunId::new(), None, synthetic::chats(), &messages, &fields);
+    let registry = ExporterRegistry::builtin(&ExportConfig::default()).unwrap();
+
+    for name in registry.names() {
+        let exporter = registry.get(name).unwrap();
+        c.bench_function(&format!("render_{}", name), |b| {
+            b.iter(|| ExportFile::render(exporter, black_box(&envelope)).unwrap())
+        });
+    }
+
+    let json = registry.get("json").unwrap();
+    c.bench_function("render_parts_json", |b| {
+        b.iter(|| ExportFile::render_parts(json, &envelope, Some(ByteSize(256 * 1024))).unwrap())
+    });
+    c.bench_function("render_partitioned_json", |b| {
+        b.iter(|| {
+            ExportFile::render_partitioned(json, &envelope, Some(Partition::Month), None).unwrap()
+        })
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = fetching, filtering, serialization
+}
+criterion_main!(benches);
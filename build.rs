@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto/pins.proto");
+    // protox compiles the definitions in Rust, so building doesn't need `protoc`.
+    let descriptors = protox::compile(["proto/pins.proto"], ["proto"])?;
+    tonic_build::configure()
+        .build_client(false)
+        .compile_fds(descriptors)?;
+    Ok(())
+}
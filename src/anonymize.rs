@@ -0,0 +1,63 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use crate::state::StateError;
+use crate::{Message, Result};
+
+const SALT_LEN: usize = 32;
+
+/// Stable sender pseudonyms: an HMAC of the user ID, keyed with a salt that never leaves
+/// this machine, so nobody can map them back without it.
+pub struct Pseudonyms {
+    salt: Vec<u8>,
+}
+
+impl Pseudonyms {
+    /// Reads the salt at `path`, creating a random one on first use.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(salt) if salt.len() >= SALT_LEN => return Ok(Pseudonyms { salt }),
+            Ok(_) => {
+                return Err(Box::new(StateError(format!(
+                    "{} is too short to be a salt",
+                    path.display()
+                ))))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut salt = vec![0; SALT_LEN];
+        getrandom::getrandom(&mut salt)?;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(&salt)?;
+        log::info!("Created a new pseudonym salt at {}", path.display());
+        Ok(Pseudonyms { salt })
+    }
+
+    pub fn pseudonym(&self, user_id: i64) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.salt).expect("HMAC takes any key size");
+        mac.update(&user_id.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        let hex: String = digest[..6].iter().map(|b| format!("{:02x}", b)).collect();
+        format!("user-{}", hex)
+    }
+
+    pub fn apply(&self, messages: &mut [Message]) {
+        for message in messages {
+            message.sender = self.pseudonym(message.sender_id);
+            // Full of user IDs and names that pseudonyms cannot stand in for.
+            message.raw = None;
+            // Named after the sender's user ID, and their face besides.
+            message.sender_photo = None;
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_derive::Serialize;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, Notify};
+
+use crate::shutdown::Shutdown;
+use crate::{Message, Result};
+
+/// Pins of the latest run, shared between the watch loop and the API.
+#[derive(Clone)]
+pub struct LatestPins {
+    chats: Arc<RwLock<Vec<String>>>,
+    chat_ids: Arc<RwLock<HashSet<i64>>>,
+    messages: Arc<RwLock<Option<Vec<Message>>>>,
+    refresh: Arc<Notify>,
+    new_pins: broadcast::Sender<Message>,
+}
+
+impl Default for LatestPins {
+    fn default() -> Self {
+        LatestPins {
+            chats: Default::default(),
+            chat_ids: Default::default(),
+            messages: Default::default(),
+            refresh: Default::default(),
+            new_pins: broadcast::channel(256).0,
+        }
+    }
+}
+
+impl LatestPins {
+    /// Stores the pins of a run and announces the ones the previous run didn't have.
+    /// The first run only sets the baseline.
+    pub fn set(&self, chats: Vec<String>, chat_ids: HashSet<i64>, messages: Vec<Message>) {
+        *self.chats.write().unwrap() = chats;
+        *self.chat_ids.write().unwrap() = chat_ids;
+        let mut latest = self.messages.write().unwrap();
+        if let Some(previous) = latest.as_ref() {
+            for message in &messages {
+                let known = previous
+                    .iter()
+                    .any(|m| m.id == message.id && m.chat == message.chat);
+                if !known {
+                    // Fails only when nobody is subscribed.
+                    let _ = self.new_pins.send(message.clone());
+                }
+            }
+        }
+        *latest = Some(messages);
+    }
+
+    /// Pins of the latest run, or none before the first run finished.
+    pub fn messages(&self) -> Vec<Message> {
+        self.messages.read().unwrap().clone().unwrap_or_default()
+    }
+
+    /// Receives the pins that are new in each later run.
+    pub fn subscribe(&self) -> broadcast::Receiver<Message> {
+        self.new_pins.subscribe()
+    }
+
+    /// Whether the latest run fetched the chat with ID `chat_id`, so its pins are watched.
+    pub fn watches(&self, chat_id: i64) -> bool {
+        self.chat_ids.read().unwrap().contains(&chat_id)
+    }
+
+    /// Asks the watch loop to start the next run right away.
+    pub fn request_refresh(&self) {
+        self.refresh.notify_one();
+    }
+
+    /// Resolves once a refresh was requested, e.g. through `POST /refresh`. A request
+    /// made while a run is in progress is kept, so the next wait returns right away.
+    pub async fn refresh_requested(&self) {
+        self.refresh.notified().await;
+    }
+}
+
+#[derive(Serialize)]
+struct Chat {
+    id: String,
+    pins: usize,
+    last_pin: Option<String>,
+}
+
+pub(crate) fn same_chat(a: &str, b: &str) -> bool {
+    a.trim_start_matches('@')
+        .eq_ignore_ascii_case(b.trim_start_matches('@'))
+}
+
+async fn chats(State(latest): State<LatestPins>) -> Json<Vec<Chat>> {
+    let messages = latest.messages();
+    let chats = latest.chats.read().unwrap();
+    Json(
+        chats
+            .iter()
+            .map(|id| {
+                let pins = messages.iter().filter(|m| same_chat(&m.chat, id));
+                Chat {
+                    id: id.clone(),
+                    pins: pins.clone().count(),
+                    last_pin: pins.map(|m| m.date.clone()).max(),
+                }
+            })
+            .collect(),
+    )
+}
+
+async fn pins(
+    State(latest): State<LatestPins>,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<Vec<Message>>, StatusCode> {
+    if !latest
+        .chats
+        .read()
+        .unwrap()
+        .iter()
+        .any(|chat| same_chat(chat, &id))
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(
+        latest
+            .messages()
+            .into_iter()
+            .filter(|m| same_chat(&m.chat, &id))
+            .collect(),
+    ))
+}
+
+async fn refresh(State(latest): State<LatestPins>) -> StatusCode {
+    latest.request_refresh();
+    StatusCode::ACCEPTED
+}
+
+/// Serves the JSON API on `listener` until a shutdown is requested.
+pub async fn serve(listener: TcpListener, latest: LatestPins, shutdown: Shutdown) -> Result<()> {
+    let app = Router::new()
+        .route("/chats", get(chats))
+        .route("/chats/{id}/pins", get(pins))
+        .route("/refresh", post(refresh))
+        .with_state(latest);
+
+    println!("Serving the API on http://{}", listener.local_addr()?);
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move { shutdown.wait().await })
+        .await?;
+    Ok(())
+}
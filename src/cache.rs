@@ -0,0 +1,82 @@
+use grammers_session::PackedChat;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::state::{self, Versioned};
+use crate::Result;
+
+/// Resolved chats keyed by username, persisted between runs so that the heavily
+/// rate-limited `resolve_username` call only happens for chats we have never seen.
+#[derive(Default, Serialize, Deserialize)]
+struct CacheFile {
+    chats: HashMap<String, String>,
+}
+
+impl Versioned for CacheFile {
+    const VERSION: u32 = 1;
+
+    fn migrate(from: u32, value: Value) -> Result<Value> {
+        match from {
+            // Version 0 had the same layout, just without the version tag.
+            0 => Ok(value),
+            _ => unreachable!("no migration from cache version {}", from),
+        }
+    }
+}
+
+pub struct ChatCache {
+    path: PathBuf,
+    file: CacheFile,
+    dirty: bool,
+    writable: bool,
+}
+
+fn cache_key(username: &str) -> String {
+    username.trim_start_matches('@').to_lowercase()
+}
+
+impl ChatCache {
+    /// Loads the cache at `path`. A missing or unreadable cache is treated as empty; one
+    /// written by a newer release is left untouched on disk.
+    pub fn load(path: &Path) -> Self {
+        let (file, writable) = match state::load(path) {
+            Ok(file) => (file, true),
+            Err(e) => {
+                log::warn!("Ignoring chat cache {}: {}", path.display(), e);
+                (CacheFile::default(), !e.is::<state::StateError>())
+            }
+        };
+
+        ChatCache {
+            path: path.to_path_buf(),
+            file,
+            dirty: false,
+            writable,
+        }
+    }
+
+    pub fn get(&self, username: &str) -> Option<PackedChat> {
+        let hex = self.file.chats.get(&cache_key(username))?;
+        PackedChat::from_hex(hex).ok()
+    }
+
+    pub fn insert(&mut self, username: &str, chat: PackedChat) {
+        self.file.chats.insert(cache_key(username), chat.to_hex());
+        self.dirty = true;
+    }
+
+    pub fn invalidate(&mut self, username: &str) {
+        if self.file.chats.remove(&cache_key(username)).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if !self.dirty || !self.writable {
+            return Ok(());
+        }
+        state::save(&self.path, &self.file)
+    }
+}
@@ -0,0 +1,498 @@
+use serde_derive::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::encryption::EncryptionConfig;
+use crate::export::digest::DigestConfig;
+use crate::export::fields::Field;
+use crate::export::ics::IcsConfig;
+use crate::export::obsidian::ObsidianConfig;
+use crate::export::site::SiteConfig;
+use crate::export::template::TemplateConfig;
+use crate::export::text::TextStrategy;
+use crate::export::{Collision, Partition, SortOrder};
+use crate::links::LinkSnapshotConfig;
+use crate::media::MediaType;
+use crate::plugin::PluginsConfig;
+use crate::redact::RedactConfig;
+use crate::scan::ScanConfig;
+use crate::script::ScriptConfig;
+use crate::signing::SigningConfig;
+use crate::throttle::Bandwidth;
+use crate::translate::TranslateConfig;
+use crate::units::ByteSize;
+use crate::upload::{
+    DiscordConfig, FtpConfig, GofileConfig, HttpConfig, HttpMode, KafkaConfig, LinkdingConfig,
+    MastodonConfig, MatrixConfig, MqttConfig, NatsConfig, NotionConfig, NtfyConfig, PasteConfig,
+    PluginUploadConfig, PushoverConfig, RaindropConfig, RedisConfig, TelegraphConfig, UploadMode,
+    WallabagConfig,
+};
+use crate::Result;
+
+/// The config file is missing or invalid.
+#[derive(Debug)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Deserialize, Clone)]
+pub struct FileConfig {
+    pub telegram_api_creds: CredsConfig,
+    pub config: UsersConfig,
+    pub upload: UploadConfig,
+    /// More destinations, as `[[uploads]]` tables, each uploaded to after `[upload]`.
+    #[serde(default)]
+    pub uploads: Vec<UploadConfig>,
+    #[serde(default)]
+    pub media: MediaConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
+    #[serde(default)]
+    pub redact: RedactConfig,
+    pub translate: Option<TranslateConfig>,
+    /// Script transforming or dropping every pin before it is exported.
+    pub script: Option<ScriptConfig>,
+    #[serde(default)]
+    pub plugins: PluginsConfig,
+    /// Detached signatures uploaded next to every export file.
+    pub signing: Option<SigningConfig>,
+    /// Keep a readable copy of every page the pins link to.
+    pub link_snapshots: Option<LinkSnapshotConfig>,
+}
+
+impl FileConfig {
+    /// Every upload destination: `[upload]`, then the `[[uploads]]` in order.
+    pub fn destinations(&self) -> impl Iterator<Item = &UploadConfig> {
+        std::iter::once(&self.upload).chain(&self.uploads)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct UsersConfig {
+    /// Chats as plain strings: `username`, or `username#topic` to only export one forum topic.
+    #[serde(default)]
+    pub usernames: Vec<String>,
+    /// Chats with per-chat options, as `[[config.chats]]` tables.
+    #[serde(default)]
+    pub chats: Vec<ChatConfig>,
+    /// Regexes matched against the titles of all dialogs; every chat whose whole title
+    /// matches one is included too.
+    #[serde(default)]
+    pub chat_title_patterns: Vec<String>,
+    /// Cap on media download and export upload speed, e.g. `"2MiB/s"`.
+    pub max_bandwidth: Option<Bandwidth>,
+    /// Only export and upload pins that earlier runs have not uploaded yet.
+    #[serde(default)]
+    pub incremental: bool,
+    /// With `incremental`, leave out chats whose pin count and newest pin are the same as
+    /// when their pins were last uploaded, without listing their pins.
+    #[serde(default)]
+    pub skip_unchanged: bool,
+    /// Fetch through a takeout session, meant for bulk exports of large histories.
+    #[serde(default)]
+    pub takeout: bool,
+    /// Seconds a run may spend fetching; chats not reached by then are left for the next run.
+    pub max_run_duration: Option<u64>,
+    /// Replace senders with pseudonyms derived from their user ID and a local salt.
+    #[serde(default)]
+    pub anonymize: bool,
+    /// Shell commands run before every run, e.g. to mount the export drive. A failing one
+    /// aborts the run.
+    #[serde(default)]
+    pub pre_run: Vec<String>,
+    /// Shell commands run after every run, whatever its outcome, which they get in
+    /// `$TPF_STATUS`.
+    #[serde(default)]
+    pub post_run: Vec<String>,
+}
+
+impl UsersConfig {
+    /// Every configured chat, from both `usernames` and `chats`.
+    pub fn chats(&self) -> Vec<ChatConfig> {
+        self.usernames
+            .iter()
+            .map(|spec| ChatConfig::from_spec(spec))
+            .chain(self.chats.iter().cloned())
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ChatConfig {
+    pub username: String,
+    /// Human-friendly name used for the chat in exports, file names and summaries.
+    pub alias: Option<String>,
+    /// Only export pins from this forum topic, given by ID or title.
+    pub topic: Option<TopicRef>,
+    /// Skip pins of messages older than this many days; older pins are not even fetched.
+    pub max_age_days: Option<u32>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum TopicRef {
+    Id(i32),
+    Title(String),
+}
+
+impl ChatConfig {
+    fn from_spec(spec: &str) -> Self {
+        match spec.split_once('#') {
+            Some((username, topic)) => ChatConfig {
+                username: username.to_string(),
+                alias: None,
+                topic: Some(match topic.parse() {
+                    Ok(id) => TopicRef::Id(id),
+                    Err(_) => TopicRef::Title(topic.to_string()),
+                }),
+                max_age_days: None,
+            },
+            None => ChatConfig {
+                username: spec.to_string(),
+                alias: None,
+                topic: None,
+                max_age_days: None,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct CredsConfig {
+    pub api_id: i32,
+    pub api_hash: String,
+    /// Connect to Telegram's test data centers, as with `--test-dc`.
+    #[serde(default)]
+    pub test_dc: bool,
+    /// Test number (`999662YYYY`, of test data center 2) to log in with, without prompts.
+    pub test_phone: Option<String>,
+    /// How the session shows up in Telegram's list of active sessions; the library's
+    /// defaults when unset.
+    pub device_model: Option<String>,
+    pub system_version: Option<String>,
+    pub app_version: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct UploadConfig {
+    #[serde(flatten)]
+    pub provider: ProviderConfig,
+    /// Seconds to wait for the connection to the provider to be established.
+    pub connect_timeout: Option<u64>,
+    /// Seconds a whole upload request may take.
+    pub timeout: Option<u64>,
+    /// Extra PEM certificate to trust, e.g. for a self-hosted server with a private CA.
+    pub ca_certificate: Option<PathBuf>,
+    /// Skip TLS certificate validation entirely. Only meant for trusted internal servers.
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+    #[serde(default)]
+    pub mode: UploadMode,
+    /// File name, without extension, of the archive kept with `mode = "merge"`.
+    #[serde(default = "default_archive_name")]
+    pub archive_name: String,
+    /// What to do when the provider already has a file of the export's name; only `ftp` and
+    /// `http` with `send = "export"` can tell. Set, `ftp` uploads are named without the run
+    /// ID, see [`Collision::apply`].
+    pub on_collision: Option<Collision>,
+    /// Export format of the files sent to this destination; `export.format` when unset.
+    /// Providers that take messages have their own.
+    pub format: Option<String>,
+}
+
+fn default_archive_name() -> String {
+    "pinned-messages".to_string()
+}
+
+/// Provider-specific settings, selected by the `provider` key of `[upload]`.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum ProviderConfig {
+    Gofile(GofileConfig),
+    Ftp(FtpConfig),
+    #[serde(rename = "0x0")]
+    ZeroXZero(PasteConfig),
+    #[serde(rename = "paste.rs")]
+    PasteRs(PasteConfig),
+    Dpaste(PasteConfig),
+    Notion(NotionConfig),
+    Discord(DiscordConfig),
+    Matrix(MatrixConfig),
+    Mastodon(MastodonConfig),
+    Http(HttpConfig),
+    Kafka(KafkaConfig),
+    Nats(NatsConfig),
+    Mqtt(MqttConfig),
+    Redis(RedisConfig),
+    Ntfy(NtfyConfig),
+    Pushover(PushoverConfig),
+    Telegraph(TelegraphConfig),
+    Linkding(LinkdingConfig),
+    Wallabag(WallabagConfig),
+    Raindrop(RaindropConfig),
+    Plugin(PluginUploadConfig),
+}
+
+impl ProviderConfig {
+    /// Whether the provider stores each message on its own instead of an export file.
+    pub fn takes_messages(&self) -> bool {
+        if let ProviderConfig::Http(http) = self {
+            return http.send == HttpMode::Message;
+        }
+        matches!(
+            self,
+            ProviderConfig::Notion(_)
+                | ProviderConfig::Discord(_)
+                | ProviderConfig::Matrix(_)
+                | ProviderConfig::Mastodon(_)
+                | ProviderConfig::Kafka(_)
+                | ProviderConfig::Nats(_)
+                | ProviderConfig::Mqtt(_)
+                | ProviderConfig::Redis(_)
+                | ProviderConfig::Ntfy(_)
+                | ProviderConfig::Pushover(_)
+                | ProviderConfig::Telegraph(_)
+                | ProviderConfig::Linkding(_)
+                | ProviderConfig::Wallabag(_)
+                | ProviderConfig::Raindrop(_)
+        )
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MediaConfig {
+    /// Download pinned sticker files (webp/tgs/webm) next to the export.
+    #[serde(default)]
+    pub download_stickers: bool,
+    /// Where downloaded media goes; defaults to `media` inside the config directory.
+    pub dir: Option<PathBuf>,
+    /// Kinds of pinned files to download; others are only described in the export.
+    #[serde(default)]
+    pub media_types: Vec<MediaType>,
+    /// Files bigger than this are not downloaded, e.g. `"20MB"`.
+    pub max_media_size: Option<ByteSize>,
+    /// Write small JPEG previews of downloaded photos and videos for the viewer.
+    #[serde(default)]
+    pub thumbnails: bool,
+    /// Download the profile photo of every sender, once per run.
+    #[serde(default)]
+    pub profile_photos: bool,
+    /// Remove EXIF and XMP metadata (GPS position, device) from downloaded JPEG and PNG files.
+    #[serde(default)]
+    pub strip_exif: bool,
+    /// Scan downloaded documents for viruses, quarantining those flagged.
+    pub scan: Option<ScanConfig>,
+    /// How many photos and files are downloaded at the same time.
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+}
+
+fn default_workers() -> usize {
+    4
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        MediaConfig {
+            download_stickers: false,
+            dir: None,
+            media_types: Vec::new(),
+            max_media_size: None,
+            thumbnails: false,
+            profile_photos: false,
+            strip_exif: false,
+            scan: None,
+            workers: default_workers(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_format() -> String {
+    "json".to_string()
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ExportConfig {
+    /// Name of a registered exporter, e.g. `"json"`.
+    #[serde(default = "default_format")]
+    pub format: String,
+    /// Also keep a local copy of every export in this directory.
+    pub dir: Option<PathBuf>,
+    /// Encrypt the exports and statistics kept in `dir`.
+    pub encryption: Option<EncryptionConfig>,
+    /// Write per-sender statistics as `<export>.stats.json` next to the export.
+    #[serde(default)]
+    pub stats_json: bool,
+    /// What to do when a file in `dir` already has the name of a new one. Set, the files are
+    /// named without the run ID, see [`Collision::apply`].
+    pub on_collision: Option<Collision>,
+    /// Wrap messages in a metadata object; `false` writes the bare array of older releases.
+    #[serde(default = "default_true")]
+    pub envelope: bool,
+    /// Split exports larger than this into numbered parts, e.g. `"50MB"`.
+    pub max_size: Option<ByteSize>,
+    /// Write a file per month or week of the pins' message dates instead of one for all.
+    pub partition: Option<Partition>,
+    /// Also write every pin as a note into an Obsidian vault.
+    pub obsidian: Option<ObsidianConfig>,
+    /// Also add every pin to a static website.
+    pub site: Option<SiteConfig>,
+    /// The template of the `template` format.
+    pub template: Option<TemplateConfig>,
+    /// Date detection for the `ics` format.
+    #[serde(default)]
+    pub ics: IcsConfig,
+    /// Period and length of the `digest` format.
+    #[serde(default)]
+    pub digest: DigestConfig,
+    /// Message fields written by the json, yaml and toml formats and sent as JSON records.
+    #[serde(default = "Field::defaults")]
+    pub fields: Vec<Field>,
+    /// Emoji and right-to-left handling of the `org` format and Obsidian notes.
+    #[serde(default)]
+    pub text: TextStrategy,
+    /// Keep the whole Telegram message object of every pin in its `raw` field.
+    #[serde(default)]
+    pub include_raw: bool,
+    /// Order of the messages in exports.
+    #[serde(default)]
+    pub sort: SortOrder,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        ExportConfig {
+            format: default_format(),
+            dir: None,
+            encryption: None,
+            stats_json: false,
+            on_collision: None,
+            envelope: true,
+            max_size: None,
+            partition: None,
+            obsidian: None,
+            site: None,
+            template: None,
+            ics: IcsConfig::default(),
+            digest: DigestConfig::default(),
+            fields: Field::defaults(),
+            text: TextStrategy::default(),
+            include_raw: false,
+            sort: SortOrder::default(),
+        }
+    }
+}
+
+pub struct ConfigPaths {
+    pub config_file: PathBuf,
+    pub session_file: PathBuf,
+    pub cache_file: PathBuf,
+    pub seen_file: PathBuf,
+    pub lock_file: PathBuf,
+    pub salt_file: PathBuf,
+    pub links_file: PathBuf,
+    pub media_dir: PathBuf,
+    pub export_dir: PathBuf,
+    pub plugins_dir: PathBuf,
+}
+
+/// Reads and parses the config file.
+pub fn load(path: &Path) -> Result<FileConfig> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| ConfigError(format!("Could not read {}: {}", path.display(), e)))?;
+    parse(path, &contents)
+}
+
+/// The config in `contents` of the file at `path`, checked as [`load`] does.
+pub fn parse(path: &Path, contents: &str) -> Result<FileConfig> {
+    let config: FileConfig = toml::from_str(contents)
+        .map_err(|e| ConfigError(format!("Invalid config {}: {}", path.display(), e)))?;
+    if config.config.skip_unchanged && !config.config.incremental {
+        return Err(Box::new(ConfigError(format!(
+            "Invalid config {}: skip_unchanged needs incremental = true",
+            path.display()
+        ))));
+    }
+    Ok(config)
+}
+
+pub fn get_config_dirs() -> ConfigPaths {
+    let mut config_dir = match home::home_dir() {
+        Some(path) => path,
+        None => panic!("Could not find home dir"),
+    };
+
+    config_dir.push(".config");
+    config_dir.push("telegram_pinned");
+    paths_in(config_dir)
+}
+
+impl ConfigPaths {
+    /// Paths for `--test-dc`: the same config file and lock, but the session, state and
+    /// output in a `test` directory, so test accounts never mix with the real one.
+    pub fn for_test_dc(self) -> Self {
+        let test_dir = self.config_file.with_file_name("test");
+        ConfigPaths {
+            config_file: self.config_file,
+            lock_file: self.lock_file,
+            plugins_dir: self.plugins_dir,
+            ..paths_in(test_dir)
+        }
+    }
+}
+
+fn paths_in(config_dir: PathBuf) -> ConfigPaths {
+    let mut config_file = config_dir.clone();
+    config_file.push("config.toml");
+
+    let mut session_file = config_dir.clone();
+    session_file.push("telegram.session");
+
+    let mut cache_file = config_dir.clone();
+    cache_file.push("chats.cache.json");
+
+    let mut seen_file = config_dir.clone();
+    seen_file.push("seen.json");
+
+    let mut lock_file = config_dir.clone();
+    lock_file.push("telegram_pinned.pid");
+
+    let mut salt_file = config_dir.clone();
+    salt_file.push("pseudonym.salt");
+
+    let mut links_file = config_dir.clone();
+    links_file.push("links.json");
+
+    let mut media_dir = config_dir.clone();
+    media_dir.push("media");
+
+    let mut export_dir = config_dir.clone();
+    export_dir.push("exports");
+
+    let mut plugins_dir = config_dir.clone();
+    plugins_dir.push("plugins");
+
+    ConfigPaths {
+        config_file,
+        session_file,
+        cache_file,
+        seen_file,
+        lock_file,
+        salt_file,
+        links_file,
+        media_dir,
+        export_dir,
+        plugins_dir,
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::api::{same_chat, LatestPins};
+use crate::config::FileConfig;
+use crate::shutdown::Shutdown;
+use crate::Result;
+
+/// State of the watch loop that the control socket reads and changes.
+#[derive(Clone)]
+pub struct Control {
+    /// Configuration the next run uses. Chats added or removed here are not written back
+    /// to the config file.
+    pub config: Arc<Mutex<FileConfig>>,
+    pub latest: LatestPins,
+}
+
+enum Command<'a> {
+    Refresh,
+    Status,
+    AddChat(&'a str),
+    RemoveChat(&'a str),
+}
+
+impl<'a> Command<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let command = match (words.next()?, words.next()) {
+            ("refresh", None) => Command::Refresh,
+            ("status", None) => Command::Status,
+            ("add-chat", Some(chat)) => Command::AddChat(chat),
+            ("remove-chat", Some(chat)) => Command::RemoveChat(chat),
+            _ => return None,
+        };
+        match words.next() {
+            Some(_) => None,
+            None => Some(command),
+        }
+    }
+}
+
+impl Control {
+    fn status(&self) -> String {
+        let config = self.config.lock().unwrap();
+        let messages = self.latest.messages();
+        let chats = config.config.chats();
+        let mut status = format!("{} chats, {} pins\n", chats.len(), messages.len());
+        for chat in chats {
+            let pins = messages
+                .iter()
+                .filter(|m| same_chat(&m.chat, &chat.username));
+            let count = pins.clone().count();
+            match pins.map(|m| m.date.as_str()).max() {
+                Some(last) => {
+                    status += &format!("{}: {} pins, last {}\n", chat.username, count, last)
+                }
+                None => status += &format!("{}: no pins\n", chat.username),
+            }
+        }
+        status
+    }
+
+    /// `chat` is a `usernames` entry, so `username#topic` works too.
+    fn add_chat(&self, chat: &str) -> String {
+        let mut config = self.config.lock().unwrap();
+        let username = chat.split_once('#').map_or(chat, |(username, _)| username);
+        if config
+            .config
+            .chats()
+            .iter()
+            .any(|c| same_chat(&c.username, username))
+        {
+            return format!("error: {} is already configured\n", username);
+        }
+        config.config.usernames.push(chat.to_string());
+        format!("ok: added {}, fetched from the next run\n", chat)
+    }
+
+    fn remove_chat(&self, chat: &str) -> String {
+        let mut config = self.config.lock().unwrap();
+        let users = &mut config.config;
+        let before = users.usernames.len() + users.chats.len();
+        users.usernames.retain(|spec| {
+            let username = spec.split_once('#').map_or(spec.as_str(), |(u, _)| u);
+            !same_chat(username, chat)
+        });
+        users.chats.retain(|c| !same_chat(&c.username, chat));
+        match before - users.usernames.len() - users.chats.len() {
+            0 => format!("error: {} is not configured\n", chat),
+            _ => format!("ok: removed {}\n", chat),
+        }
+    }
+
+    fn handle(&self, line: &str) -> String {
+        match Command::parse(line) {
+            Some(Command::Refresh) => {
+                self.latest.request_refresh();
+                "ok: refresh requested\n".to_string()
+            }
+            Some(Command::Status) => self.status(),
+            Some(Command::AddChat(chat)) => self.add_chat(chat),
+            Some(Command::RemoveChat(chat)) => self.remove_chat(chat),
+            None => format!(
+                "error: unknown command {:?}; expected refresh, status, add-chat <chat> or remove-chat <chat>\n",
+                line.trim()
+            ),
+        }
+    }
+}
+
+/// Answers one line per command until the client disconnects.
+async fn handle_connection(stream: UnixStream, control: Control) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        writer.write_all(control.handle(&line).as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// Removes a socket left behind by an instance that didn't exit cleanly, but refuses to
+/// take over one that still answers.
+async fn clear_stale_socket(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if UnixStream::connect(path).await.is_ok() {
+        return Err(format!(
+            "control socket {} is in use by another instance",
+            path.display()
+        )
+        .into());
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Creates the control socket at `path`, only accessible to the current user.
+pub async fn bind(path: &Path) -> Result<UnixListener> {
+    clear_stale_socket(path).await?;
+    let listener = UnixListener::bind(path)?;
+    // Anyone who can connect can change the chat list.
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Accepts control commands on `listener` until a shutdown is requested.
+pub async fn serve(listener: UnixListener, control: Control, shutdown: Shutdown) -> Result<()> {
+    if let Some(path) = listener.local_addr()?.as_pathname() {
+        println!("Listening for control commands on {}", path.display());
+    }
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                let control = control.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, control).await {
+                        log::warn!("Control connection failed: {}", e);
+                    }
+                });
+            }
+            _ = shutdown.wait() => break,
+        }
+    }
+    Ok(())
+}
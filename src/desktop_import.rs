@@ -0,0 +1,337 @@
+use chrono::{DateTime, NaiveDateTime, SecondsFormat};
+use serde_derive::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::anonymize::Pseudonyms;
+use crate::cache::ChatCache;
+use crate::config::{ConfigPaths, FileConfig};
+use crate::encryption;
+use crate::export::{self, Envelope, ExportFile};
+use crate::media::{MediaInfo, MediaType};
+use crate::redact::Redactor;
+use crate::run::RunId;
+use crate::seen::SeenPins;
+use crate::stickers::StickerInfo;
+use crate::translate;
+use crate::{ChatInfo, ChatKind, ExporterRegistry, Message, Result};
+
+#[derive(Debug)]
+pub struct DesktopImportError(String);
+
+impl fmt::Display for DesktopImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for DesktopImportError {}
+
+/// A chat in Telegram Desktop's `result.json`.
+#[derive(Deserialize)]
+struct DesktopChat {
+    name: Option<String>,
+    /// The bare ID, as the chat cache has it.
+    id: i64,
+    /// `public_channel`, `private_supergroup`, `personal_chat`, ...
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    messages: Vec<DesktopMessage>,
+}
+
+#[derive(Deserialize)]
+struct DesktopMessage {
+    id: i32,
+    /// `message` or `service`.
+    #[serde(rename = "type")]
+    kind: String,
+    /// Local time of the exporting machine.
+    date: String,
+    /// Only in exports of newer releases.
+    date_unixtime: Option<String>,
+    from: Option<String>,
+    /// `user123`, `channel123`, ...
+    from_id: Option<String>,
+    /// `pin_message` for the service message of a pin, with the pinned `message_id`.
+    action: Option<String>,
+    message_id: Option<i32>,
+    /// A string, or a list of strings and entities with a `text` each.
+    #[serde(default)]
+    text: Value,
+    photo: Option<String>,
+    photo_file_size: Option<u64>,
+    file: Option<String>,
+    file_name: Option<String>,
+    file_size: Option<u64>,
+    mime_type: Option<String>,
+    media_type: Option<String>,
+    sticker_emoji: Option<String>,
+}
+
+/// Both exports of a single chat and of the whole account.
+fn chats(export: Value) -> Result<Vec<DesktopChat>> {
+    if export.get("messages").is_some() {
+        return Ok(vec![serde_json::from_value(export)?]);
+    }
+    let mut chats = Vec::new();
+    for list in ["chats", "left_chats"] {
+        if let Some(list) = export.get(list).and_then(|chats| chats.get("list")) {
+            chats.extend(serde_json::from_value::<Vec<DesktopChat>>(list.clone())?);
+        }
+    }
+    Ok(chats)
+}
+
+fn plain_text(text: &Value) -> String {
+    match text {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(|part| match part {
+                Value::String(text) => text.as_str(),
+                part => part.get("text").and_then(Value::as_str).unwrap_or_default(),
+            })
+            .collect(),
+        _ => String::new(),
+    }
+}
+
+/// The path of a file next to `result.json`; exports made without files only name them.
+fn exported_file(dir: &Path, file: &str) -> Option<String> {
+    let path = dir.join(file);
+    path.is_file().then(|| path.display().to_string())
+}
+
+impl DesktopChat {
+    fn chat_kind(&self) -> Option<ChatKind> {
+        match self.kind.as_deref()? {
+            "public_channel" | "public_supergroup" => Some(ChatKind::Public),
+            "private_channel" | "private_supergroup" => Some(ChatKind::Private),
+            "private_group" => Some(ChatKind::Group),
+            "personal_chat" | "bot_chat" | "saved_messages" => Some(ChatKind::User),
+            _ => None,
+        }
+    }
+}
+
+impl DesktopMessage {
+    fn sent_at(&self) -> DateTime<chrono::Utc> {
+        let unix = self
+            .date_unixtime
+            .as_ref()
+            .and_then(|unix| unix.parse().ok());
+        match unix {
+            Some(unix) => DateTime::from_timestamp(unix, 0).unwrap_or_default(),
+            // Older exports only have the local time, taken as UTC.
+            None => NaiveDateTime::parse_from_str(&self.date, "%Y-%m-%dT%H:%M:%S")
+                .unwrap_or_default()
+                .and_utc(),
+        }
+    }
+
+    fn sticker(&self, dir: &Path) -> Option<StickerInfo> {
+        (self.media_type.as_deref() == Some("sticker")).then(|| StickerInfo {
+            set_name: None,
+            emoji: self.sticker_emoji.clone().unwrap_or_default(),
+            file: self.file.as_ref().and_then(|file| exported_file(dir, file)),
+            size: self.file_size,
+        })
+    }
+
+    fn media(&self, dir: &Path) -> Option<MediaInfo> {
+        let (kind, file, size, mime_type) = match (&self.photo, &self.file) {
+            (Some(photo), _) => (
+                MediaType::Photo,
+                photo,
+                self.photo_file_size,
+                Some("image/jpeg".to_string()),
+            ),
+            (None, Some(file)) => {
+                let kind = match self.media_type.as_deref() {
+                    Some("sticker") => return None,
+                    Some("video_file" | "video_message" | "animation") => MediaType::Video,
+                    Some("audio_file" | "voice_message") => MediaType::Audio,
+                    _ => MediaType::Document,
+                };
+                (kind, file, self.file_size, self.mime_type.clone())
+            }
+            (None, None) => return None,
+        };
+        let path = exported_file(dir, file);
+        Some(MediaInfo {
+            kind,
+            file_name: self.file_name.clone(),
+            mime_type,
+            size,
+            skipped: path
+                .is_none()
+                .then(|| "not included in the Telegram Desktop export".to_string()),
+            file: path,
+            sha256: None,
+            thumbnail: None,
+            quarantined: None,
+        })
+    }
+}
+
+/// Reads the pins of a Telegram Desktop export (`result.json`, of one chat or the whole
+/// account) and writes them as a JSON export into `export_dir`, next to the fetched ones, so
+/// `search` and `serve` show them too. Chats are matched to the configured ones through the
+/// chat cache, or named with `chat` for the export of a single chat. With `mark_seen`, the
+/// pins also count as uploaded for incremental runs.
+///
+/// Desktop exports record no pinned state, only the service messages of pins; every message
+/// they point to is imported, even one unpinned later.
+pub fn import(
+    config: &FileConfig,
+    paths: &ConfigPaths,
+    export_dir: &Path,
+    source: &Path,
+    chat: Option<&str>,
+    mark_seen: bool,
+) -> Result<()> {
+    let file = match source.is_dir() {
+        true => source.join("result.json"),
+        false => source.to_path_buf(),
+    };
+    let dir = file.parent().unwrap_or(Path::new("."));
+    let export: Value = serde_json::from_slice(&fs::read(&file)?).map_err(|e| {
+        DesktopImportError(format!(
+            "{} is not a Telegram Desktop JSON export: {}",
+            file.display(),
+            e
+        ))
+    })?;
+    let desktop_chats = chats(export)?;
+    if chat.is_some() && desktop_chats.len() != 1 {
+        return Err(Box::new(DesktopImportError(format!(
+            "--chat names the chat of a single-chat export; {} has {}",
+            file.display(),
+            desktop_chats.len()
+        ))));
+    }
+
+    let cache = ChatCache::load(&paths.cache_file);
+    let configured = config.config.chats();
+    let by_id: HashMap<i64, _> = configured
+        .iter()
+        .filter_map(|chat| Some((cache.get(&chat.username)?.id, chat)))
+        .collect();
+
+    let mut infos = Vec::new();
+    let mut messages = Vec::new();
+    for desktop_chat in desktop_chats {
+        let title = desktop_chat.name.clone().unwrap_or_default();
+        let chat_config = by_id.get(&desktop_chat.id).copied();
+        let (name, alias) = match (chat, chat_config) {
+            (Some(chat), _) => {
+                let configured = configured.iter().find(|c| c.username == chat);
+                (chat.to_string(), configured.and_then(|c| c.alias.clone()))
+            }
+            (None, Some(chat_config)) => (chat_config.username.clone(), chat_config.alias.clone()),
+            (None, None) => {
+                println!("Skipping chat {:?}: not a configured chat", title);
+                continue;
+            }
+        };
+        let pinned: HashSet<i32> = desktop_chat
+            .messages
+            .iter()
+            .filter(|m| m.kind == "service" && m.action.as_deref() == Some("pin_message"))
+            .filter_map(|m| m.message_id)
+            .collect();
+        let before = messages.len();
+        for message in desktop_chat.messages.iter().filter(|m| m.kind == "message") {
+            if !pinned.contains(&message.id) {
+                continue;
+            }
+            let sent_at = message.sent_at();
+            let sender_id = message
+                .from_id
+                .as_deref()
+                .map(|id| id.trim_start_matches(char::is_alphabetic))
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(desktop_chat.id);
+            messages.push(Message {
+                id: message.id,
+                chat: name.clone(),
+                chat_alias: alias.clone(),
+                chat_id: Some(desktop_chat.id),
+                chat_kind: desktop_chat.chat_kind(),
+                sender: message.from.clone().unwrap_or_else(|| title.clone()),
+                sender_photo: None,
+                pin_order: None,
+                sender_id,
+                text: plain_text(&message.text),
+                date: sent_at.date_naive().to_string(),
+                datetime: sent_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                views: None,
+                forwards: None,
+                sticker: message.sticker(dir),
+                media: message.media(dir),
+                quote: None,
+                links: Vec::new(),
+                language: None,
+                text_translated: None,
+                raw: None,
+                extra: Default::default(),
+            });
+        }
+        println!(
+            "Found {} pins of {} in chat {:?}.",
+            messages.len() - before,
+            name,
+            title
+        );
+        infos.push(ChatInfo {
+            name,
+            alias,
+            id: desktop_chat.id,
+            title: desktop_chat.name,
+            ..Default::default()
+        });
+    }
+    if messages.is_empty() {
+        println!("No pins of configured chats in {}.", file.display());
+        return Ok(());
+    }
+
+    // The same privacy settings as fetched pins.
+    export::sort(&mut messages, config.export.sort, None);
+    Redactor::new(&config.redact)?.apply(&mut messages);
+    if config.config.anonymize {
+        Pseudonyms::load(&paths.salt_file)?.apply(&mut messages);
+    }
+    translate::detect_languages(&mut messages);
+
+    // Always JSON, the format `search` and `serve` read.
+    let registry = ExporterRegistry::builtin(&config.export)?;
+    let run_id = RunId::new();
+    let envelope = Envelope::new(run_id, None, infos, &messages, &config.export.fields);
+    let mut export_file = ExportFile::render(registry.get("json")?, &envelope)?;
+    if let Some(encryption) = &config.export.encryption {
+        encryption::private_dir(export_dir)?;
+        export_file = encryption.seal(&export_file)?;
+    }
+    match export_file.write_to(export_dir, config.export.on_collision, run_id)? {
+        Some(path) => println!("Imported {} pins into {}", messages.len(), path.display()),
+        None => println!("Skipped {}, it already exists", export_file.name),
+    }
+
+    if mark_seen {
+        let mut seen = SeenPins::load(&paths.seen_file)?;
+        for chat in &envelope.chats {
+            seen.add_chat(&chat.name);
+        }
+        for message in &messages {
+            seen.insert(message);
+        }
+        seen.save()?;
+        println!("Marked them as uploaded for incremental runs.");
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use age::secrecy::SecretString;
+use age::{Decryptor, Encryptor, Identity, IdentityFile, Recipient};
+use serde_derive::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io::{Read as _, Write};
+use std::os::unix::fs::{DirBuilderExt as _, PermissionsExt as _};
+use std::path::{Path, PathBuf};
+
+use crate::export::ExportFile;
+use crate::Result;
+
+/// Appended to the name of every encrypted file.
+pub const SUFFIX: &str = ".age";
+
+#[derive(Deserialize, Clone)]
+pub struct EncryptionConfig {
+    /// age public keys (`age1...`) the local exports are encrypted to.
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    /// Identity file, as written by `age-keygen`, that reads the exports back for `search`
+    /// and `serve`.
+    pub identity_file: Option<PathBuf>,
+    /// File holding a passphrase to encrypt with instead of `recipients`.
+    pub passphrase_file: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct EncryptionError(pub String);
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+fn error(message: impl Into<String>) -> Box<dyn std::error::Error> {
+    Box::new(EncryptionError(message.into()))
+}
+
+impl EncryptionConfig {
+    fn passphrase(path: &Path) -> Result<SecretString> {
+        let passphrase = fs::read_to_string(path)
+            .map_err(|e| error(format!("Could not read {}: {}", path.display(), e)))?;
+        Ok(SecretString::from(
+            passphrase.trim_end_matches(['\r', '\n']),
+        ))
+    }
+
+    fn recipients(&self) -> Result<Vec<Box<dyn Recipient>>> {
+        match (&self.passphrase_file, self.recipients.is_empty()) {
+            (Some(path), true) => Ok(vec![Box::new(age::scrypt::Recipient::new(
+                Self::passphrase(path)?,
+            ))]),
+            (Some(_), false) => Err(error(
+                "export.encryption takes recipients or a passphrase_file, not both",
+            )),
+            (None, true) => Err(error(
+                "export.encryption needs recipients or a passphrase_file",
+            )),
+            (None, false) => self
+                .recipients
+                .iter()
+                .map(|recipient| {
+                    recipient
+                        .parse::<age::x25519::Recipient>()
+                        .map(|recipient| Box::new(recipient) as Box<dyn Recipient>)
+                        .map_err(|e| error(format!("Invalid age recipient {}: {}", recipient, e)))
+                })
+                .collect(),
+        }
+    }
+
+    fn identities(&self) -> Result<Vec<Box<dyn Identity>>> {
+        if let Some(path) = &self.passphrase_file {
+            return Ok(vec![Box::new(age::scrypt::Identity::new(
+                Self::passphrase(path)?,
+            ))]);
+        }
+        let path = self.identity_file.as_ref().ok_or_else(|| {
+            error("Reading encrypted exports needs export.encryption.identity_file")
+        })?;
+        let identities = IdentityFile::from_file(path.to_string_lossy().into_owned())
+            .map_err(|e| error(format!("Could not read {}: {}", path.display(), e)))?
+            .into_identities()
+            .map_err(|e| error(format!("Invalid identity file {}: {}", path.display(), e)))?;
+        Ok(identities)
+    }
+
+    /// `file` encrypted, named with [`SUFFIX`].
+    pub fn seal(&self, file: &ExportFile) -> Result<ExportFile> {
+        let bytes = Vec::with_capacity(file.bytes.len() + 256);
+        let bytes = self.seal_into(bytes, |out| Ok(out.write_all(&file.bytes)?))?;
+        Ok(ExportFile {
+            name: format!("{}{}", file.name, SUFFIX),
+            content_type: "application/octet-stream".to_string(),
+            bytes,
+        })
+    }
+
+    /// Encrypts whatever `write` writes into `out` as it goes, like [`EncryptionConfig::seal`]
+    /// but without holding the plain text.
+    pub fn seal_into<W: Write>(
+        &self,
+        out: W,
+        write: impl FnOnce(&mut dyn Write) -> Result<()>,
+    ) -> Result<W> {
+        let recipients = self.recipients()?;
+        let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| r.as_ref() as _))?;
+        let mut writer = encryptor.wrap_output(out)?;
+        write(&mut writer)?;
+        Ok(writer.finish()?)
+    }
+
+    /// The contents of a file written by [`EncryptionConfig::seal`].
+    pub fn open(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let identities = self.identities()?;
+        let mut reader =
+            Decryptor::new_buffered(bytes)?.decrypt(identities.iter().map(|i| i.as_ref() as _))?;
+        let mut plain = Vec::new();
+        reader.read_to_end(&mut plain)?;
+        Ok(plain)
+    }
+}
+
+/// Creates `dir` if needed and makes it accessible to its owner only, so other users of the
+/// machine can't even list the exports.
+pub fn private_dir(dir: &Path) -> Result<()> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    Ok(())
+}
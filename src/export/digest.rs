@@ -0,0 +1,203 @@
+use serde_derive::Deserialize;
+use std::io::Write;
+
+use super::{Envelope, Exporter};
+use crate::{media, Message, Result};
+
+fn default_max_items() -> usize {
+    50
+}
+
+fn default_max_text() -> usize {
+    200
+}
+
+/// How far back from the export a digest looks.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    /// The last 24 hours.
+    Day,
+    /// The last 7 days.
+    #[default]
+    Week,
+    /// The last 30 days.
+    Month,
+}
+
+impl DigestPeriod {
+    fn seconds(self) -> i64 {
+        let days = match self {
+            DigestPeriod::Day => 1,
+            DigestPeriod::Week => 7,
+            DigestPeriod::Month => 30,
+        };
+        days * 24 * 60 * 60
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            DigestPeriod::Day => "Today's pins",
+            DigestPeriod::Week => "This week's pins",
+            DigestPeriod::Month => "This month's pins",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DigestConfig {
+    #[serde(default)]
+    pub period: DigestPeriod,
+    /// Pins listed at most, the newest ones; the others are only counted.
+    #[serde(default = "default_max_items")]
+    pub max_items: usize,
+    /// Characters of a pin's text shown before it is cut off.
+    #[serde(default = "default_max_text")]
+    pub max_text: usize,
+    /// Heading of the digest, by default after its period, e.g. "This week's pins".
+    pub title: Option<String>,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        DigestConfig {
+            period: DigestPeriod::default(),
+            max_items: default_max_items(),
+            max_text: default_max_text(),
+            title: None,
+        }
+    }
+}
+
+/// A short HTML summary of the pins sent during the last `period`, grouped by chat, with the
+/// start of each text and a link to the pin. Styles are inline and there are no scripts or
+/// images, so it reads the same as the body of an email.
+pub struct DigestExporter {
+    config: DigestConfig,
+}
+
+impl DigestExporter {
+    pub fn new(config: &DigestConfig) -> Self {
+        DigestExporter {
+            config: config.clone(),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The first `max` characters of the text, on one line.
+fn shorten(text: &str, max: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.chars().count() > max {
+        true => format!("{}…", text.chars().take(max).collect::<String>().trim_end()),
+        false => text,
+    }
+}
+
+fn summary(message: &Message, max: usize) -> String {
+    if !message.text.trim().is_empty() {
+        return shorten(&message.text, max);
+    }
+    if let Some(sticker) = &message.sticker {
+        return format!("[sticker {}]", sticker.emoji);
+    }
+    match &message.media {
+        Some(info) => media::placeholder(info),
+        None => "Pinned message".to_string(),
+    }
+}
+
+impl Exporter for DigestExporter {
+    fn extension(&self) -> &'static str {
+        "html"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/html"
+    }
+
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()> {
+        let generated_at = chrono::DateTime::parse_from_rfc3339(&export.generated_at)
+            .map_or_else(|_| chrono::Utc::now().timestamp(), |at| at.timestamp());
+        let since = generated_at - self.config.period.seconds();
+        let mut pins: Vec<&Message> = export
+            .messages
+            .iter()
+            .filter(|m| m.sent_at() >= since)
+            .collect();
+        pins.sort_by_cached_key(|m| std::cmp::Reverse(m.sort_key()));
+        let more = pins.len().saturating_sub(self.config.max_items);
+        pins.truncate(self.config.max_items);
+
+        let title = self
+            .config
+            .title
+            .as_deref()
+            .unwrap_or(self.config.period.title());
+        writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>",
+            escape(title)
+        )?;
+        writeln!(
+            out,
+            "<body style=\"font-family:system-ui,sans-serif;max-width:40rem;margin:0 auto;padding:1rem;line-height:1.5;color:#222\">"
+        )?;
+        writeln!(out, "<h1 style=\"font-size:1.4rem\">{}</h1>", escape(title))?;
+        if pins.is_empty() {
+            writeln!(out, "<p>No pins in this period.</p>")?;
+        }
+
+        // Chats in the order of the export, pins newest first within them.
+        let mut chats: Vec<&str> = export.chats.iter().map(|c| c.name.as_str()).collect();
+        for pin in &pins {
+            if !chats.contains(&pin.chat.as_str()) {
+                chats.push(&pin.chat);
+            }
+        }
+        for chat in chats {
+            let mut chat_pins = pins.iter().filter(|m| m.chat == chat).peekable();
+            let Some(first) = chat_pins.peek() else {
+                continue;
+            };
+            writeln!(
+                out,
+                "<h2 style=\"font-size:1.1rem;margin-top:1.5rem\">{}</h2>\n<ul style=\"padding-left:1.2rem\">",
+                escape(first.chat_label())
+            )?;
+            for pin in chat_pins {
+                let date = match pin.link() {
+                    Some(link) => format!(
+                        "<a href=\"{}\" style=\"color:#0a5fb4\">{}</a>",
+                        escape(&link),
+                        escape(&pin.date)
+                    ),
+                    None => escape(&pin.date),
+                };
+                writeln!(
+                    out,
+                    "<li style=\"margin-bottom:.6rem\">{}{}<br>{}</li>",
+                    date,
+                    match pin.sender.is_empty() {
+                        true => String::new(),
+                        false =>
+                            format!(" <span style=\"color:#666\">{}</span>", escape(&pin.sender)),
+                    },
+                    escape(&summary(pin, self.config.max_text))
+                )?;
+            }
+            writeln!(out, "</ul>")?;
+        }
+        if more > 0 {
+            writeln!(out, "<p style=\"color:#666\">And {} more pins.</p>", more)?;
+        }
+        writeln!(out, "</body>\n</html>")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use serde::ser::{Serialize, SerializeMap as _, SerializeSeq as _, Serializer};
+use serde_derive::Deserialize;
+use std::ops::Deref;
+
+use crate::Message;
+
+/// A message field that structured exports can include, as listed in `export.fields`.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Id,
+    Chat,
+    Sender,
+    /// Path of the downloaded profile photo of the sender.
+    #[serde(rename = "sender_photo")]
+    SenderPhoto,
+    /// Position among the chat's pins, 1 at the top.
+    #[serde(rename = "pin_order")]
+    PinOrder,
+    Text,
+    Date,
+    /// Date and time in UTC, RFC 3339.
+    Datetime,
+    Views,
+    Forwards,
+    /// The `t.me` link, which is not stored otherwise.
+    Link,
+    Sticker,
+    Media,
+    Quote,
+    /// Snapshots of linked pages, with `[link_snapshots]`.
+    Links,
+    Language,
+    #[serde(rename = "text_translated")]
+    TextTranslated,
+    /// Only set with `export.include_raw`.
+    Raw,
+}
+
+impl Field {
+    /// The fields written unless `export.fields` says otherwise.
+    pub fn defaults() -> Vec<Field> {
+        vec![
+            Field::Id,
+            Field::Chat,
+            Field::Sender,
+            Field::SenderPhoto,
+            Field::PinOrder,
+            Field::Text,
+            Field::Date,
+            Field::Datetime,
+            Field::Views,
+            Field::Forwards,
+            Field::Sticker,
+            Field::Media,
+            Field::Quote,
+            Field::Links,
+            Field::Language,
+            Field::TextTranslated,
+            Field::Raw,
+        ]
+    }
+}
+
+/// Clears what `fields` leaves out of `messages` for the formats and uploads that lay messages
+/// out their own way instead of writing the selected fields: the sender and their photo, so
+/// leaving out `sender` keeps sender identities out of every export.
+pub fn mask(messages: &mut [Message], fields: &[Field]) {
+    for message in messages {
+        if !fields.contains(&Field::Sender) {
+            message.sender.clear();
+        }
+        if !fields.contains(&Field::SenderPhoto) {
+            message.sender_photo = None;
+        }
+    }
+}
+
+/// `message` serialized with only `fields`, in their order.
+pub struct Selected<'a> {
+    pub message: &'a Message,
+    pub fields: &'a [Field],
+}
+
+impl Serialize for Selected<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let message = self.message;
+        let mut map = serializer.serialize_map(None)?;
+        for field in self.fields {
+            match field {
+                Field::Id => map.serialize_entry("id", &message.id)?,
+                Field::Chat => {
+                    map.serialize_entry("chat", &message.chat)?;
+                    if let Some(alias) = &message.chat_alias {
+                        map.serialize_entry("chat_alias", alias)?;
+                    }
+                    // Kept with the chat, so pins read back from the export still link.
+                    if let Some(id) = message.chat_id {
+                        map.serialize_entry("chat_id", &id)?;
+                    }
+                    if let Some(kind) = message.chat_kind {
+                        map.serialize_entry("chat_kind", &kind)?;
+                    }
+                }
+                Field::Sender => map.serialize_entry("sender", &message.sender)?,
+                Field::SenderPhoto => {
+                    if let Some(photo) = &message.sender_photo {
+                        map.serialize_entry("sender_photo", photo)?;
+                    }
+                }
+                Field::PinOrder => {
+                    if let Some(order) = message.pin_order {
+                        map.serialize_entry("pin_order", &order)?;
+                    }
+                }
+                Field::Text => map.serialize_entry("text", &message.text)?,
+                Field::Date => map.serialize_entry("date", &message.date)?,
+                Field::Datetime => {
+                    if !message.datetime.is_empty() {
+                        map.serialize_entry("datetime", &message.datetime)?;
+                    }
+                }
+                Field::Views => {
+                    if let Some(views) = message.views {
+                        map.serialize_entry("views", &views)?;
+                    }
+                }
+                Field::Forwards => {
+                    if let Some(forwards) = message.forwards {
+                        map.serialize_entry("forwards", &forwards)?;
+                    }
+                }
+                Field::Link => {
+                    if let Some(link) = message.link() {
+                        map.serialize_entry("link", &link)?;
+                    }
+                }
+                Field::Sticker => {
+                    if let Some(sticker) = &message.sticker {
+                        map.serialize_entry("sticker", sticker)?;
+                    }
+                }
+                Field::Media => {
+                    if let Some(media) = &message.media {
+                        map.serialize_entry("media", media)?;
+                    }
+                }
+                Field::Quote => {
+                    if let Some(quote) = &message.quote {
+                        map.serialize_entry("quote", quote)?;
+                    }
+                }
+                Field::Links => {
+                    if !message.links.is_empty() {
+                        map.serialize_entry("links", &message.links)?;
+                    }
+                }
+                Field::Language => {
+                    if let Some(language) = &message.language {
+                        map.serialize_entry("language", language)?;
+                    }
+                }
+                Field::TextTranslated => {
+                    if let Some(translated) = &message.text_translated {
+                        map.serialize_entry("text_translated", translated)?;
+                    }
+                }
+                Field::Raw => {
+                    if let Some(raw) = &message.raw {
+                        map.serialize_entry("raw", raw)?;
+                    }
+                }
+            }
+        }
+        for (key, value) in &message.extra {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Messages that serialize as a list of [`Selected`] records. Derefs to the messages.
+#[derive(Clone, Copy)]
+pub struct Records<'a> {
+    pub messages: &'a [Message],
+    pub fields: &'a [Field],
+}
+
+impl<'a> Records<'a> {
+    pub fn with_messages<'b>(&self, messages: &'b [Message]) -> Records<'b>
+    where
+        'a: 'b,
+    {
+        Records {
+            messages,
+            fields: self.fields,
+        }
+    }
+}
+
+impl Deref for Records<'_> {
+    type Target = [Message];
+
+    fn deref(&self) -> &[Message] {
+        self.messages
+    }
+}
+
+impl Serialize for Records<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.messages.len()))?;
+        for message in self.messages {
+            seq.serialize_element(&Selected {
+                message,
+                fields: self.fields,
+            })?;
+        }
+        seq.end()
+    }
+}
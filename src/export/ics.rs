@@ -0,0 +1,191 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+use serde_derive::Deserialize;
+use std::io::Write;
+
+use super::{Envelope, Exporter};
+use crate::{Message, Result};
+
+/// ISO dates (`2024-05-01 18:30`) and day-first dates (`1.5.2024`, `01/05/2024 at 18:30`).
+const DEFAULT_PATTERNS: &[&str] = &[
+    r"(?P<year>\d{4})-(?P<month>\d{1,2})-(?P<day>\d{1,2})(?:[ T](?P<hour>\d{1,2}):(?P<minute>\d{2}))?",
+    r"(?P<day>\d{1,2})[./](?P<month>\d{1,2})[./](?P<year>\d{4})(?:,?\s+(?:at\s+)?(?P<hour>\d{1,2})[:.](?P<minute>\d{2}))?",
+];
+
+fn default_duration() -> u32 {
+    60
+}
+
+#[derive(Deserialize, Clone)]
+pub struct IcsConfig {
+    /// Regexes finding an event date in the text, tried in order. They need `year`, `month`
+    /// and `day` groups and may have `hour` and `minute`.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Length of events that have a start time.
+    #[serde(default = "default_duration")]
+    pub duration_minutes: u32,
+}
+
+impl Default for IcsConfig {
+    fn default() -> Self {
+        IcsConfig {
+            patterns: Vec::new(),
+            duration_minutes: default_duration(),
+        }
+    }
+}
+
+enum Start {
+    Day(NaiveDate),
+    Time(NaiveDateTime),
+}
+
+/// One calendar event per pin that mentions a date; pins without one are left out.
+pub struct IcsExporter {
+    patterns: Vec<Regex>,
+    duration_minutes: u32,
+}
+
+impl IcsExporter {
+    pub fn new(config: &IcsConfig) -> Result<Self> {
+        let patterns: Vec<&str> = match config.patterns.is_empty() {
+            true => DEFAULT_PATTERNS.to_vec(),
+            false => config.patterns.iter().map(String::as_str).collect(),
+        };
+        let patterns = patterns
+            .into_iter()
+            .map(Regex::new)
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(IcsExporter {
+            patterns,
+            duration_minutes: config.duration_minutes,
+        })
+    }
+
+    fn find_start(&self, text: &str) -> Option<Start> {
+        for pattern in &self.patterns {
+            for captures in pattern.captures_iter(text) {
+                let number = |name: &str| captures.name(name)?.as_str().parse::<u32>().ok();
+                let (Some(year), Some(month), Some(day)) =
+                    (number("year"), number("month"), number("day"))
+                else {
+                    continue;
+                };
+                let Some(day) = NaiveDate::from_ymd_opt(year as i32, month, day) else {
+                    continue;
+                };
+                let time = match (number("hour"), number("minute")) {
+                    (Some(hour), Some(minute)) => NaiveTime::from_hms_opt(hour, minute, 0),
+                    _ => None,
+                };
+                return Some(match time {
+                    Some(time) => Start::Time(day.and_time(time)),
+                    None => Start::Day(day),
+                });
+            }
+        }
+        None
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Writes a content line, folded at 75 octets as RFC 5545 requires.
+fn write_line(out: &mut dyn Write, line: &str) -> Result<()> {
+    let mut start = 0;
+    let mut limit = 75;
+    while line.len() - start > limit {
+        let mut end = start + limit;
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        write!(out, "{}\r\n ", &line[start..end])?;
+        start = end;
+        // Continuation lines start with a space.
+        limit = 74;
+    }
+    write!(out, "{}\r\n", &line[start..])?;
+    Ok(())
+}
+
+fn summary(message: &Message) -> String {
+    let line = message.text.lines().find(|line| !line.trim().is_empty());
+    line.unwrap_or("Pinned message").trim().to_string()
+}
+
+impl Exporter for IcsExporter {
+    fn extension(&self) -> &'static str {
+        "ics"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/calendar"
+    }
+
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()> {
+        let stamp = chrono::DateTime::parse_from_rfc3339(&export.generated_at)?
+            .with_timezone(&chrono::Utc)
+            .format("%Y%m%dT%H%M%SZ")
+            .to_string();
+
+        write_line(out, "BEGIN:VCALENDAR")?;
+        write_line(out, "VERSION:2.0")?;
+        write_line(out, "PRODID:-//telegram-pin-fetcher//EN")?;
+        for message in export.messages.iter() {
+            let Some(start) = self.find_start(&message.text) else {
+                continue;
+            };
+            let link = message.link();
+            write_line(out, "BEGIN:VEVENT")?;
+            write_line(
+                out,
+                &format!(
+                    "UID:{}-{}@telegram-pin-fetcher",
+                    message.chat.trim_start_matches('@'),
+                    message.id
+                ),
+            )?;
+            write_line(out, &format!("DTSTAMP:{}", stamp))?;
+            match start {
+                Start::Day(day) => {
+                    write_line(out, &format!("DTSTART;VALUE=DATE:{}", day.format("%Y%m%d")))?;
+                }
+                Start::Time(time) => {
+                    write_line(out, &format!("DTSTART:{}", time.format("%Y%m%dT%H%M%S")))?;
+                    write_line(out, &format!("DURATION:PT{}M", self.duration_minutes))?;
+                }
+            }
+            write_line(out, &format!("SUMMARY:{}", escape(&summary(message))))?;
+            write_line(
+                out,
+                &format!(
+                    "DESCRIPTION:{}",
+                    escape(&format!(
+                        "{}\n\nPinned {}in {}{}",
+                        message.text,
+                        match message.sender.is_empty() {
+                            true => String::new(),
+                            false => format!("by {} ", message.sender),
+                        },
+                        message.chat_label(),
+                        link.as_deref()
+                            .map(|link| format!(": {}", link))
+                            .unwrap_or_default()
+                    ))
+                ),
+            )?;
+            if let Some(link) = &link {
+                write_line(out, &format!("URL:{}", link))?;
+            }
+            write_line(out, "END:VEVENT")?;
+        }
+        write_line(out, "END:VCALENDAR")?;
+        Ok(())
+    }
+}
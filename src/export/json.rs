@@ -0,0 +1,50 @@
+use serde_json::Value;
+use std::io::Write;
+
+use super::{Envelope, Exporter};
+use crate::{ChatInfo, Message, Result};
+
+/// The default format. Without `envelope` only the bare message array is written, as older
+/// releases did.
+pub struct JsonExporter {
+    pub envelope: bool,
+}
+
+impl Exporter for JsonExporter {
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()> {
+        match self.envelope {
+            true => serde_json::to_writer(out, export)?,
+            false => serde_json::to_writer(out, &export.messages)?,
+        }
+        Ok(())
+    }
+}
+
+/// Chats and messages of a JSON export, with or without the metadata envelope. Chats are
+/// empty for bare arrays and for envelopes of schema version 1, which only had their names.
+pub fn read(bytes: &[u8]) -> Result<(Vec<ChatInfo>, Vec<Message>)> {
+    read_value(serde_json::from_slice(bytes)?)
+}
+
+/// Like [`read`], for a file already parsed.
+pub fn read_value(value: Value) -> Result<(Vec<ChatInfo>, Vec<Message>)> {
+    let (chats, messages) = match value {
+        Value::Object(mut envelope) => (
+            envelope.remove("chats").unwrap_or_default(),
+            envelope.remove("messages").unwrap_or_default(),
+        ),
+        array => (Value::Null, array),
+    };
+    Ok((
+        serde_json::from_value(chats).unwrap_or_default(),
+        serde_json::from_value(messages)?,
+    ))
+}
@@ -0,0 +1,607 @@
+pub mod digest;
+pub mod fields;
+pub mod ics;
+mod json;
+pub mod obsidian;
+mod org;
+pub mod site;
+mod tdesktop;
+pub mod template;
+pub mod text;
+mod toml;
+mod yaml;
+
+use chrono::NaiveDate;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::ExportConfig;
+use crate::encryption::{self, EncryptionConfig};
+use crate::run::RunId;
+use crate::units::ByteSize;
+use crate::{ChatInfo, Message, Result};
+
+pub use self::toml::TomlExporter;
+pub use digest::DigestExporter;
+pub use fields::{Field, Records, Selected};
+pub use ics::IcsExporter;
+pub use json::{read as read_json, read_value as read_json_value, JsonExporter};
+pub use org::OrgExporter;
+pub use tdesktop::{is_tdesktop, TdesktopExporter};
+pub use template::TemplateExporter;
+pub use text::TextStrategy;
+pub use yaml::YamlExporter;
+
+/// Bumped whenever the layout of exported records changes incompatibly.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Top-level export object, so consumers can tell formats and origins apart.
+#[derive(Serialize)]
+pub struct Envelope<'a> {
+    pub schema_version: u32,
+    pub generated_at: String,
+    /// The run that made the export, also part of its file name.
+    pub run_id: RunId,
+    pub tool_version: &'static str,
+    pub account: Option<String>,
+    /// Metadata of the exported chats; plain names up to schema version 1.
+    pub chats: Vec<ChatInfo>,
+    /// Set when the export was split; numbered from 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parts: Option<usize>,
+    /// Set when the export was partitioned by date, e.g. `2024-05`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<String>,
+    pub messages: Records<'a>,
+}
+
+impl<'a> Envelope<'a> {
+    /// Messages are written with only `fields`, see `export.fields`.
+    pub fn new(
+        run_id: RunId,
+        account: Option<String>,
+        chats: Vec<ChatInfo>,
+        messages: &'a [Message],
+        fields: &'a [Field],
+    ) -> Self {
+        Envelope {
+            schema_version: SCHEMA_VERSION,
+            generated_at: chrono::offset::Utc::now().to_rfc3339(),
+            run_id,
+            tool_version: env!("CARGO_PKG_VERSION"),
+            account,
+            chats,
+            part: None,
+            parts: None,
+            period: None,
+            messages: Records { messages, fields },
+        }
+    }
+
+    /// This envelope with `chats` and `messages` in place of its own.
+    pub fn with_contents<'b>(&self, chats: Vec<ChatInfo>, messages: &'b [Message]) -> Envelope<'b>
+    where
+        'a: 'b,
+    {
+        Envelope {
+            chats,
+            ..self.with_messages(messages)
+        }
+    }
+
+    fn with_messages<'b>(&self, messages: &'b [Message]) -> Envelope<'b>
+    where
+        'a: 'b,
+    {
+        Envelope {
+            schema_version: self.schema_version,
+            generated_at: self.generated_at.clone(),
+            run_id: self.run_id,
+            tool_version: self.tool_version,
+            account: self.account.clone(),
+            chats: self.chats.clone(),
+            part: self.part,
+            parts: self.parts,
+            period: self.period.clone(),
+            messages: self.messages.with_messages(messages),
+        }
+    }
+}
+
+/// Adds `new_chats` and `new` to the chats and pins of an earlier export. Pins seen again
+/// replace their earlier copy, e.g. after an edit; pins end up sorted by date, chats by name.
+pub fn merge(
+    chats: &mut Vec<ChatInfo>,
+    messages: &mut Vec<Message>,
+    new_chats: &[ChatInfo],
+    new: &[Message],
+) {
+    for chat in new_chats {
+        chats.retain(|known| known.name != chat.name);
+        chats.push(chat.clone());
+    }
+    let ids: HashSet<_> = new.iter().map(|m| (m.chat.as_str(), m.id)).collect();
+    messages.retain(|known| !ids.contains(&(known.chat.as_str(), known.id)));
+    messages.extend(new.iter().cloned());
+    messages.sort_by_cached_key(Message::sort_key);
+    chats.sort_by(|a, b| a.name.cmp(&b.name));
+}
+
+/// Name shared by every file of today's export, e.g. `2024-05-01`.
+pub fn today_stem() -> String {
+    let now = chrono::offset::Utc::now();
+    now.date_naive().format("%Y-%m-%d").to_string()
+}
+
+/// An output format for exports. Implement this and add it to an [`ExporterRegistry`] to make
+/// a new format selectable through `export.format` without touching the rest of the pipeline.
+pub trait Exporter {
+    /// File extension, without the leading dot.
+    fn extension(&self) -> &str;
+
+    /// MIME type sent along when the export is uploaded.
+    fn content_type(&self) -> &str {
+        "application/octet-stream"
+    }
+
+    /// Serializes `export` (its metadata and `export.messages`) into `out`.
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub struct UnknownFormat {
+    pub format: String,
+    pub available: Vec<String>,
+}
+
+impl fmt::Display for UnknownFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unknown export format {} (available: {})",
+            self.format,
+            self.available.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownFormat {}
+
+/// Exporters keyed by the format name used in the config.
+pub struct ExporterRegistry {
+    exporters: BTreeMap<String, Box<dyn Exporter>>,
+}
+
+impl ExporterRegistry {
+    /// A registry without any formats.
+    pub fn empty() -> Self {
+        ExporterRegistry {
+            exporters: BTreeMap::new(),
+        }
+    }
+
+    /// A registry with every built-in format, set up from the `[export]` config.
+    pub fn builtin(config: &ExportConfig) -> Result<Self> {
+        let envelope = config.envelope;
+        let mut registry = Self::empty();
+        registry.register("json", JsonExporter { envelope });
+        registry.register("yaml", YamlExporter { envelope });
+        registry.register("toml", TomlExporter { envelope });
+        registry.register("org", OrgExporter { text: config.text });
+        registry.register("ics", IcsExporter::new(&config.ics)?);
+        registry.register("tdesktop", TdesktopExporter);
+        registry.register("digest", DigestExporter::new(&config.digest));
+        if let Some(template) = &config.template {
+            registry.register("template", TemplateExporter::new(template)?);
+        }
+        Ok(registry)
+    }
+
+    /// Adds `exporter` under `name`, replacing any format previously registered with that name.
+    pub fn register(&mut self, name: &str, exporter: impl Exporter + 'static) {
+        self.exporters
+            .insert(name.to_lowercase(), Box::new(exporter));
+    }
+
+    pub fn get(&self, name: &str) -> Result<&dyn Exporter> {
+        self.exporters
+            .get(&name.to_lowercase())
+            .map(Box::as_ref)
+            .ok_or_else(|| {
+                UnknownFormat {
+                    format: name.to_string(),
+                    available: self.names().map(str::to_string).collect(),
+                }
+                .into()
+            })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.exporters.keys().map(String::as_str)
+    }
+}
+
+impl Default for ExporterRegistry {
+    fn default() -> Self {
+        Self::builtin(&ExportConfig::default()).expect("default exporters are valid")
+    }
+}
+
+/// Order of the messages in an export, see `export.sort`.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// Oldest first.
+    #[default]
+    #[serde(alias = "date")]
+    DateAsc,
+    /// Newest first.
+    DateDesc,
+    /// Chat by chat in the order they are configured, oldest first within each.
+    ChatThenDate,
+    /// Chat by chat in the order they are configured, each in its pinned list's order from
+    /// the top; pins without a `pin_order` go last, by date.
+    PinOrder,
+    /// As fetched: chat by chat, in the order Telegram returned them.
+    None,
+}
+
+/// Puts `messages` in `order`, by when they were sent and then by ID.
+/// With a `partition`, the messages of each period are then kept next to each other, in that
+/// order, as [`ExportFile::render_partitioned`] needs.
+pub fn sort(messages: &mut [Message], order: SortOrder, partition: Option<Partition>) {
+    match order {
+        SortOrder::DateAsc => messages.sort_by_cached_key(Message::sort_key),
+        SortOrder::DateDesc => messages.sort_by_cached_key(|m| std::cmp::Reverse(m.sort_key())),
+        SortOrder::ChatThenDate => {
+            let rank = chat_rank(messages);
+            messages.sort_by_cached_key(|m| (rank[&m.chat], m.sort_key()));
+        }
+        SortOrder::PinOrder => {
+            let rank = chat_rank(messages);
+            messages.sort_by_cached_key(|m| {
+                let order = m.pin_order.unwrap_or(u32::MAX);
+                (rank[&m.chat], order, m.sort_key())
+            });
+        }
+        SortOrder::None => {}
+    }
+    if let Some(partition) = partition {
+        messages.sort_by_cached_key(|m| partition.period(&m.date));
+    }
+}
+
+/// Position of every chat by its first message, the order chats are configured in.
+fn chat_rank(messages: &[Message]) -> HashMap<String, usize> {
+    let mut chats: Vec<&str> = Vec::new();
+    for message in messages {
+        if !chats.contains(&message.chat.as_str()) {
+            chats.push(&message.chat);
+        }
+    }
+    chats
+        .into_iter()
+        .enumerate()
+        .map(|(i, chat)| (chat.to_string(), i))
+        .collect()
+}
+
+/// Counts the bytes written to it and drops them, to measure an export without holding it.
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Period the messages of an export are split by, see `export.partition`.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Partition {
+    Month,
+    /// ISO 8601 weeks, starting on Monday.
+    Week,
+}
+
+impl Partition {
+    /// The period a message `date` falls in, e.g. `2024-05` or `2024-W18`.
+    pub fn period(self, date: &str) -> String {
+        let Ok(day) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+            return "undated".to_string();
+        };
+        let format = match self {
+            Partition::Month => "%Y-%m",
+            Partition::Week => "%G-W%V",
+        };
+        day.format(format).to_string()
+    }
+}
+
+/// What to do when a file of the same name is already where an export is written or uploaded.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Collision {
+    /// Replace the existing file.
+    Overwrite,
+    /// Keep the existing file and leave the new one out.
+    Skip,
+    /// Write under the first free name with `-1`, `-2`, ... before the extension.
+    Suffix,
+}
+
+impl Collision {
+    /// How to write a file rendered as `name`, and under which name, with `on_collision` as
+    /// configured. Rendered names carry the run ID, so files of different runs never meet;
+    /// when `on_collision` is set, the run ID is left out, as in `2024-05-01.json`, for a
+    /// later run of the day to find the earlier one's file. Unset, the name is kept and the
+    /// file written without looking.
+    pub fn apply(on_collision: Option<Self>, name: &str, run_id: RunId) -> (Self, String) {
+        match on_collision {
+            Some(collision) => (
+                collision,
+                name.replacen(&format!(".{}", run_id.short()), "", 1),
+            ),
+            None => (Collision::Overwrite, name.to_string()),
+        }
+    }
+
+    /// The name to write to, or `None` to skip it. `exists` is asked about `name` and, when
+    /// suffixing, the suffixed names after it; it is not called for `Overwrite`.
+    pub fn resolve<E>(
+        self,
+        name: &str,
+        mut exists: impl FnMut(&str) -> std::result::Result<bool, E>,
+    ) -> std::result::Result<Option<String>, E> {
+        if self == Collision::Overwrite || !exists(name)? {
+            return Ok(Some(name.to_string()));
+        }
+        if self == Collision::Skip {
+            return Ok(None);
+        }
+        let mut n = 1;
+        loop {
+            let candidate = suffixed(name, n);
+            if !exists(&candidate)? {
+                return Ok(Some(candidate));
+            }
+            n += 1;
+        }
+    }
+}
+
+/// `name`, a file name or the path of a URL, with `-<n>` before the extension of its last
+/// segment: `2024-05-01.json` becomes `2024-05-01-1.json`.
+pub fn suffixed(name: &str, n: usize) -> String {
+    let segment = name.rfind('/').map_or(0, |slash| slash + 1);
+    match name[segment..].rfind('.') {
+        Some(0) | None => format!("{}-{}", name, n),
+        Some(dot) => {
+            let (stem, extension) = name.split_at(segment + dot);
+            format!("{}-{}{}", stem, n, extension)
+        }
+    }
+}
+
+/// A serialized export, ready to be written locally or handed to a provider.
+#[derive(Clone)]
+pub struct ExportFile {
+    pub name: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+impl ExportFile {
+    /// The name of the export of `envelope` with `exporter`, after today's date and the run,
+    /// e.g. `2024-05-01.1b4e28ba.json`, so runs on the same day do not overwrite each other.
+    /// The period of a partitioned export follows, as in `2024-05-01.1b4e28ba.2024-04.json`,
+    /// and the number of a part, as in `2024-05-01.1b4e28ba.part2.json`.
+    pub fn name(exporter: &dyn Exporter, envelope: &Envelope) -> String {
+        let mut name = format!("{}.{}", today_stem(), envelope.run_id.short());
+        if let Some(period) = &envelope.period {
+            name = format!("{}.{}", name, period);
+        }
+        if let Some(part) = envelope.part {
+            name = format!("{}.part{}", name, part);
+        }
+        format!("{}.{}", name, exporter.extension())
+    }
+
+    /// Serializes the export with `exporter`, named as [`ExportFile::name`] says.
+    pub fn render(exporter: &dyn Exporter, envelope: &Envelope) -> Result<Self> {
+        let mut bytes = Vec::new();
+        exporter.write(envelope, &mut bytes)?;
+        Ok(ExportFile {
+            name: Self::name(exporter, envelope),
+            content_type: exporter.content_type().to_string(),
+            bytes,
+        })
+    }
+
+    /// How many bytes [`ExportFile::render`] would produce, without keeping them.
+    fn rendered_size(exporter: &dyn Exporter, envelope: &Envelope) -> Result<usize> {
+        let mut counter = Counter(0);
+        exporter.write(envelope, &mut counter)?;
+        Ok(counter.0)
+    }
+
+    /// The numbered parts (`<date>.<run>.part1.<ext>`, ...) `envelope` is split into whenever
+    /// the whole export would exceed `max_size` bytes, or just `envelope`. A single message
+    /// larger than the limit still gets a part of its own. Nothing is rendered to find out.
+    pub fn parts<'a>(
+        exporter: &dyn Exporter,
+        envelope: &Envelope<'a>,
+        max_size: Option<ByteSize>,
+    ) -> Result<Vec<Envelope<'a>>> {
+        let messages = envelope.messages.messages;
+        let max_size = match max_size {
+            Some(ByteSize(max)) if Self::rendered_size(exporter, envelope)? as u64 > max => {
+                max as usize
+            }
+            _ => return Ok(vec![envelope.with_messages(messages)]),
+        };
+
+        let empty = Self::rendered_size(exporter, &envelope.with_messages(&[]))?;
+        // Room for the part counters added below.
+        let overhead = empty + 32;
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let mut size = overhead;
+        for (i, message) in messages.iter().enumerate() {
+            let single = envelope.with_messages(std::slice::from_ref(message));
+            let message_size = Self::rendered_size(exporter, &single)? - empty + 1;
+            if size + message_size > max_size && i > start {
+                chunks.push(&messages[start..i]);
+                start = i;
+                size = overhead;
+            }
+            if overhead + message_size > max_size {
+                log::warn!(
+                    "Message of {} bytes exceeds the export size limit",
+                    message_size
+                );
+            }
+            size += message_size;
+        }
+        chunks.push(&messages[start..]);
+
+        let parts = chunks.len();
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut part = envelope.with_messages(chunk);
+                part.part = Some(i + 1);
+                part.parts = Some(parts);
+                part
+            })
+            .collect())
+    }
+
+    /// Like [`ExportFile::parts`], but with a file, or its parts, per `partition` period the
+    /// messages were sent in. The messages of a period must be next to each other, see
+    /// [`sort`].
+    pub fn partitions<'a>(
+        exporter: &dyn Exporter,
+        envelope: &Envelope<'a>,
+        partition: Option<Partition>,
+        max_size: Option<ByteSize>,
+    ) -> Result<Vec<Envelope<'a>>> {
+        let messages = envelope.messages.messages;
+        let Some(partition) = partition.filter(|_| !messages.is_empty()) else {
+            return Self::parts(exporter, envelope, max_size);
+        };
+        let mut envelopes = Vec::new();
+        let same_period =
+            |a: &Message, b: &Message| partition.period(&a.date) == partition.period(&b.date);
+        for chunk in messages.chunk_by(same_period) {
+            let mut period = envelope.with_messages(chunk);
+            period.period = Some(partition.period(&chunk[0].date));
+            envelopes.extend(Self::parts(exporter, &period, max_size)?);
+        }
+        Ok(envelopes)
+    }
+
+    /// The files of [`ExportFile::parts`], rendered.
+    pub fn render_parts(
+        exporter: &dyn Exporter,
+        envelope: &Envelope,
+        max_size: Option<ByteSize>,
+    ) -> Result<Vec<Self>> {
+        Self::parts(exporter, envelope, max_size)?
+            .iter()
+            .map(|part| Self::render(exporter, part))
+            .collect()
+    }
+
+    /// The files of [`ExportFile::partitions`], rendered.
+    pub fn render_partitioned(
+        exporter: &dyn Exporter,
+        envelope: &Envelope,
+        partition: Option<Partition>,
+        max_size: Option<ByteSize>,
+    ) -> Result<Vec<Self>> {
+        Self::partitions(exporter, envelope, partition, max_size)?
+            .iter()
+            .map(|part| Self::render(exporter, part))
+            .collect()
+    }
+
+    /// Serializes the export of `envelope` with `exporter` straight into a file in `dir`,
+    /// encrypted with `encryption` if set, named and handling an existing file as
+    /// [`ExportFile::write_to`] would for the rendered and sealed file, so the export is never
+    /// held in memory as a whole. `None` if it was skipped.
+    pub fn stream_to(
+        exporter: &dyn Exporter,
+        envelope: &Envelope,
+        dir: &Path,
+        on_collision: Option<Collision>,
+        encryption: Option<&EncryptionConfig>,
+    ) -> Result<Option<PathBuf>> {
+        let mut name = Self::name(exporter, envelope);
+        if encryption.is_some() {
+            name.push_str(encryption::SUFFIX);
+        }
+        let Some(path) = destination(dir, &name, on_collision, envelope.run_id)? else {
+            return Ok(None);
+        };
+        let mut writer = BufWriter::new(fs::File::create(&path)?);
+        match encryption {
+            Some(encryption) => {
+                encryption.seal_into(&mut writer, |out| exporter.write(envelope, out))?;
+            }
+            None => exporter.write(envelope, &mut writer)?,
+        }
+        writer.flush()?;
+        Ok(Some(path))
+    }
+
+    /// The export name with its extension replaced, for files that accompany it.
+    pub fn sibling_name(&self, suffix: &str) -> String {
+        let stem = Path::new(&self.name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.name.clone());
+        format!("{}.{}", stem, suffix)
+    }
+
+    /// Writes the file of run `run_id` into `dir`, handling an existing file of the same name
+    /// as `on_collision` says, see [`Collision::apply`]. `None` if it was skipped.
+    pub fn write_to(
+        &self,
+        dir: &Path,
+        on_collision: Option<Collision>,
+        run_id: RunId,
+    ) -> Result<Option<PathBuf>> {
+        let Some(path) = destination(dir, &self.name, on_collision, run_id)? else {
+            return Ok(None);
+        };
+        fs::write(&path, &self.bytes)?;
+        Ok(Some(path))
+    }
+}
+
+/// Where in `dir` to write the file rendered as `name` by run `run_id`, creating `dir`; `None`
+/// to skip it, see [`Collision::apply`].
+fn destination(
+    dir: &Path,
+    name: &str,
+    on_collision: Option<Collision>,
+    run_id: RunId,
+) -> Result<Option<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let (collision, name) = Collision::apply(on_collision, name, run_id);
+    let name = collision.resolve(&name, |name| dir.join(name).try_exists())?;
+    Ok(name.map(|name| dir.join(name)))
+}
@@ -0,0 +1,121 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::TextStrategy;
+use crate::{media, Message, Result};
+
+#[derive(Deserialize, Clone)]
+pub struct ObsidianConfig {
+    /// Folder inside the vault that receives one note per pinned message.
+    pub dir: PathBuf,
+}
+
+#[derive(Serialize)]
+struct FrontMatter<'a> {
+    chat: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    sender: &'a str,
+    date: &'a str,
+    message_id: i32,
+    tags: Vec<String>,
+}
+
+/// Hashtags in the text, without the `#`, in the order they first appear.
+fn hashtags(text: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    for word in text.split_whitespace() {
+        let Some(tag) = word.strip_prefix('#') else {
+            continue;
+        };
+        let tag: String = tag
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+            .collect();
+        // Obsidian does not accept purely numeric tags.
+        if tag.chars().any(|c| !c.is_ascii_digit()) && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+fn note_name(message: &Message) -> String {
+    let chat: String = message
+        .chat_label()
+        .chars()
+        .filter(|c| !matches!(c, '/' | '\\' | ':' | '[' | ']' | '#' | '^' | '|'))
+        .collect();
+    format!("{} {} {}.md", message.date, chat, message.id)
+}
+
+/// Copies a downloaded media file into the vault's `attachments` folder and returns the name
+/// to embed, so the wiki-link resolves no matter where the media directory lives.
+fn attach(file: &str, attachments: &Path) -> Result<String> {
+    let source = Path::new(file);
+    let name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file.to_string());
+    let target = attachments.join(&name);
+    if !target.exists() {
+        fs::create_dir_all(attachments)?;
+        fs::copy(source, &target)?;
+    }
+    Ok(name)
+}
+
+fn note(message: &Message, strategy: TextStrategy, attachments: &Path) -> Result<String> {
+    let text = strategy.normalize(&message.text);
+    let sender = strategy.normalize(&message.sender);
+    let front_matter = FrontMatter {
+        chat: message.chat_label(),
+        sender: &sender,
+        date: &message.date,
+        message_id: message.id,
+        tags: hashtags(&text),
+    };
+    let mut note = format!("---\n{}---\n\n", serde_yaml::to_string(&front_matter)?);
+    if let Some(quote) = &message.quote {
+        for line in strategy.normalize(&quote.text).lines() {
+            note.push_str(&format!("> {}\n", line));
+        }
+        note.push('\n');
+    }
+    note.push_str(&text);
+    note.push('\n');
+    if let Some(media) = &message.media {
+        match &media.file {
+            Some(file) => note.push_str(&format!("\n![[{}]]\n", attach(file, attachments)?)),
+            None => note.push_str(&format!("\n{}\n", media::placeholder(media))),
+        }
+    }
+    if let Some(sticker) = &message.sticker {
+        match &sticker.file {
+            Some(file) => note.push_str(&format!("\n![[{}]]\n", attach(file, attachments)?)),
+            None if text.is_empty() => {
+                note.push_str(&format!("{}\n", strategy.normalize(&sticker.emoji)))
+            }
+            None => {}
+        }
+    }
+    Ok(note)
+}
+
+/// Writes one Markdown note per message into `config.dir`, replacing notes from earlier runs.
+/// Text is rendered with `strategy`, see `export.text`.
+pub fn write_vault(
+    config: &ObsidianConfig,
+    strategy: TextStrategy,
+    messages: &[Message],
+) -> Result<usize> {
+    fs::create_dir_all(&config.dir)?;
+    let attachments = config.dir.join("attachments");
+    for message in messages {
+        fs::write(
+            config.dir.join(note_name(message)),
+            note(message, strategy, &attachments)?,
+        )?;
+    }
+    Ok(messages.len())
+}
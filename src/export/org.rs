@@ -0,0 +1,118 @@
+use chrono::NaiveDate;
+use std::io::Write;
+
+use super::{Envelope, Exporter, TextStrategy};
+use crate::{media, Message, Result};
+
+/// One heading per chat with a `TODO` entry per pin, scheduled on the day it was sent, so
+/// the file can be added to `org-agenda-files` as is.
+pub struct OrgExporter {
+    pub text: TextStrategy,
+}
+
+fn headline(message: &Message, text: &str, strategy: TextStrategy) -> String {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .or_else(|| {
+            message
+                .sticker
+                .as_ref()
+                .map(|s| strategy.normalize(&s.emoji))
+        })
+        .or_else(|| message.media.as_ref().map(media::placeholder))
+        .filter(|headline| !headline.is_empty())
+        .unwrap_or_else(|| "Pinned message".to_string())
+}
+
+fn timestamp(date: &str) -> String {
+    match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(day) => day.format("<%Y-%m-%d %a>").to_string(),
+        Err(_) => format!("<{}>", date),
+    }
+}
+
+fn write_entry(message: &Message, strategy: TextStrategy, out: &mut dyn Write) -> Result<()> {
+    let text = strategy.normalize(&message.text);
+    writeln!(out, "** TODO {}", headline(message, &text, strategy))?;
+    writeln!(out, "   SCHEDULED: {}", timestamp(&message.date))?;
+    writeln!(out, "   :PROPERTIES:")?;
+    if !message.sender.is_empty() {
+        writeln!(out, "   :SENDER:   {}", strategy.normalize(&message.sender))?;
+    }
+    writeln!(out, "   :DATE:     {}", message.date)?;
+    if let Some(sticker) = &message.sticker {
+        if let Some(set_name) = &sticker.set_name {
+            writeln!(out, "   :STICKER_SET: {}", set_name)?;
+        }
+        if let Some(file) = &sticker.file {
+            writeln!(out, "   :STICKER_FILE: {}", file)?;
+        }
+    }
+    if let Some(file) = message.media.as_ref().and_then(|media| media.file.as_ref()) {
+        writeln!(out, "   :MEDIA_FILE: {}", file)?;
+    }
+    if let Some(signature) = message.media.as_ref().and_then(|m| m.quarantined.as_ref()) {
+        writeln!(out, "   :QUARANTINED: {}", signature)?;
+    }
+    if let Some(id) = message.quote.as_ref().and_then(|quote| quote.message_id) {
+        writeln!(out, "   :QUOTE_OF: {}", id)?;
+    }
+    writeln!(out, "   :END:")?;
+    if let Some(quote) = &message.quote {
+        writeln!(out, "   #+begin_quote")?;
+        for line in strategy.normalize(&quote.text).lines() {
+            writeln!(out, "   {}", line)?;
+        }
+        writeln!(out, "   #+end_quote")?;
+    }
+    // Indented, so lines starting with `*` are not mistaken for headings.
+    for line in text.lines() {
+        match line.is_empty() {
+            true => writeln!(out)?,
+            false => writeln!(out, "   {}", line)?,
+        }
+    }
+    Ok(())
+}
+
+impl Exporter for OrgExporter {
+    fn extension(&self) -> &'static str {
+        "org"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/org"
+    }
+
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()> {
+        writeln!(out, "#+TITLE: Pinned Telegram messages")?;
+        writeln!(out, "#+DATE: {}", timestamp(&export.generated_at[..10]))?;
+        if let (Some(part), Some(parts)) = (export.part, export.parts) {
+            writeln!(out, "#+SUBTITLE: Part {} of {}", part, parts)?;
+        }
+
+        let mut chats: Vec<String> = export.chats.iter().map(|c| c.name.clone()).collect();
+        for message in export.messages.iter() {
+            if !chats.contains(&message.chat) {
+                chats.push(message.chat.clone());
+            }
+        }
+        for chat in &chats {
+            let mut messages = export
+                .messages
+                .iter()
+                .filter(|m| &m.chat == chat)
+                .peekable();
+            let Some(first) = messages.peek() else {
+                continue;
+            };
+            writeln!(out, "\n* {}", first.chat_label())?;
+            for message in messages {
+                write_entry(message, self.text, out)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,350 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::TextStrategy;
+use crate::state::{self, StateError, Versioned};
+use crate::{media, ChatInfo, Message, Result};
+
+/// Every pin the site was built from, kept in the site itself so later runs can add to it.
+const DATA_FILE: &str = "pins.json";
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:46rem;margin:2rem auto;\
+padding:0 1rem;line-height:1.5;color:#222}a{color:#0a5fb4}header a{text-decoration:none}\
+article{border-top:1px solid #ddd;padding:1rem 0}.meta{color:#666;font-size:.9rem}\
+.text{white-space:pre-wrap}img,video{max-width:100%;max-height:24rem}nav{margin:1.5rem 0}\
+nav a,nav strong{margin-right:.6rem}input{width:100%;padding:.5rem;font-size:1rem}\
+blockquote{margin:0 0 .5rem;padding-left:.8rem;border-left:3px solid #ccc;color:#555}\
+.avatar{width:1.6rem;height:1.6rem;border-radius:50%;vertical-align:middle;margin-right:.4rem}";
+
+const SEARCH_SCRIPT: &str = r#"<script>
+const input = document.getElementById("search"), results = document.getElementById("results");
+let pins = null;
+input.addEventListener("input", async () => {
+  pins = pins || await (await fetch("search.json")).json();
+  const words = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+  results.replaceChildren();
+  if (!words.length) return;
+  const hits = pins.filter(p => words.every(w => (p.text + " " + p.sender + " " + p.chat).toLowerCase().includes(w)));
+  for (const pin of hits.slice(0, 50)) {
+    const li = document.createElement("li"), a = document.createElement("a");
+    a.href = pin.url;
+    a.textContent = pin.date + " " + pin.chat + " " + pin.sender + ": " + pin.text.slice(0, 120);
+    li.append(a);
+    results.append(li);
+  }
+});
+</script>"#;
+
+fn default_title() -> String {
+    "Pinned messages".to_string()
+}
+
+fn default_page_size() -> usize {
+    50
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SiteConfig {
+    /// Root of the generated site, e.g. the folder published to GitHub Pages.
+    pub dir: PathBuf,
+    #[serde(default = "default_title")]
+    pub title: String,
+    /// Pins per page of a chat.
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SiteData {
+    chats: Vec<ChatInfo>,
+    messages: Vec<Message>,
+}
+
+impl Versioned for SiteData {
+    const VERSION: u32 = 1;
+
+    fn migrate(_from: u32, _value: Value) -> Result<Value> {
+        // The site always tags its data, so an untagged file was put there by something else.
+        Err(Box::new(StateError(
+            "not site data of this tool, it has no version".to_string(),
+        )))
+    }
+}
+
+#[derive(Serialize)]
+struct SearchEntry<'a> {
+    chat: &'a str,
+    sender: &'a str,
+    date: &'a str,
+    text: &'a str,
+    url: String,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Directory name of a chat's pages.
+fn slug(chat: &str) -> String {
+    chat.trim_start_matches('@')
+        .chars()
+        .map(|c| match c.is_alphanumeric() || c == '-' || c == '_' {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+/// Pages are numbered from the oldest pins on, so new pins only change the last ones.
+fn page_name(page: usize) -> String {
+    format!("page-{}.html", page)
+}
+
+/// Writes `contents` unless the file already has them, so unchanged pages keep their
+/// modification time and deploys only push what changed. Returns whether it was written.
+fn write_if_changed(path: &Path, contents: &[u8]) -> Result<bool> {
+    if fs::read(path).is_ok_and(|old| old == contents) {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+fn html(site_title: &str, title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<link rel=\"stylesheet\" href=\"{}style.css\">\n</head>\n<body>\n\
+         <header><a href=\"{}index.html\">{}</a></header>\n<h1>{}</h1>\n{}</body>\n</html>\n",
+        escape(title),
+        root,
+        root,
+        escape(site_title),
+        escape(title),
+        body
+    )
+}
+
+/// Copies a downloaded file into the site's `media` folder and returns its URL from a chat
+/// page.
+fn publish_media(file: &str, site_dir: &Path) -> Result<Option<String>> {
+    let source = Path::new(file);
+    let Some(name) = source.file_name() else {
+        return Ok(None);
+    };
+    let target = site_dir.join("media").join(name);
+    if !target.exists() {
+        if !source.exists() {
+            return Ok(None);
+        }
+        fs::create_dir_all(site_dir.join("media"))?;
+        fs::copy(source, &target)?;
+    }
+    Ok(Some(format!("../media/{}", name.to_string_lossy())))
+}
+
+fn embed(url: &str, preview: Option<&str>) -> String {
+    let extension = url.rsplit('.').next().unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "mp4" | "webm" => match preview {
+            Some(preview) => format!(
+                "<video src=\"{}\" poster=\"{}\" controls></video>",
+                escape(url),
+                escape(preview)
+            ),
+            None => format!("<video src=\"{}\" controls></video>", escape(url)),
+        },
+        "jpg" | "jpeg" | "png" | "webp" | "gif" => format!(
+            "<a href=\"{}\"><img src=\"{}\" alt=\"\" loading=\"lazy\"></a>",
+            escape(url),
+            escape(preview.unwrap_or(url))
+        ),
+        _ => format!("<a href=\"{}\">Download</a>", escape(url)),
+    }
+}
+
+fn article(message: &Message, strategy: TextStrategy, site_dir: &Path) -> Result<String> {
+    let avatar = match &message.sender_photo {
+        Some(photo) => publish_media(photo, site_dir)?,
+        None => None,
+    };
+    let avatar = avatar
+        .map(|url| format!("<img class=\"avatar\" src=\"{}\" alt=\"\">", escape(&url)))
+        .unwrap_or_default();
+    let link = message
+        .link()
+        .map(|link| format!(" · <a href=\"{}\">Open in Telegram</a>", escape(&link)))
+        .unwrap_or_default();
+    let sender = match message.sender.is_empty() {
+        true => String::new(),
+        false => format!("{} · ", escape(&strategy.normalize(&message.sender))),
+    };
+    let mut out = format!(
+        "<article id=\"pin-{}\">\n<div class=\"meta\">{}{}{}{}</div>\n",
+        message.id,
+        avatar,
+        sender,
+        escape(&message.date),
+        link
+    );
+    if let Some(quote) = &message.quote {
+        let quoted = escape(&strategy.normalize(&quote.text));
+        out.push_str(&format!(
+            "<blockquote class=\"text\">{}</blockquote>\n",
+            quoted
+        ));
+    }
+    let text = strategy.normalize(&message.text);
+    if !text.is_empty() {
+        out.push_str(&format!("<div class=\"text\">{}</div>\n", escape(&text)));
+    }
+    if let Some(sticker) = &message.sticker {
+        let url = match &sticker.file {
+            Some(file) => publish_media(file, site_dir)?,
+            None => None,
+        };
+        match url {
+            Some(url) => out.push_str(&format!("{}\n", embed(&url, None))),
+            None if text.is_empty() => {
+                out.push_str(&format!("<div>{}</div>\n", escape(&sticker.emoji)))
+            }
+            None => {}
+        }
+    }
+    if let Some(info) = &message.media {
+        let url = match &info.file {
+            Some(file) => publish_media(file, site_dir)?,
+            None => None,
+        };
+        let preview = match &info.thumbnail {
+            Some(thumbnail) => publish_media(thumbnail, site_dir)?,
+            None => None,
+        };
+        match url {
+            Some(url) => out.push_str(&format!("{}\n", embed(&url, preview.as_deref()))),
+            None => out.push_str(&format!(
+                "<div>{}</div>\n",
+                escape(&media::placeholder(info))
+            )),
+        }
+    }
+    out.push_str("</article>\n");
+    Ok(out)
+}
+
+fn navigation(pages: usize, current: Option<usize>) -> String {
+    let mut nav = String::from("<nav>Pages: ");
+    for page in 1..=pages {
+        match Some(page) == current {
+            true => nav.push_str(&format!("<strong>{}</strong>", page)),
+            false => nav.push_str(&format!("<a href=\"{}\">{}</a>", page_name(page), page)),
+        }
+    }
+    nav.push_str("</nav>\n");
+    nav
+}
+
+/// Adds `messages` to the static site in `config.dir` and regenerates it: an index of the
+/// chats with a search box over `search.json`, and per chat an index showing the newest pins
+/// plus numbered pages of `page_size` pins. Pins from earlier runs are kept in `pins.json`;
+/// files whose contents did not change are left alone. Returns how many files were written.
+pub fn write_site(
+    config: &SiteConfig,
+    strategy: TextStrategy,
+    chats: &[ChatInfo],
+    messages: &[Message],
+) -> Result<usize> {
+    let data_path = config.dir.join(DATA_FILE);
+    fs::create_dir_all(&config.dir)?;
+    let mut data: SiteData = state::load(&data_path)?;
+    super::merge(&mut data.chats, &mut data.messages, chats, messages);
+
+    let page_size = config.page_size.max(1);
+    let mut written = 0;
+    let mut index = String::from(
+        "<input id=\"search\" type=\"search\" placeholder=\"Search pins\">\n<ul id=\"results\"></ul>\n<h2>Chats</h2>\n<ul>\n",
+    );
+    let mut search = Vec::new();
+    for chat in &data.chats {
+        let pins: Vec<&Message> = data
+            .messages
+            .iter()
+            .filter(|message| message.chat == chat.name)
+            .collect();
+        if pins.is_empty() {
+            continue;
+        }
+        let slug = slug(chat.alias.as_ref().unwrap_or(&chat.name));
+        let title = chat
+            .alias
+            .clone()
+            .or_else(|| chat.title.clone())
+            .unwrap_or_else(|| chat.name.clone());
+        let pages = pins.len().div_ceil(page_size);
+        index.push_str(&format!(
+            "<li><a href=\"{}/index.html\">{}</a> <span class=\"meta\">{} pins, latest {}</span></li>\n",
+            slug,
+            escape(&title),
+            pins.len(),
+            escape(&pins[pins.len() - 1].date)
+        ));
+
+        for (number, chunk) in pins.chunks(page_size).enumerate() {
+            let page = number + 1;
+            let mut body = navigation(pages, Some(page));
+            // Newest first within a page.
+            for message in chunk.iter().rev() {
+                body.push_str(&article(message, strategy, &config.dir)?);
+                search.push(SearchEntry {
+                    chat: message.chat_label(),
+                    sender: &message.sender,
+                    date: &message.date,
+                    text: &message.text,
+                    url: format!("{}/{}#pin-{}", slug, page_name(page), message.id),
+                });
+            }
+            body.push_str(&navigation(pages, Some(page)));
+            let page_title = format!("{}, page {}", title, page);
+            let contents = html(&config.title, &page_title, "../", &body);
+            let path = config.dir.join(&slug).join(page_name(page));
+            written += usize::from(write_if_changed(&path, contents.as_bytes())?);
+        }
+
+        let mut body = String::new();
+        if let Some(description) = &chat.description {
+            body.push_str(&format!("<p>{}</p>\n", escape(description)));
+        }
+        body.push_str(&navigation(pages, None));
+        for message in pins.iter().rev().take(page_size) {
+            body.push_str(&article(message, strategy, &config.dir)?);
+        }
+        let contents = html(&config.title, &title, "../", &body);
+        let path = config.dir.join(&slug).join("index.html");
+        written += usize::from(write_if_changed(&path, contents.as_bytes())?);
+    }
+    index.push_str("</ul>\n");
+    index.push_str(SEARCH_SCRIPT);
+    index.push('\n');
+
+    let files = [
+        (
+            "index.html",
+            html(&config.title, &config.title, "", &index).into_bytes(),
+        ),
+        ("style.css", STYLE.as_bytes().to_vec()),
+        ("search.json", serde_json::to_vec(&search)?),
+    ];
+    for (name, contents) in files {
+        written += usize::from(write_if_changed(&config.dir.join(name), &contents)?);
+    }
+    state::save(&data_path, &data)?;
+    Ok(written)
+}
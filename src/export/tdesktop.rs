@@ -0,0 +1,209 @@
+use serde_derive::Serialize;
+use serde_json::Value;
+use std::io::Write;
+
+use super::{Envelope, Exporter};
+use crate::media::MediaType;
+use crate::{ChatInfo, Message, Result};
+
+/// What Telegram Desktop writes in place of a file it did not download.
+const NOT_INCLUDED: &str = "(File not included. Change data exporting settings to download.)";
+
+/// The layout of Telegram Desktop's "Export chat history" in machine-readable JSON, so
+/// viewers and analysis scripts made for its `result.json` read the pins as they are: one
+/// chat object for an export of a single chat, the `chats` list of an account export
+/// otherwise.
+///
+/// Only the pins are written, without the service messages of their pinning, and without
+/// `from_id`, which would give away the senders of anonymized exports.
+pub struct TdesktopExporter;
+
+#[derive(Serialize)]
+struct Account<'a> {
+    about: &'static str,
+    chats: ChatList<'a>,
+}
+
+#[derive(Serialize)]
+struct ChatList<'a> {
+    about: &'static str,
+    list: Vec<Chat<'a>>,
+}
+
+#[derive(Serialize)]
+struct Chat<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: i64,
+    messages: Vec<DesktopMessage<'a>>,
+}
+
+#[derive(Serialize)]
+struct TextEntity<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+struct DesktopMessage<'a> {
+    id: i32,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    date: String,
+    date_unixtime: String,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    from: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to_message_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    photo: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    photo_file_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    media_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sticker_emoji: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mime_type: Option<&'a str>,
+    text: &'a str,
+    text_entities: Vec<TextEntity<'a>>,
+}
+
+/// Whether `value` is a file of this format rather than of the json one, which shares its
+/// extension: a chat object has a `type`, an account export a `chats` object, and neither is
+/// found in an envelope.
+pub fn is_tdesktop(value: &Value) -> bool {
+    value.get("type").is_some_and(Value::is_string)
+        || value.get("chats").is_some_and(Value::is_object)
+}
+
+/// The kind of chat, told apart as far as the export knows: only channel posts have views.
+fn chat_kind(info: Option<&ChatInfo>, messages: &[&Message]) -> &'static str {
+    let public = info.is_some_and(|info| info.username.is_some());
+    match (messages.iter().any(|m| m.views.is_some()), public) {
+        (true, true) => "public_channel",
+        (true, false) => "private_channel",
+        (false, true) => "public_supergroup",
+        (false, false) => "private_supergroup",
+    }
+}
+
+fn desktop_message(message: &Message) -> DesktopMessage<'_> {
+    let sent_at = chrono::DateTime::from_timestamp(message.sent_at(), 0).unwrap_or_default();
+    let mut out = DesktopMessage {
+        id: message.id,
+        kind: "message",
+        // Desktop writes the local time of the exporting machine; exports are in UTC.
+        date: sent_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        date_unixtime: sent_at.timestamp().to_string(),
+        from: &message.sender,
+        reply_to_message_id: message
+            .quote
+            .as_ref()
+            .filter(|quote| quote.chat_id.is_none())
+            .and_then(|quote| quote.message_id),
+        photo: None,
+        photo_file_size: None,
+        file: None,
+        file_name: None,
+        file_size: None,
+        thumbnail: None,
+        media_type: None,
+        sticker_emoji: None,
+        mime_type: None,
+        text: &message.text,
+        text_entities: Vec::new(),
+    };
+    if !message.text.is_empty() {
+        out.text_entities.push(TextEntity {
+            kind: "plain",
+            text: &message.text,
+        });
+    }
+    if let Some(sticker) = &message.sticker {
+        out.file = Some(sticker.file.as_deref().unwrap_or(NOT_INCLUDED));
+        out.file_size = sticker.size;
+        out.media_type = Some("sticker");
+        out.sticker_emoji = Some(&sticker.emoji);
+    }
+    if let Some(media) = &message.media {
+        let file = Some(media.file.as_deref().unwrap_or(NOT_INCLUDED));
+        match media.kind {
+            MediaType::Photo => {
+                out.photo = file;
+                out.photo_file_size = media.size;
+            }
+            kind => {
+                out.file = file;
+                out.file_name = media.file_name.as_deref();
+                out.file_size = media.size;
+                out.thumbnail = media.thumbnail.as_deref();
+                out.mime_type = media.mime_type.as_deref();
+                out.media_type = match kind {
+                    MediaType::Video => Some("video_file"),
+                    MediaType::Audio => Some("audio_file"),
+                    _ => None,
+                };
+            }
+        }
+    }
+    out
+}
+
+impl Exporter for TdesktopExporter {
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()> {
+        let mut names: Vec<&str> = export.chats.iter().map(|c| c.name.as_str()).collect();
+        for message in export.messages.iter() {
+            if !names.contains(&message.chat.as_str()) {
+                names.push(&message.chat);
+            }
+        }
+        let mut chats = Vec::new();
+        for name in names {
+            let messages: Vec<&Message> =
+                export.messages.iter().filter(|m| m.chat == name).collect();
+            let info = export.chats.iter().find(|c| c.name == name);
+            chats.push(Chat {
+                name: info
+                    .and_then(|info| info.alias.as_deref().or(info.title.as_deref()))
+                    .or(messages.first().map(|m| m.chat_label()))
+                    .unwrap_or(name),
+                kind: chat_kind(info, &messages),
+                id: info.map_or(0, |info| info.id),
+                messages: messages.into_iter().map(desktop_message).collect(),
+            });
+        }
+        match chats.len() {
+            1 => serde_json::to_writer_pretty(out, &chats[0])?,
+            _ => serde_json::to_writer_pretty(
+                out,
+                &Account {
+                    about: "Pinned messages exported by telegram-pin-fetcher.",
+                    chats: ChatList {
+                        about: "This is the list of chats the pins were exported from.",
+                        list: chats,
+                    },
+                },
+            )?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use serde_derive::Deserialize;
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use tera::{Context, Tera};
+
+use super::{Envelope, Exporter};
+use crate::Result;
+
+fn default_extension() -> String {
+    "txt".to_string()
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TemplateConfig {
+    /// Tera template file. Names ending in `.html`, `.htm` or `.xml` get their
+    /// values escaped.
+    pub path: PathBuf,
+    /// Extension of the export files, without the dot; also decides their content type.
+    #[serde(default = "default_extension")]
+    pub extension: String,
+}
+
+#[derive(Debug)]
+pub struct TemplateError(String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Tera keeps the cause of an error in its source chain, not in its message.
+fn describe(error: &tera::Error) -> String {
+    let mut text = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        text.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    text
+}
+
+/// Renders the export through a user's Tera template, given the envelope's keys
+/// (`messages`, `chats`, `run_id`, `generated_at`, ...) as variables, with the messages
+/// holding the fields of `export.fields`.
+pub struct TemplateExporter {
+    tera: Tera,
+    name: String,
+    extension: String,
+}
+
+impl TemplateExporter {
+    /// Reads and compiles the template, so syntax errors are reported before any export is
+    /// rendered.
+    pub fn new(config: &TemplateConfig) -> Result<Self> {
+        let mut tera = Tera::default();
+        tera.add_template_file(&config.path, None).map_err(|e| {
+            TemplateError(format!(
+                "Invalid template {}: {}",
+                config.path.display(),
+                describe(&e)
+            ))
+        })?;
+        Ok(TemplateExporter {
+            tera,
+            name: config.path.display().to_string(),
+            extension: config.extension.trim_start_matches('.').to_string(),
+        })
+    }
+}
+
+impl Exporter for TemplateExporter {
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self.extension.to_lowercase().as_str() {
+            "html" | "htm" => "text/html",
+            "md" | "markdown" => "text/markdown",
+            "csv" => "text/csv",
+            "xml" => "application/xml",
+            "json" => "application/json",
+            _ => "text/plain",
+        }
+    }
+
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()> {
+        let context = Context::from_serialize(export)?;
+        self.tera
+            .render_to(&self.name, &context, out)
+            .map_err(|e| {
+                TemplateError(format!("Could not render {}: {}", self.name, describe(&e)))
+            })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+use serde_derive::Deserialize;
+use unicode_bidi::{bidi_class, BidiClass};
+
+/// How the plain-text formats (`org` and Obsidian notes) render emoji, invisible
+/// characters and right-to-left text.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TextStrategy {
+    /// Keep everything, but isolate right-to-left lines so they cannot reorder the markup
+    /// around them, and drop bidi overrides and zero-width spaces.
+    #[default]
+    Keep,
+    /// Remove emoji (including ZWJ sequences and custom emoji placeholders) and every
+    /// invisible formatting character.
+    Strip,
+    /// Plain ASCII: emoji become `:name:` and other scripts are transliterated.
+    Transliterate,
+}
+
+const ZWJ: char = '\u{200d}';
+const ZWNJ: char = '\u{200c}';
+/// First strong isolate and pop directional isolate.
+const FSI: char = '\u{2068}';
+const PDI: char = '\u{2069}';
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1f000..=0x1faff | 0x2600..=0x27bf | 0x2300..=0x23ff | 0x2b00..=0x2bff | 0x3030 | 0x303d)
+}
+
+/// Characters that only modify the emoji before them: variation selectors, skin tones,
+/// keycaps and tag sequences.
+fn is_emoji_modifier(c: char) -> bool {
+    matches!(c as u32, 0xfe0e | 0xfe0f | 0x1f3fb..=0x1f3ff | 0x20e3 | 0xe0020..=0xe007f)
+}
+
+/// Bidi embeddings, overrides and isolates, which could leak past the end of a line.
+fn is_bidi_control(c: char) -> bool {
+    matches!(c as u32, 0x202a..=0x202e | 0x2066..=0x2069 | 0x200e | 0x200f | 0x061c)
+}
+
+/// Zero-width spaces and byte order marks. Joiners are kept: Persian needs ZWNJ.
+fn is_invisible(c: char) -> bool {
+    matches!(c, '\u{200b}' | '\u{2060}' | '\u{feff}')
+}
+
+fn is_rtl(c: char) -> bool {
+    matches!(bidi_class(c), BidiClass::R | BidiClass::AL)
+}
+
+/// Splits `line` into text and emoji clusters (an emoji with its modifiers and any
+/// emoji joined to it with ZWJ).
+fn segments(line: &str) -> Vec<(bool, String)> {
+    let mut segments: Vec<(bool, String)> = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        // Keycaps like 1️⃣ start with a plain digit.
+        let keycap = matches!(c, '0'..='9' | '#' | '*')
+            && chars.peek().is_some_and(|&next| is_emoji_modifier(next));
+        let emoji = is_emoji(c) || keycap;
+        if !emoji {
+            match segments.last_mut() {
+                Some((false, text)) => text.push(c),
+                _ => segments.push((false, c.to_string())),
+            }
+            continue;
+        }
+        let mut cluster = c.to_string();
+        while let Some(&next) = chars.peek() {
+            let flag = cluster.chars().count() == 1 && is_regional(c, next);
+            if is_emoji_modifier(next) || flag {
+                cluster.push(next);
+                chars.next();
+            } else if next == ZWJ {
+                cluster.push(next);
+                chars.next();
+                if let Some(joined) = chars.next_if(|&joined| is_emoji(joined)) {
+                    cluster.push(joined);
+                }
+            } else {
+                break;
+            }
+        }
+        segments.push((true, cluster));
+    }
+    segments
+}
+
+/// The second half of a flag, e.g. 🇪🇸, which is two regional indicators.
+fn is_regional(first: char, next: char) -> bool {
+    let regional = |c: char| matches!(c as u32, 0x1f1e6..=0x1f1ff);
+    regional(first) && regional(next)
+}
+
+fn shortcode(emoji: &str) -> String {
+    let name = deunicode::deunicode(emoji);
+    let name: Vec<&str> = name.split_whitespace().collect();
+    match name.is_empty() {
+        true => String::new(),
+        false => format!(":{}:", name.join("_").to_lowercase()),
+    }
+}
+
+fn normalize_line(line: &str, strategy: TextStrategy) -> String {
+    let line: String = line
+        .chars()
+        .filter(|&c| !is_bidi_control(c) && !is_invisible(c))
+        .collect();
+    match strategy {
+        TextStrategy::Keep => match line.chars().any(is_rtl) {
+            true => format!("{}{}{}", FSI, line, PDI),
+            false => line,
+        },
+        TextStrategy::Strip => {
+            let mut out = String::new();
+            let mut after_emoji = false;
+            for (emoji, text) in segments(&line) {
+                after_emoji = emoji;
+                if emoji {
+                    continue;
+                }
+                let text: String = text.chars().filter(|&c| c != ZWJ && c != ZWNJ).collect();
+                // Removing an emoji between two words would leave a double space.
+                match out.is_empty() || out.ends_with(' ') {
+                    true => out.push_str(text.strip_prefix(' ').unwrap_or(&text)),
+                    false => out.push_str(&text),
+                }
+            }
+            match after_emoji {
+                true => out.trim_end().to_string(),
+                false => out,
+            }
+        }
+        TextStrategy::Transliterate => segments(&line)
+            .into_iter()
+            .map(|(emoji, text)| match emoji {
+                true => shortcode(&text),
+                false => deunicode::deunicode(&text),
+            })
+            .collect(),
+    }
+}
+
+impl TextStrategy {
+    /// `text` rendered with this strategy, line by line.
+    pub fn normalize(self, text: &str) -> String {
+        text.split('\n')
+            .map(|line| normalize_line(line, self))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emoji(line: &str) -> Vec<String> {
+        segments(line)
+            .into_iter()
+            .filter_map(|(emoji, text)| emoji.then_some(text))
+            .collect()
+    }
+
+    #[test]
+    fn zwj_sequences_are_one_cluster() {
+        // Family: man, woman, girl.
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        assert_eq!(emoji(&format!("hi {} there", family)), [family]);
+        assert_eq!(
+            segments(&format!("a{}b", family)),
+            [
+                (false, "a".to_string()),
+                (true, family.to_string()),
+                (false, "b".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn keycaps_and_skin_tones_keep_their_modifiers() {
+        let keycap = "1\u{fe0f}\u{20e3}";
+        let thumbs_up = "\u{1f44d}\u{1f3fd}";
+        assert_eq!(
+            emoji(&format!("{} step, {}", keycap, thumbs_up)),
+            [keycap, thumbs_up]
+        );
+        // A digit on its own is text.
+        assert!(emoji("room 12").is_empty());
+    }
+
+    #[test]
+    fn flags_pair_regional_indicators() {
+        let spain = "\u{1f1ea}\u{1f1f8}";
+        let france = "\u{1f1eb}\u{1f1f7}";
+        assert_eq!(emoji(&format!("{}{}", spain, france)), [spain, france]);
+    }
+
+    #[test]
+    fn keep_isolates_rtl_lines() {
+        let hebrew = "שלום world";
+        assert_eq!(
+            normalize_line(hebrew, TextStrategy::Keep),
+            format!("{}{}{}", FSI, hebrew, PDI)
+        );
+        assert_eq!(normalize_line("hello", TextStrategy::Keep), "hello");
+        // Overrides and zero-width spaces are dropped, so they cannot leak.
+        assert_eq!(
+            normalize_line("\u{202e}abc\u{200b}", TextStrategy::Keep),
+            "abc"
+        );
+    }
+
+    #[test]
+    fn strip_removes_emoji_without_double_spaces() {
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        assert_eq!(
+            normalize_line(&format!("the {} is home", family), TextStrategy::Strip),
+            "the is home"
+        );
+        assert_eq!(normalize_line("done \u{2705}", TextStrategy::Strip), "done");
+        // Persian keeps ZWNJ under Keep, but Strip removes every joiner.
+        assert_eq!(
+            normalize_line("می\u{200c}خواهم", TextStrategy::Strip),
+            "میخواهم"
+        );
+    }
+
+    #[test]
+    fn transliterate_names_emoji() {
+        assert_eq!(
+            normalize_line("café \u{1f600}", TextStrategy::Transliterate),
+            "cafe :grinning:"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use serde_derive::Serialize;
+use std::io::Write;
+
+use super::{Envelope, Exporter, Records};
+use crate::Result;
+
+/// TOML documents must be tables, so without an envelope the messages still live under a
+/// top-level `messages` array.
+#[derive(Serialize)]
+struct Bare<'a> {
+    messages: Records<'a>,
+}
+
+/// The line an envelope without messages has for them, dropped when they follow as tables.
+const EMPTY_MESSAGES: &str = "messages = []\n";
+
+pub struct TomlExporter {
+    pub envelope: bool,
+}
+
+impl Exporter for TomlExporter {
+    fn extension(&self) -> &'static str {
+        "toml"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/toml"
+    }
+
+    /// The toml crate only serializes whole documents into a string, so the metadata and
+    /// every message are serialized on their own, keeping just one message's text in memory.
+    /// Each message becomes a `[[messages]]` table, exactly as in a whole document.
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()> {
+        if export.messages.is_empty() {
+            let document = match self.envelope {
+                true => toml::to_string(export)?,
+                false => toml::to_string(&Bare {
+                    messages: export.messages,
+                })?,
+            };
+            out.write_all(document.as_bytes())?;
+            return Ok(());
+        }
+        if self.envelope {
+            let metadata = toml::to_string(&export.with_messages(&[]))?;
+            let metadata = metadata.replacen(EMPTY_MESSAGES, "", 1);
+            out.write_all(metadata.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+        for (i, message) in export.messages.iter().enumerate() {
+            let record = Bare {
+                messages: export.messages.with_messages(std::slice::from_ref(message)),
+            };
+            if i > 0 {
+                out.write_all(b"\n")?;
+            }
+            out.write_all(toml::to_string(&record)?.as_bytes())?;
+        }
+        Ok(())
+    }
+}
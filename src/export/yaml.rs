@@ -0,0 +1,26 @@
+use std::io::Write;
+
+use super::{Envelope, Exporter};
+use crate::Result;
+
+pub struct YamlExporter {
+    pub envelope: bool,
+}
+
+impl Exporter for YamlExporter {
+    fn extension(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/yaml"
+    }
+
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()> {
+        match self.envelope {
+            true => serde_yaml::to_writer(out, export)?,
+            false => serde_yaml::to_writer(out, &export.messages)?,
+        }
+        Ok(())
+    }
+}
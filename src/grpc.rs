@@ -0,0 +1,106 @@
+use futures_util::stream::{self, Stream};
+use std::pin::Pin as Boxed;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+
+use crate::api::{same_chat, LatestPins};
+use crate::shutdown::Shutdown;
+use crate::{Message, Result};
+
+/// Types generated from `proto/pins.proto`.
+pub mod proto {
+    tonic::include_proto!("pins");
+}
+
+use proto::pins_server::{Pins, PinsServer};
+
+impl From<Message> for proto::Pin {
+    fn from(message: Message) -> Self {
+        proto::Pin {
+            link: message.link().unwrap_or_default(),
+            id: message.id,
+            chat: message.chat,
+            sender: message.sender,
+            text: message.text,
+            date: message.date,
+            datetime: message.datetime,
+        }
+    }
+}
+
+/// An empty filter matches every chat.
+fn wanted(filter: &str, message: &Message) -> bool {
+    filter.is_empty() || same_chat(filter, &message.chat)
+}
+
+struct Service {
+    latest: LatestPins,
+    shutdown: Shutdown,
+}
+
+#[tonic::async_trait]
+impl Pins for Service {
+    async fn fetch_pins(
+        &self,
+        request: Request<proto::FetchPinsRequest>,
+    ) -> std::result::Result<Response<proto::FetchPinsResponse>, Status> {
+        let chat = request.into_inner().chat;
+        let pins = self
+            .latest
+            .messages()
+            .into_iter()
+            .filter(|m| wanted(&chat, m))
+            .map(proto::Pin::from)
+            .collect();
+        Ok(Response::new(proto::FetchPinsResponse { pins }))
+    }
+
+    type StreamNewPinsStream =
+        Boxed<Box<dyn Stream<Item = std::result::Result<proto::Pin, Status>> + Send>>;
+
+    async fn stream_new_pins(
+        &self,
+        request: Request<proto::StreamNewPinsRequest>,
+    ) -> std::result::Result<Response<Self::StreamNewPinsStream>, Status> {
+        let chat = request.into_inner().chat;
+        let receiver = self.latest.subscribe();
+        let state = (receiver, chat, self.shutdown.clone());
+        let pins = stream::unfold(state, |(mut receiver, chat, shutdown)| async move {
+            loop {
+                // Ends the stream, or the server would wait for the client to hang up.
+                let received = tokio::select! {
+                    received = receiver.recv() => received,
+                    _ = shutdown.wait() => return None,
+                };
+                match received {
+                    Ok(message) if wanted(&chat, &message) => {
+                        return Some((Ok(message.into()), (receiver, chat, shutdown)));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("A gRPC client fell behind, skipped {} pins", skipped);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(pins)))
+    }
+}
+
+/// Serves the gRPC service on `listener` until a shutdown is requested.
+pub async fn serve(listener: TcpListener, latest: LatestPins, shutdown: Shutdown) -> Result<()> {
+    println!("Serving gRPC on {}", listener.local_addr()?);
+    let incoming = TcpIncoming::from_listener(listener, true, None)
+        .map_err(|e| -> Box<dyn std::error::Error> { e })?;
+    tonic::transport::Server::builder()
+        .add_service(PinsServer::new(Service {
+            latest,
+            shutdown: shutdown.clone(),
+        }))
+        .serve_with_incoming_shutdown(incoming, async move { shutdown.wait().await })
+        .await?;
+    Ok(())
+}
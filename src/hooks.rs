@@ -0,0 +1,37 @@
+use std::fmt;
+use tokio::process::Command;
+
+use crate::Result;
+
+#[derive(Debug)]
+pub struct HookError(String);
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for HookError {}
+
+/// Runs each of `commands` with `sh -c`, in order, with `env` added to their environment.
+/// Stops at the first command that cannot be run or exits unsuccessfully.
+pub async fn run(commands: &[String], env: &[(&str, String)]) -> Result<()> {
+    for command in commands {
+        log::info!("Running hook {:?}", command);
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .envs(env.iter().map(|(name, value)| (name, value)))
+            .status()
+            .await
+            .map_err(|e| HookError(format!("Could not run hook {:?}: {}", command, e)))?;
+        if !status.success() {
+            return Err(Box::new(HookError(format!(
+                "Hook {:?} failed with {}",
+                command, status
+            ))));
+        }
+    }
+    Ok(())
+}
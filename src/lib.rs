@@ -0,0 +1,218 @@
+pub mod anonymize;
+pub mod api;
+pub mod cache;
+pub mod config;
+pub mod control;
+pub mod desktop_import;
+pub mod encryption;
+pub mod export;
+pub mod grpc;
+pub mod hooks;
+pub mod links;
+pub mod lock;
+pub mod media;
+pub mod plugin;
+pub mod raw;
+pub mod redact;
+pub mod reload;
+pub mod review;
+pub mod run;
+pub mod scan;
+pub mod script;
+pub mod search;
+pub mod seen;
+pub mod serve;
+pub mod session_import;
+pub mod shutdown;
+pub mod signing;
+pub mod state;
+pub mod stats;
+pub mod stickers;
+pub mod summary;
+pub mod synthetic;
+pub mod systemd;
+pub mod takeout;
+pub mod telegram;
+pub mod throttle;
+pub mod topics;
+pub mod translate;
+pub mod units;
+pub mod upload;
+
+use links::LinkSnapshot;
+use media::MediaInfo;
+use serde_derive::{Deserialize, Serialize};
+use stickers::StickerInfo;
+
+pub use export::{Exporter, ExporterRegistry};
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// A single pinned message, as it ends up in every export format.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Message {
+    /// Message ID, unique within its chat. Exports of older releases do not have it.
+    #[serde(default)]
+    pub id: i32,
+    /// The configured username of the chat the message was pinned in.
+    #[serde(default)]
+    pub chat: String,
+    /// The chat's `alias` from the config, shown in its place wherever people read it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_alias: Option<String>,
+    /// Telegram ID of the chat, which links to pins of private channels and supergroups
+    /// contain. Exports of older releases do not have it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<i64>,
+    /// What kind of chat `chat` is, which decides how its pins can be linked to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_kind: Option<ChatKind>,
+    /// Missing from exports that leave it out of `export.fields`.
+    #[serde(default)]
+    pub sender: String,
+    /// The sender's profile photo when the pins were fetched, with `media.profile_photos`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_photo: Option<String>,
+    /// Position of the pin in its chat's list of pins when it was fetched, 1 being the one
+    /// Telegram shows at the top. Exports of older releases do not have it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin_order: Option<u32>,
+    /// Telegram user ID of the sender, only kept in memory for `anonymize`.
+    #[serde(skip)]
+    pub sender_id: i64,
+    #[serde(default)]
+    pub text: String,
+    /// The UTC day the message was sent, `YYYY-MM-DD`.
+    #[serde(default)]
+    pub date: String,
+    /// When the message was sent in UTC, as RFC 3339. Exports of older releases only have
+    /// `date`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub datetime: String,
+    /// Views of a channel post when it was fetched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub views: Option<i32>,
+    /// Times a channel post had been forwarded when it was fetched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwards: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sticker: Option<StickerInfo>,
+    /// A pinned photo or file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<MediaInfo>,
+    /// The fragment a pinned reply quotes from the message it answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<QuoteInfo>,
+    /// Readable copies of the pages the text links to, with `[link_snapshots]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<LinkSnapshot>,
+    /// ISO 639-3 code of the text's language, when it could be detected reliably.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// The text translated by the `[translate]` service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_translated: Option<String>,
+    /// The whole Telegram message object, with `export.include_raw`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<serde_json::Value>,
+    /// Fields the `[script]` hook added, written after the selected ones.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Part of a message quoted by a reply, exactly as it was highlighted.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuoteInfo {
+    pub text: String,
+    /// ID of the quoted message, unless Telegram no longer knows it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<i32>,
+    /// ID of the chat the quoted message is in, when it is not the pin's own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<i64>,
+}
+
+/// The kinds of chats that [`Message::link`] tells apart.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatKind {
+    /// A channel or supergroup with a username, the `chat` of its messages.
+    Public,
+    /// A channel or supergroup without one, whose pins only its members can open.
+    Private,
+    /// A basic group; Telegram has no links to its messages.
+    Group,
+    /// A private chat with a user or bot, which has no links either.
+    User,
+}
+
+/// A chat as it was at export time, so archives stay readable after it is renamed.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ChatInfo {
+    /// The configured name that messages refer to in `chat`.
+    pub name: String,
+    /// Name given to the chat in the config, see [`Message::chat_alias`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    pub id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub members: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl Message {
+    /// Unix time the message was sent, from `datetime` or, for messages of older exports,
+    /// the start of its `date`.
+    pub fn sent_at(&self) -> i64 {
+        if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(&self.datetime) {
+            return datetime.timestamp();
+        }
+        chrono::NaiveDate::parse_from_str(&self.date, "%Y-%m-%d")
+            .ok()
+            .and_then(|day| day.and_hms_opt(0, 0, 0))
+            .map_or(i64::MIN, |start| start.and_utc().timestamp())
+    }
+
+    /// Sort key ordering messages by when they were sent, and messages of the same second by
+    /// chat and then ID, so pins of several chats sent together always come out the same way.
+    pub fn sort_key(&self) -> (i64, String, i32) {
+        (self.sent_at(), self.chat.clone(), self.id)
+    }
+
+    /// The chat as people should read it: its alias, or else the configured name.
+    pub fn chat_label(&self) -> &str {
+        self.chat_alias.as_deref().unwrap_or(&self.chat)
+    }
+
+    /// `t.me` link to the message, if Telegram has one: public chats are linked by username,
+    /// private channels and supergroups by ID, for their members. Messages of older exports,
+    /// which have no `chat_kind`, are taken to be of a public chat when `chat` is a username,
+    /// not a phone number or `contact:` name of a private chat.
+    pub fn link(&self) -> Option<String> {
+        if self.id == 0 {
+            return None;
+        }
+        let username = self.chat.trim_start_matches('@');
+        match self.chat_kind {
+            Some(ChatKind::Public) => {}
+            Some(ChatKind::Private) => {
+                return Some(format!("https://t.me/c/{}/{}", self.chat_id?, self.id));
+            }
+            Some(ChatKind::Group | ChatKind::User) => return None,
+            None if !is_username(username) => return None,
+            None => {}
+        }
+        Some(format!("https://t.me/{}/{}", username, self.id))
+    }
+}
+
+/// Whether `name` can be a Telegram username, rather than a phone number or a chat's title.
+fn is_username(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,280 @@
+use futures_util::StreamExt as _;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::state::{self, StateError, Versioned};
+use crate::units::ByteSize;
+use crate::{Message, Result};
+
+/// Elements whose text makes up an article, taken outermost only.
+const BLOCKS: &[&str] = &[
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "pre",
+    "blockquote",
+];
+
+/// Page chrome left out of the text.
+const CHROME: &[&str] = &[
+    "nav", "header", "footer", "aside", "form", "script", "style", "noscript",
+];
+
+fn default_max_size() -> ByteSize {
+    ByteSize(5 * 1024 * 1024)
+}
+
+fn default_max_text() -> usize {
+    50_000
+}
+
+fn default_timeout() -> u64 {
+    20
+}
+
+fn default_workers() -> usize {
+    4
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LinkSnapshotConfig {
+    /// Pages bigger than this are not read.
+    #[serde(default = "default_max_size")]
+    pub max_size: ByteSize,
+    /// Characters of extracted text kept per page.
+    #[serde(default = "default_max_text")]
+    pub max_text: usize,
+    /// Seconds one page may take to load.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// How many pages are fetched at the same time.
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+    /// Sent as `User-Agent`; some sites turn away the default one.
+    pub user_agent: Option<String>,
+}
+
+/// The readable part of a linked page as it was when first fetched.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LinkSnapshot {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub text: String,
+    /// When the page was fetched, RFC 3339.
+    pub fetched_at: String,
+}
+
+/// Every distinct `http(s)` link in `text`, in order, without the punctuation around it.
+pub fn urls(text: &str) -> Vec<&str> {
+    let pattern = Regex::new(r#"https?://[^\s<>"]+"#).expect("valid URL pattern");
+    let mut urls = Vec::new();
+    for found in pattern.find_iter(text) {
+        let mut url = found
+            .as_str()
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', '\'']);
+        // A closing parenthesis belongs to the link only if it opened one, as Wikipedia's do.
+        while url.ends_with(')') && url.matches('(').count() < url.matches(')').count() {
+            url = &url[..url.len() - 1];
+        }
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn is_within(element: ElementRef, names: &[&str]) -> bool {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .any(|ancestor| names.contains(&ancestor.value().name()))
+}
+
+/// The title and article text of an HTML page: the paragraphs, headings, list items and
+/// quotes of its `<article>` or `<main>`, or else of its body, without navigation and
+/// other chrome.
+fn extract(html: &str) -> (Option<String>, String) {
+    let document = Html::parse_document(html);
+    let select = |selector: &str| Selector::parse(selector).expect("valid selector");
+    let title = document
+        .select(&select(r#"meta[property="og:title"]"#))
+        .find_map(|meta| meta.value().attr("content"))
+        .map(collapse)
+        .or_else(|| {
+            document
+                .select(&select("title"))
+                .next()
+                .map(|title| collapse(&title.text().collect::<String>()))
+        })
+        .filter(|title| !title.is_empty());
+
+    let root = ["article", "main", r#"[role="main"]"#, "body"]
+        .iter()
+        .find_map(|selector| document.select(&select(selector)).next());
+    let Some(root) = root else {
+        return (title, String::new());
+    };
+    let blocks = select(&BLOCKS.join(", "));
+    let mut paragraphs = Vec::new();
+    for block in root.select(&blocks) {
+        if is_within(block, BLOCKS) || is_within(block, CHROME) {
+            continue;
+        }
+        let text: String = block.text().collect();
+        let text = match block.value().name() {
+            "pre" => text.trim_matches('\n').to_string(),
+            _ => collapse(&text),
+        };
+        if !text.is_empty() {
+            paragraphs.push(text);
+        }
+    }
+    if paragraphs.is_empty() {
+        paragraphs.push(collapse(&root.text().collect::<String>()));
+    }
+    (title, paragraphs.join("\n\n"))
+}
+
+async fn fetch(
+    http_client: &reqwest::Client,
+    config: &LinkSnapshotConfig,
+    url: &str,
+) -> Result<LinkSnapshot> {
+    let mut res = http_client.get(url).send().await?.error_for_status()?;
+    if res
+        .content_length()
+        .is_some_and(|len| len > config.max_size.0)
+    {
+        return Err(format!("bigger than {}", config.max_size).into());
+    }
+    let content_type = res
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("text/html")
+        .to_lowercase();
+    let html = content_type.contains("html");
+    if !html && !content_type.starts_with("text/plain") {
+        return Err(format!("not a web page ({})", content_type).into());
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = res.chunk().await? {
+        bytes.extend_from_slice(&chunk);
+        if bytes.len() as u64 > config.max_size.0 {
+            return Err(format!("bigger than {}", config.max_size).into());
+        }
+    }
+    let body = String::from_utf8_lossy(&bytes);
+    let (title, text) = match html {
+        true => extract(&body),
+        false => (None, body.trim().to_string()),
+    };
+    Ok(LinkSnapshot {
+        url: url.to_string(),
+        title,
+        text: text.chars().take(config.max_text).collect(),
+        fetched_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Snapshots of every link fetched so far, keyed by URL.
+#[derive(Default, Serialize, Deserialize)]
+struct SnapshotFile {
+    snapshots: HashMap<String, LinkSnapshot>,
+}
+
+impl Versioned for SnapshotFile {
+    const VERSION: u32 = 1;
+
+    fn migrate(_from: u32, _value: Value) -> Result<Value> {
+        // Only ever saved with a version; reading anything else could lose the snapshots.
+        Err(Box::new(StateError(
+            "not a link snapshot index, it has no version".to_string(),
+        )))
+    }
+}
+
+/// Snapshots kept between runs, so a page is fetched once and its first copy survives the
+/// page going away.
+pub struct LinkSnapshots {
+    path: PathBuf,
+    file: SnapshotFile,
+}
+
+impl LinkSnapshots {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(LinkSnapshots {
+            path: path.to_path_buf(),
+            file: state::load(path)?,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        state::save(&self.path, &self.file)
+    }
+
+    /// Sets `links` of every message to the snapshots of the links in its text, fetching
+    /// pages not seen before. Pages that fail to load are left out with a warning and tried
+    /// again on the next run. Returns how many pages were fetched.
+    pub async fn attach(
+        &mut self,
+        config: &LinkSnapshotConfig,
+        messages: &mut [Message],
+    ) -> Result<usize> {
+        let mut missing: Vec<String> = Vec::new();
+        for message in messages.iter() {
+            for url in urls(&message.text) {
+                if !self.file.snapshots.contains_key(url) && !missing.iter().any(|m| m == url) {
+                    missing.push(url.to_string());
+                }
+            }
+        }
+
+        let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(config.timeout));
+        if let Some(user_agent) = &config.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        let http_client = builder.build()?;
+        let results: Vec<_> = futures_util::stream::iter(&missing)
+            .map(|url| {
+                let http_client = &http_client;
+                async move { (url, fetch(http_client, config, url).await) }
+            })
+            .buffer_unordered(config.workers.max(1))
+            .collect()
+            .await;
+        let mut fetched = 0;
+        for (url, result) in results {
+            match result {
+                Ok(snapshot) => {
+                    self.file.snapshots.insert(url.clone(), snapshot);
+                    fetched += 1;
+                }
+                Err(e) => log::warn!("Could not snapshot {}: {}", url, e),
+            }
+        }
+
+        for message in messages.iter_mut() {
+            message.links = urls(&message.text)
+                .into_iter()
+                .filter_map(|url| self.file.snapshots.get(url).cloned())
+                .collect();
+        }
+        Ok(fetched)
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+use std::fs::{self, File, TryLockError};
+use std::io::Write as _;
+use std::path::Path;
+
+use crate::Result;
+
+#[derive(Debug)]
+pub struct AlreadyRunningError {
+    pid: Option<u32>,
+}
+
+impl fmt::Display for AlreadyRunningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.pid {
+            Some(pid) => write!(f, "Another instance (pid {}) is already running", pid)?,
+            None => write!(f, "Another instance is already running")?,
+        }
+        write!(f, "; pass `--wait` to wait for it to finish.")
+    }
+}
+
+impl std::error::Error for AlreadyRunningError {}
+
+/// Advisory lock held for as long as the process runs, so overlapping invocations don't
+/// share the session or upload the same pins twice.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Locks the pidfile at `path` and writes this process' pid to it. Fails with
+    /// [`AlreadyRunningError`] while another instance holds it, unless `wait` is set.
+    pub fn acquire(path: &Path, wait: bool) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Not truncated before locking, so the pid of the running instance stays readable.
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) if wait => {
+                println!("Waiting for the other instance to finish...");
+                file.lock()?;
+            }
+            Err(TryLockError::WouldBlock) => {
+                let pid = fs::read_to_string(path)
+                    .ok()
+                    .and_then(|pid| pid.trim().parse().ok());
+                return Err(Box::new(AlreadyRunningError { pid }));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        Ok(InstanceLock { _file: file })
+    }
+}
@@ -1,223 +1,902 @@
-use chrono;
-use grammers_client::{Client, Config, SignInError};
-use grammers_session::Session;
-use grammers_tl_types as tl;
-use home;
-use log;
-use serde_derive::{Deserialize, Serialize};
+use clap::{Args, Parser, Subcommand};
+use grammers_client::Client;
 use simple_logger::SimpleLogger;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{self, BufRead as _, Write as _};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal as _};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use telegram_pin_fetcher::anonymize::Pseudonyms;
+use telegram_pin_fetcher::api::{self, LatestPins};
+use telegram_pin_fetcher::cache::ChatCache;
+use telegram_pin_fetcher::config::{self, get_config_dirs, ConfigError, ConfigPaths, FileConfig};
+use telegram_pin_fetcher::control::{self, Control};
+use telegram_pin_fetcher::desktop_import;
+use telegram_pin_fetcher::encryption::{self, EncryptionConfig};
+use telegram_pin_fetcher::export::{self, fields, obsidian, site, Envelope, ExportFile};
+use telegram_pin_fetcher::links::LinkSnapshots;
+use telegram_pin_fetcher::lock::{AlreadyRunningError, InstanceLock};
+use telegram_pin_fetcher::plugin::Plugins;
+use telegram_pin_fetcher::redact::Redactor;
+use telegram_pin_fetcher::review::{self, ReviewError};
+use telegram_pin_fetcher::script::Script;
+use telegram_pin_fetcher::seen::SeenPins;
+use telegram_pin_fetcher::serve::{self, Archive};
+use telegram_pin_fetcher::session_import;
+use telegram_pin_fetcher::shutdown::Shutdown;
+use telegram_pin_fetcher::stats::Stats;
+use telegram_pin_fetcher::summary::{PartialFetchError, RunSummary, UploadFailedError};
+use telegram_pin_fetcher::systemd::{self, ActivatedSockets};
+use telegram_pin_fetcher::telegram::{self, AuthRequiredError, FetchedPins};
+use telegram_pin_fetcher::upload::{self, UploadMode};
+use telegram_pin_fetcher::{grpc, hooks, reload, search, synthetic, translate};
+use telegram_pin_fetcher::{ExporterRegistry, Result};
+use tokio::net::TcpListener;
 use tokio::runtime;
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
-
-#[derive(Deserialize)]
-struct FileConfig {
-    telegram_api_creds: CredsConfig,
-    config: UsersConfig,
-    upload: UploadConfig,
+/// Exit code used when the config file is missing or invalid.
+const EXIT_CONFIG: i32 = 2;
+/// Exit code used when the session needs an interactive login first.
+const EXIT_AUTH_REQUIRED: i32 = 3;
+/// Exit code used when some chats could not be fetched, though the rest was exported.
+const EXIT_PARTIAL_FETCH: i32 = 4;
+/// Exit code used when the export could not be uploaded.
+const EXIT_UPLOAD_FAILED: i32 = 5;
+/// Exit code used when another instance holds the lock and `--wait` wasn't given.
+const EXIT_ALREADY_RUNNING: i32 = 6;
+
+#[derive(Parser)]
+#[command(version, about = "Fetch pinned Telegram messages and upload them")]
+struct Cli {
+    /// Never prompt for input; fail instead if the session is not authorized.
+    #[arg(long)]
+    non_interactive: bool,
+
+    /// Wait for a running instance to finish instead of exiting.
+    #[arg(long)]
+    wait: bool,
+
+    /// Fetch and export as usual but upload nothing, whatever the config says. The export is
+    /// written to the local export directory instead.
+    #[arg(long, global = true)]
+    no_upload: bool,
+
+    /// Connect to Telegram's test data centers, e.g. for integration tests with test numbers.
+    /// The session, state and output are kept in a `test` directory next to the config.
+    #[arg(long, global = true)]
+    test_dc: bool,
+
+    /// Instead of a run, fetch this many generated pins from a mock source and put them
+    /// through the export pipeline with the configured settings, printing how long each stage
+    /// took. Nothing is written or uploaded.
+    #[arg(long, value_name = "PINS")]
+    bench_synthetic: Option<usize>,
+
+    /// List the fetched pins in a pager before exporting them and ask which to leave out,
+    /// e.g. to curate a digest. Single runs only, not `watch`.
+    #[arg(long)]
+    review: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Deserialize)]
-struct UsersConfig {
-    usernames: Vec<String>,
+#[derive(Subcommand)]
+enum Command {
+    /// Sign in interactively and store the session, without fetching anything.
+    Login,
+    /// Log the stored session out and delete it, e.g. to switch to another account.
+    Logout,
+    /// Print the account the stored session is logged in as.
+    Whoami,
+    /// Use an authorized Telethon or Pyrogram session instead of logging in: the path of its
+    /// `.session` file, or a string session.
+    ImportSession {
+        session: String,
+        /// Replace the stored session if there is one.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Keep running, fetching and uploading every `--interval` seconds until stopped.
+    Watch(WatchArgs),
+    /// Search the pins in previously written local JSON exports.
+    Search {
+        /// Words to look for; `sender:name` and `chat:name` narrow the search.
+        query: String,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Browse the pins in local exports from a web page.
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: SocketAddr,
+        /// Also answer GraphQL queries on `/graphql`.
+        #[arg(long)]
+        graphql: bool,
+    },
+    /// Add the pins of a Telegram Desktop JSON export to the local exports: the path of its
+    /// `result.json` or of the folder holding it.
+    Import {
+        path: PathBuf,
+        /// Configured chat the export is of, for a single-chat export of a chat missing from
+        /// the chat cache.
+        #[arg(long)]
+        chat: Option<String>,
+        /// Also count the imported pins as uploaded, so incremental runs skip them.
+        #[arg(long)]
+        mark_seen: bool,
+    },
 }
 
-#[derive(Deserialize)]
-struct CredsConfig {
-    api_id: i32,
-    api_hash: String,
+#[derive(Args)]
+struct WatchArgs {
+    #[arg(long, default_value_t = 3600)]
+    interval: u64,
+    /// Also serve a JSON API with the latest pins on this address, e.g. `127.0.0.1:8081`.
+    #[arg(long)]
+    api: Option<SocketAddr>,
+    /// Also serve the latest pins over gRPC on this address, e.g. `127.0.0.1:50051`.
+    #[arg(long)]
+    grpc: Option<SocketAddr>,
+    /// Accept `refresh`, `status`, `add-chat` and `remove-chat` commands on this Unix socket.
+    #[arg(long)]
+    control: Option<PathBuf>,
 }
 
-#[derive(Deserialize)]
-struct UploadConfig {
-    provider: String,
-    api_token: String,
+/// Command-line switches that change what a run does with its pins.
+#[derive(Clone, Copy)]
+struct RunOptions {
+    no_upload: bool,
+    review: bool,
 }
 
-#[derive(Serialize, Debug)]
-struct Message {
-    sender: String,
-    text: String,
-    date: String,
+/// Fetches every configured chat and uploads the result once, between the `pre_run` and
+/// `post_run` hooks.
+async fn run_once(
+    client: &mut Client,
+    config: &FileConfig,
+    paths: &ConfigPaths,
+    shutdown: &Shutdown,
+    latest: Option<&LatestPins>,
+    options: RunOptions,
+) -> Result<()> {
+    let export_dir = export_dir(config, paths).display().to_string();
+    let mut summary = RunSummary::default();
+    log::info!("Run {} started", summary.run_id);
+    let mut result = hooks::run(
+        &config.config.pre_run,
+        &[
+            ("TPF_RUN_ID", summary.run_id.to_string()),
+            ("TPF_EXPORT_DIR", export_dir.clone()),
+        ],
+    )
+    .await;
+    if result.is_ok() {
+        result = export_pins(
+            client,
+            config,
+            paths,
+            shutdown,
+            latest,
+            options,
+            &mut summary,
+        )
+        .await;
+    }
+    if !config.config.post_run.is_empty() {
+        let status = match &result {
+            Err(_) => "failure",
+            Ok(()) if shutdown.is_requested() => "interrupted",
+            Ok(()) if summary.upload_failed => "failure",
+            Ok(()) if !summary.skipped.is_empty() => "partial",
+            Ok(()) => "success",
+        };
+        let exported: Vec<_> = summary
+            .exported
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+        let env = [
+            ("TPF_RUN_ID", summary.run_id.to_string()),
+            ("TPF_STATUS", status.to_string()),
+            ("TPF_EXPORT_DIR", export_dir),
+            ("TPF_EXPORT_PATH", exported.join("\n")),
+            ("TPF_MESSAGES", summary.messages.to_string()),
+            (
+                "TPF_ERROR",
+                result
+                    .as_ref()
+                    .err()
+                    .map(|e| e.to_string())
+                    .unwrap_or_default(),
+            ),
+        ];
+        if let Err(e) = hooks::run(&config.config.post_run, &env).await {
+            log::error!("{}", e);
+        }
+    }
+    result?;
+    summary.into_result()
 }
 
-fn prompt(message: &str) -> Result<String> {
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock();
-    stdout.write_all(message.as_bytes())?;
-    stdout.flush()?;
+async fn export_pins(
+    client: &mut Client,
+    config: &FileConfig,
+    paths: &ConfigPaths,
+    shutdown: &Shutdown,
+    latest: Option<&LatestPins>,
+    options: RunOptions,
+    summary: &mut RunSummary,
+) -> Result<()> {
+    let deadline = config
+        .config
+        .max_run_duration
+        .map(|secs| Instant::now() + Duration::from_secs(secs));
+    // Compiled before fetching, so a broken plugin fails the run early.
+    let plugins_dir = config.plugins.dir.as_ref().unwrap_or(&paths.plugins_dir);
+    let plugins = Plugins::load(plugins_dir, &config.plugins)?;
+    // A rejected token fails the run now rather than after every chat was fetched.
+    if !options.no_upload {
+        for destination in config.destinations() {
+            if let Some(account) =
+                upload::check_account(config, destination, &plugins, summary.run_id).await?
+            {
+                log::info!("Uploading to {}", account);
+                summary.accounts.push(account);
+            }
+        }
+    }
+    let mut cache = ChatCache::load(&paths.cache_file);
+    let fetched =
+        telegram::get_pinned_messages(client, config, &mut cache, paths, shutdown, deadline).await;
+    cache.save()?;
+    let FetchedPins {
+        mut messages,
+        chats,
+        skipped,
+        mismatches,
+        unchanged,
+        counts,
+        chat_ids,
+    } = fetched?;
+    summary.chats = chats.len();
+    summary.skipped = skipped;
+    summary.mismatches = mismatches;
+    summary.unchanged = unchanged;
+    export::sort(&mut messages, config.export.sort, config.export.partition);
+
+    if shutdown.is_requested() {
+        println!("Interrupted, skipping upload of a partial export.");
+        return Ok(());
+    }
 
-    let stdin = io::stdin();
-    let mut stdin = stdin.lock();
+    Redactor::new(&config.redact)?.apply(&mut messages);
+    if config.config.anonymize {
+        Pseudonyms::load(&paths.salt_file)?.apply(&mut messages);
+    }
+    translate::detect_languages(&mut messages);
 
-    let mut line = String::new();
-    stdin.read_line(&mut line)?;
-    Ok(line)
-}
+    if let Some(latest) = latest {
+        let chats = config.config.chats().into_iter().map(|c| c.username);
+        latest.set(chats.collect(), chat_ids, messages.clone());
+    }
 
-async fn get_pinned_messages(client: Client, creds_toml: &FileConfig) -> Result<Vec<Message>> {
-    let chat_names = &creds_toml.config.usernames;
-    let mut messages = Vec::<Message>::new();
+    let mut seen = match config.config.incremental {
+        true => Some(SeenPins::load(&paths.seen_file)?),
+        false => None,
+    };
+    let mut messages: Vec<_> = match &seen {
+        Some(seen) => messages.into_iter().filter(|m| !seen.contains(m)).collect(),
+        None => messages,
+    };
+    // Chats new to an incremental setup get all their pins once, the others only new ones.
+    if let Some(seen) = &mut seen {
+        for chat in chats.iter().filter(|chat| !seen.knows_chat(&chat.name)) {
+            let pins = messages.iter().filter(|m| m.chat == chat.name).count();
+            let label = chat.alias.as_ref().unwrap_or(&chat.name);
+            println!(
+                "Backfilling {} pins of {}, new since the last run.",
+                pins, label
+            );
+            summary.backfilled.push((label.clone(), pins));
+        }
+        if messages.is_empty() {
+            println!("No new pinned messages since the last run.");
+            // Chats without pins are known from now on too.
+            if !options.no_upload && (!summary.backfilled.is_empty() || !counts.is_empty()) {
+                for chat in &chats {
+                    seen.add_chat(&chat.name);
+                }
+                for (chat, count) in &counts {
+                    seen.set_count(chat, *count);
+                }
+                seen.save()?;
+            }
+            return Ok(());
+        }
+    }
+
+    // Only for the pins about to be exported, and after redaction, so redacted links are
+    // never visited.
+    if let Some(links_config) = &config.link_snapshots {
+        let mut snapshots = LinkSnapshots::load(&paths.links_file)?;
+        let fetched = snapshots.attach(links_config, &mut messages).await?;
+        snapshots.save()?;
+        if fetched > 0 {
+            println!("Saved snapshots of {} linked pages.", fetched);
+        }
+    }
+
+    // Translated after redaction, so redacted data never reaches the service.
+    if let Some(translate_config) = &config.translate {
+        if let Err(e) = translate::translate(translate_config, &mut messages).await {
+            println!(
+                "Error translating messages, exporting them untranslated: {}",
+                e
+            );
+        }
+    }
+
+    // Last, so the script sees the pins as they will be exported.
+    if let Some(script_config) = &config.script {
+        let dropped = Script::load(script_config)?.apply(&mut messages)?;
+        if dropped > 0 {
+            println!("The script dropped {} pins.", dropped);
+        }
+    }
+    let dropped = plugins.filter(&mut messages)?;
+    if dropped > 0 {
+        println!("Plugins dropped {} pins.", dropped);
+    }
 
-    for chat_name in chat_names {
-        let maybe_chat = client.resolve_username(chat_name.as_str()).await?;
-        let chat = maybe_chat.unwrap_or_else(|| panic!("Chat {} could not be found", chat_name));
-        let mut pinned_messages = client
-            .search_messages(&chat)
-            .filter(tl::enums::MessagesFilter::InputMessagesFilterPinned);
+    // Last of all, so the pins are reviewed exactly as they will be exported.
+    let left_out = match options.review {
+        true => review::review(&mut messages)?,
+        false => Vec::new(),
+    };
+    if !left_out.is_empty() {
+        println!("Left out {} pins.", left_out.len());
+    }
+    if messages.is_empty() && options.review {
+        println!("Every pin was left out, nothing to export.");
+        return Ok(());
+    }
+    fields::mask(&mut messages, &config.export.fields);
 
+    summary.messages = messages.len();
+
+    let account = client.get_me().await?.username().map(str::to_string);
+    let envelope = Envelope::new(
+        summary.run_id,
+        account,
+        chats,
+        &messages,
+        &config.export.fields,
+    );
+    let mut registry = ExporterRegistry::builtin(&config.export)?;
+    plugins.register_exporters(&mut registry)?;
+    let exporter = registry.get(&config.export.format)?;
+    let export_dir = export_dir(config, paths);
+    let encryption = config.export.encryption.as_ref();
+    let local = |file: &ExportFile| match encryption {
+        Some(encryption) => encryption.seal(file),
+        None => Ok(file.clone()),
+    };
+    // Without an upload, the local copy is the only one. Streamed into the files, so the
+    // export of a huge archive never has to fit in memory.
+    if config.export.dir.is_some() || options.no_upload {
+        if encryption.is_some() {
+            encryption::private_dir(export_dir)?;
+        }
+        let partition = config.export.partition;
+        for part in ExportFile::partitions(exporter, &envelope, partition, config.export.max_size)?
+        {
+            let on_collision = config.export.on_collision;
+            match ExportFile::stream_to(exporter, &part, export_dir, on_collision, encryption)? {
+                Some(path) => {
+                    println!("Wrote {}", path.display());
+                    summary.exported.push(path);
+                }
+                None => println!(
+                    "Skipped {}, it already exists",
+                    ExportFile::name(exporter, &part)
+                ),
+            }
+        }
+    }
+
+    if let Some(obsidian) = &config.export.obsidian {
+        let notes = obsidian::write_vault(obsidian, config.export.text, &messages)?;
+        println!("Wrote {} notes to {}", notes, obsidian.dir.display());
+    }
+
+    if let Some(site_config) = &config.export.site {
+        let text = config.export.text;
+        let written = site::write_site(site_config, text, &envelope.chats, &messages)?;
         println!(
-            "Chat {} has {} total pinned messages.",
-            chat_name,
-            pinned_messages.total().await.unwrap()
+            "Updated {} files of the site in {}",
+            written,
+            site_config.dir.display()
         );
+    }
+
+    let stats = Stats::from_messages(&messages);
+    if config.export.stats_json {
+        let stats_file = ExportFile {
+            name: format!(
+                "{}.{}.stats.json",
+                export::today_stem(),
+                summary.run_id.short()
+            ),
+            content_type: "application/json".to_string(),
+            bytes: serde_json::to_vec_pretty(&stats)?,
+        };
+        if encryption.is_some() {
+            encryption::private_dir(export_dir)?;
+        }
+        let stats_file = local(&stats_file)?;
+        match stats_file.write_to(export_dir, config.export.on_collision, summary.run_id)? {
+            Some(path) => println!("Wrote {}", path.display()),
+            None => println!("Skipped {}, it already exists", stats_file.name),
+        }
+    }
+    summary.stats = Some(stats);
 
-        while let Some(msg) = pinned_messages.next().await? {
-            if let Some(_) = msg.media() {
+    if options.no_upload {
+        println!("Not uploading (--no-upload).");
+    }
+    // Files per format, rendered once however many destinations ask for it, and all before
+    // the first upload so an unknown format does not stop the run halfway.
+    let mut rendered = HashMap::new();
+    let mut destinations = Vec::new();
+    for destination in config.destinations().filter(|_| !options.no_upload) {
+        let format = destination
+            .format
+            .as_deref()
+            .unwrap_or(&config.export.format)
+            .to_lowercase();
+        let exporter = registry.get(&format)?;
+        let takes_files =
+            !destination.provider.takes_messages() && destination.mode != UploadMode::Merge;
+        if takes_files && !rendered.contains_key(&format) {
+            let files = ExportFile::render_partitioned(
+                exporter,
+                &envelope,
+                config.export.partition,
+                config.export.max_size,
+            )?;
+            rendered.insert(format.clone(), files);
+        }
+        destinations.push((destination, exporter, format));
+    }
+    // Signed once per format too, and all before the first upload like the rendering.
+    if let Some(signing) = &config.signing {
+        let mut signed = HashSet::new();
+        for (destination, _, format) in &destinations {
+            let takes_files =
+                !destination.provider.takes_messages() && destination.mode != UploadMode::Merge;
+            let Some(files) = rendered.get_mut(format).filter(|_| takes_files) else {
                 continue;
+            };
+            if signed.insert(format.clone()) {
+                let mut signatures = Vec::new();
+                for file in files.iter() {
+                    signatures.push(signing.sign(file).await?);
+                }
+                files.extend(signatures);
+            }
+        }
+    }
+
+    for (destination, exporter, format) in destinations {
+        if destination.provider.takes_messages() {
+            match upload::upload_messages(config, destination, &plugins, summary.run_id, &messages)
+                .await
+            {
+                Ok(result) => {
+                    println!("Synced pinned messages to {}", result.url);
+                    summary.uploads.push(result);
+                }
+                Err(e) => {
+                    println!("Error uploading messages: {}", e);
+                    summary.upload_failed = true;
+                }
+            }
+        } else if destination.mode == UploadMode::Merge {
+            match upload::upload_merged(config, destination, &plugins, &format, exporter, &envelope)
+                .await
+            {
+                Ok(result) => {
+                    println!("Merged pinned messages into {}", result.url);
+                    summary.uploads.push(result);
+                }
+                Err(e) => {
+                    println!("Error merging into the archive: {}", e);
+                    summary.upload_failed = true;
+                }
             }
-            let sender = msg.sender().unwrap();
-            let text = msg.text();
-            let date = msg.date().date_naive();
+        } else {
+            for file in rendered[&format].iter().cloned() {
+                let name = file.name.clone();
+                match upload::upload(config, destination, &plugins, summary.run_id, file).await {
+                    Ok(result) => {
+                        println!("Uploaded {} to {}", name, result.url);
+                        summary.uploads.push(result);
+                    }
+                    Err(e) => {
+                        println!("Error uploading {}: {}", name, e);
+                        summary.upload_failed = true;
+                    }
+                }
+            }
+        }
+    }
 
-            messages.push(Message {
-                sender: sender.username().unwrap().to_string(),
-                text: text.to_string(),
-                date: date.to_string(),
-            });
+    // Failed or skipped uploads are retried with the same pins on the next run.
+    if let (Some(seen), false) = (&mut seen, summary.upload_failed || options.no_upload) {
+        for chat in &envelope.chats {
+            seen.add_chat(&chat.name);
+        }
+        // Left-out pins too, so they are not offered again.
+        for message in messages.iter().chain(&left_out) {
+            seen.insert(message);
         }
+        for (chat, count) in &counts {
+            seen.set_count(chat, *count);
+        }
+        seen.save()?;
     }
 
-    messages.sort_by(|a, b| a.date.cmp(&b.date));
+    summary.print();
 
-    Ok(messages)
+    Ok(())
 }
 
-async fn login_and_get_pinned_messages(
+async fn watch(
+    client: &mut Client,
     config: &FileConfig,
-    session_file: &PathBuf,
-) -> Result<Vec<Message>> {
-    let client = Client::connect(Config {
-        session: Session::load_file_or_create(&session_file).unwrap(),
-        api_id: config.telegram_api_creds.api_id.clone(),
-        api_hash: config.telegram_api_creds.api_hash.clone(),
-        params: Default::default(),
-    })
-    .await?;
-
-    if !client.is_authorized().await? {
-        println!("Signing in...");
-        let phone = prompt("Enter your phone number (international format): ")?;
-        let token = client.request_login_code(&phone).await?;
-        let code = prompt("Enter the code you received: ")?;
-        let signed_in = client.sign_in(&token, &code).await;
-        match signed_in {
-            Err(SignInError::PasswordRequired(password_token)) => {
-                // Note: this `prompt` method will echo the password in the console.
-                //       Real code might want to use a better way to handle this.
-                let hint = password_token.hint().unwrap_or("None");
-                let prompt_message = format!("Enter the password (hint {}): ", &hint);
-                let password = prompt(prompt_message.as_str())?;
-
-                client
-                    .check_password(password_token, password.trim())
-                    .await?;
-            }
-            Ok(_) => (),
-            Err(e) => panic!("{}", e),
-        };
-        println!("Signed in!");
+    paths: &ConfigPaths,
+    shutdown: &Shutdown,
+    args: WatchArgs,
+    no_upload: bool,
+    test_dc: bool,
+) -> Result<()> {
+    let interval = Duration::from_secs(args.interval);
+    let latest = LatestPins::default();
+    let config = Arc::new(Mutex::new(config.clone()));
+    // Sockets passed by systemd take precedence over the addresses given on the command line.
+    let mut activated = ActivatedSockets::take()?;
+    let api_listener = match (activated.tcp("api")?, args.api) {
+        (Some(listener), _) => Some(listener),
+        (None, Some(addr)) => Some(TcpListener::bind(addr).await?),
+        (None, None) => None,
+    };
+    let grpc_listener = match (activated.tcp("grpc")?, args.grpc) {
+        (Some(listener), _) => Some(listener),
+        (None, Some(addr)) => Some(TcpListener::bind(addr).await?),
+        (None, None) => None,
+    };
+    let control_listener = match (activated.unix("control")?, &args.control) {
+        (Some(listener), _) => Some(listener),
+        (None, Some(path)) => Some(control::bind(path).await?),
+        (None, None) => None,
+    };
+    for name in activated.unused() {
+        log::warn!(
+            "Ignoring activated socket {:?}, name it api, grpc or control",
+            name
+        );
     }
 
-    Ok(get_pinned_messages(client, config).await.unwrap())
-}
+    let server = async {
+        match api_listener {
+            Some(listener) => api::serve(listener, latest.clone(), shutdown.clone()).await,
+            None => Ok(()),
+        }
+    };
+    let grpc_server = async {
+        match grpc_listener {
+            Some(listener) => grpc::serve(listener, latest.clone(), shutdown.clone()).await,
+            None => Ok(()),
+        }
+    };
+    let control_server = async {
+        let Some(listener) = control_listener else {
+            return Ok(());
+        };
+        let control = Control {
+            config: config.clone(),
+            latest: latest.clone(),
+        };
+        control::serve(listener, control, shutdown.clone()).await?;
+        // A socket from systemd is left for systemd to clean up.
+        if let Some(path) = &args.control {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    };
+
+    // Pins between runs trigger a run of their own instead of waiting for the interval.
+    let updates_client = client.clone();
+    let updates = async {
+        loop {
+            let update = tokio::select! {
+                update = updates_client.next_update() => update,
+                _ = shutdown.wait() => break,
+            };
+            match update {
+                // Pins in other chats of the account are none of the runs' business.
+                Ok(Some(update)) => match telegram::pin_update_chat(&update) {
+                    Some(chat_id) if latest.watches(chat_id) => {
+                        log::info!("Pin event received, refreshing");
+                        latest.request_refresh();
+                    }
+                    Some(chat_id) => log::debug!("Ignoring a pin event of chat {}", chat_id),
+                    None => {}
+                },
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("Could not receive updates: {}", e);
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs(30)) => {}
+                        _ = shutdown.wait() => break,
+                    }
+                }
+            }
+        }
+    };
+
+    let reloads = async {
+        let config_file = paths.config_file.clone();
+        let overrides = |config: &mut FileConfig| apply_overrides(config, test_dc);
+        let reload = reload::watch(config_file, config.clone(), overrides, shutdown.clone());
+        if let Err(e) = reload.await {
+            log::error!("Not watching the config file for changes: {}", e);
+        }
+    };
 
-fn get_config_dirs() -> (PathBuf, PathBuf) {
-    let mut config_dir = match home::home_dir() {
-        Some(path) => path,
-        None => panic!("Could not find home dir"),
+    let runs = async {
+        while !shutdown.is_requested() {
+            // Changes to the config file or through the control socket apply from the next run on.
+            let config = config.lock().unwrap().clone();
+            let run = run_once(
+                &mut *client,
+                &config,
+                paths,
+                shutdown,
+                Some(&latest),
+                RunOptions {
+                    no_upload,
+                    review: false,
+                },
+            );
+            if let Err(e) = run.await {
+                log::error!("Run failed: {}", e);
+            }
+            // Keeps the update state current, so a restart catches up from here.
+            if let Err(e) = client.session().save_to_file(&paths.session_file) {
+                log::warn!("Could not save the session: {}", e);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = latest.refresh_requested() => log::info!("Refresh requested"),
+                _ = shutdown.wait() => {}
+            }
+        }
+        systemd::notify_stopping();
     };
 
-    config_dir.push(".config");
-    config_dir.push("telegram_pinned");
+    systemd::notify_ready();
+    let (server, grpc_server, control_server, (), (), (), ()) = tokio::join!(
+        server,
+        grpc_server,
+        control_server,
+        reloads,
+        updates,
+        systemd::watchdog(shutdown),
+        runs
+    );
+    server.and(grpc_server).and(control_server)
+}
 
-    let mut config_file = config_dir.clone();
-    config_file.push("config.toml");
+/// Local exports are written here; also where `search` and `serve` read them from.
+fn export_dir<'a>(config: &'a FileConfig, paths: &'a ConfigPaths) -> &'a Path {
+    config.export.dir.as_deref().unwrap_or(&paths.export_dir)
+}
 
-    let mut session_file = config_dir.clone();
-    session_file.push("telegram.session");
+fn print_search(
+    export_dir: &Path,
+    encryption: Option<&EncryptionConfig>,
+    query: &str,
+    limit: usize,
+) -> Result<()> {
+    let messages = search::load_exports(export_dir, encryption)?;
+    let hits = search::search(messages, query, limit)?;
+    if hits.is_empty() {
+        println!("No pinned messages match {:?}.", query);
+    }
+    for message in hits {
+        println!("{}  {}  {}", message.date, message.chat, message.sender);
+        for line in message.text.lines() {
+            println!("    {}", line);
+        }
+        if let Some(link) = message.link() {
+            println!("    {}", link);
+        }
+        println!();
+    }
+    Ok(())
+}
 
-    (config_file, session_file)
+/// Applies the command line options that override the config file, `--test-dc`, to `config`.
+fn apply_overrides(config: &mut FileConfig, test_dc: bool) {
+    config.telegram_api_creds.test_dc |= test_dc;
 }
 
-async fn async_main() -> Result<()> {
+async fn async_main(cli: Cli) -> Result<()> {
     SimpleLogger::new()
         .with_level(log::LevelFilter::Info)
+        .with_module_level("tantivy", log::LevelFilter::Warn)
         .init()
         .unwrap();
 
-    let (config_file_path, session_file_path) = get_config_dirs();
-
-    let config_file_contents = fs::read_to_string(&config_file_path).unwrap();
-    let creds_toml: FileConfig = toml::from_str(&config_file_contents).unwrap();
-
-    let messages = login_and_get_pinned_messages(&creds_toml, &session_file_path).await?;
-
-    match upload_messages(&creds_toml, messages).await {
-        Err(_) => println!("Error uploading messages"),
-        _ => (),
-    };
-
-    Ok(())
-}
+    let mut paths = get_config_dirs();
 
-async fn upload_messages(creds_toml: &FileConfig, messages: Vec<Message>) -> Result<()> {
-    let payload = serde_json::to_string(&messages).unwrap().clone();
+    let mut creds_toml = config::load(&paths.config_file)?;
+    apply_overrides(&mut creds_toml, cli.test_dc);
+    if creds_toml.telegram_api_creds.test_dc {
+        log::warn!("Using Telegram's test data centers");
+        paths = paths.for_test_dc();
+        if let Some(test_dir) = paths.session_file.parent() {
+            fs::create_dir_all(test_dir)?;
+        }
+    }
 
-    if creds_toml.upload.provider != "gofile" {
-        panic!("Only gofile upload provider is supported.");
+    if let Some(count) = cli.bench_synthetic {
+        return synthetic::bench(&creds_toml, count);
     }
 
-    let http_client = reqwest::Client::new();
-    let payload_bytes = String::from_utf8(payload.into_bytes()).unwrap();
-    let mut file_part_headers = reqwest::header::HeaderMap::new();
-    file_part_headers.insert(
-        reqwest::header::CONTENT_TYPE,
-        "application/json".parse().unwrap(),
-    );
+    if let Some(Command::Search { query, limit }) = &cli.command {
+        // Works offline: only the local exports are read.
+        let encryption = creds_toml.export.encryption.as_ref();
+        return print_search(export_dir(&creds_toml, &paths), encryption, query, *limit);
+    }
 
-    let now = chrono::offset::Utc::now();
-    let date = now.date_naive();
-    let filename = date.format("%Y-%m-%d.json").to_string();
+    if let Some(Command::Serve { addr, graphql }) = &cli.command {
+        let archive = Archive {
+            export_dir: export_dir(&creds_toml, &paths).to_path_buf(),
+            encryption: creds_toml.export.encryption.clone(),
+            media_dir: creds_toml
+                .media
+                .dir
+                .clone()
+                .unwrap_or_else(|| paths.media_dir.clone()),
+        };
+        return serve::serve(*addr, archive, *graphql, Shutdown::listen()?).await;
+    }
 
-    let file_part = reqwest::multipart::Part::bytes(payload_bytes.into_bytes())
-        .file_name(filename)
-        .headers(file_part_headers);
+    // Held until exit; everything below uses the session and the state files.
+    let _lock = InstanceLock::acquire(&paths.lock_file, cli.wait)?;
 
-    let form = reqwest::multipart::Form::new()
-        .part("file", file_part)
-        .text("folderId", "cf71f5f5-d849-4c80-94c7-eb73e5253c86");
+    if let Some(Command::ImportSession { session, force }) = &cli.command {
+        return session_import::import(&creds_toml, &paths, session, *force).await;
+    }
+    if let Some(Command::Import {
+        path,
+        chat,
+        mark_seen,
+    }) = &cli.command
+    {
+        let export_dir = export_dir(&creds_toml, &paths);
+        return desktop_import::import(
+            &creds_toml,
+            &paths,
+            export_dir,
+            path,
+            chat.as_deref(),
+            *mark_seen,
+        );
+    }
 
-    let req = http_client
-        .post("https://store1.gofile.io/contents/uploadfile")
-        .bearer_auth(&creds_toml.upload.api_token)
-        .multipart(form);
+    let interactive = !cli.non_interactive && io::stdin().is_terminal();
+    if cli.review {
+        if matches!(cli.command, Some(Command::Watch(_))) {
+            return Err(Box::new(ReviewError(
+                "--review is for single runs, not watch".to_string(),
+            )));
+        }
+        review::check_terminal(cli.non_interactive)?;
+    }
 
-    match req.send().await {
-        Ok(res) => println!("Response from remote: {}", res.text().await?),
-        Err(e) => println!("Error pushing data to remote: {}", e)
+    // The daemon also reacts to pin events, including those missed while it was down.
+    let catch_up = matches!(cli.command, Some(Command::Watch(_)));
+    let mut client = telegram::connect(&creds_toml, &paths.session_file, catch_up).await?;
+    match cli.command {
+        Some(Command::Whoami) => {
+            return telegram::whoami(&client, &creds_toml, &paths.session_file).await
+        }
+        Some(Command::Logout) => {
+            return telegram::log_out(&client, &paths.session_file, &paths.cache_file).await
+        }
+        _ => {}
     }
+    telegram::sign_in(&mut client, &creds_toml, &paths.session_file, interactive).await?;
+
+    let shutdown = Shutdown::listen()?;
+    let result = match cli.command {
+        Some(Command::Login) => Ok(()),
+        Some(Command::Watch(args)) => {
+            watch(
+                &mut client,
+                &creds_toml,
+                &paths,
+                &shutdown,
+                args,
+                cli.no_upload,
+                cli.test_dc,
+            )
+            .await
+        }
+        Some(Command::Search { .. } | Command::Serve { .. }) => {
+            unreachable!("handled before connecting")
+        }
+        Some(
+            Command::Logout
+            | Command::Whoami
+            | Command::ImportSession { .. }
+            | Command::Import { .. },
+        ) => {
+            unreachable!("handled before signing in")
+        }
+        None => {
+            run_once(
+                &mut client,
+                &creds_toml,
+                &paths,
+                &shutdown,
+                None,
+                RunOptions {
+                    no_upload: cli.no_upload,
+                    review: cli.review,
+                },
+            )
+            .await
+        }
+    };
 
-    Ok(())
+    client.session().save_to_file(&paths.session_file)?;
+
+    result
 }
 
-fn main() -> Result<()> {
-    runtime::Builder::new_current_thread()
+fn main() {
+    let cli = Cli::parse();
+
+    let result = runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .unwrap()
-        .block_on(async_main())
+        .block_on(async_main(cli));
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        let code = if e.is::<ConfigError>() {
+            EXIT_CONFIG
+        } else if e.is::<AuthRequiredError>() {
+            EXIT_AUTH_REQUIRED
+        } else if e.is::<PartialFetchError>() {
+            EXIT_PARTIAL_FETCH
+        } else if e.is::<UploadFailedError>() {
+            EXIT_UPLOAD_FAILED
+        } else if e.is::<AlreadyRunningError>() {
+            EXIT_ALREADY_RUNNING
+        } else {
+            1
+        };
+        process::exit(code);
+    }
 }
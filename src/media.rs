@@ -0,0 +1,684 @@
+use futures_util::{StreamExt as _, TryStreamExt as _};
+use grammers_client::client::files::MAX_CHUNK_SIZE;
+use grammers_client::types::{Chat, Downloadable, Media};
+use grammers_client::Client;
+use grammers_tl_types as tl;
+use image::DynamicImage;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal as _, Seek as _, Write as _};
+use std::os::unix::fs::MetadataExt as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::MediaConfig;
+use crate::scan;
+use crate::state::{self, StateError, Versioned};
+use crate::stickers;
+use crate::throttle::{Bandwidth, Throttle};
+use crate::units::ByteSize;
+use crate::Result;
+
+/// Directory inside the media directory holding one link per distinct file content.
+const STORE_DIR: &str = ".sha256";
+
+/// Longest side of generated thumbnails, in pixels.
+const THUMBNAIL_SIZE: u32 = 320;
+
+/// How often a download's progress line is redrawn.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Kinds of pinned files, as listed in `media.media_types`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    Photo,
+    Video,
+    Audio,
+    /// Any other file, including images sent uncompressed.
+    Document,
+}
+
+/// A pinned photo or file. Files that were not downloaded are still recorded, with the
+/// reason in `skipped`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MediaInfo {
+    #[serde(rename = "type")]
+    pub kind: MediaType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Size in bytes; for photos, that of the largest size Telegram keeps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// SHA-256 of the downloaded file; pins with the same hash share one copy on disk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Small JPEG preview of a downloaded photo or video, with `media.thumbnails`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+    /// Signature the virus scan matched; the file is then in quarantine, not in `file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantined: Option<String>,
+}
+
+/// The metadata of a photo or document, with the ID and extension its download is named by.
+fn describe(media: &Media) -> Option<(MediaInfo, i64, String)> {
+    let (info, id, extension) = match media {
+        Media::Photo(photo) => {
+            let size = photo.thumbs().iter().map(|thumb| thumb.size() as u64).max();
+            let info = MediaInfo {
+                kind: MediaType::Photo,
+                file_name: None,
+                mime_type: Some("image/jpeg".to_string()),
+                size,
+                file: None,
+                sha256: None,
+                thumbnail: None,
+                skipped: None,
+                quarantined: None,
+            };
+            (info, photo.id(), "jpg".to_string())
+        }
+        Media::Document(doc) => {
+            let mime_type = doc.mime_type().map(str::to_string);
+            let kind = match mime_type.as_deref().and_then(|mime| mime.split_once('/')) {
+                Some(("video", _)) => MediaType::Video,
+                Some(("audio", _)) => MediaType::Audio,
+                _ => MediaType::Document,
+            };
+            let file_name = Some(doc.name().to_string()).filter(|name| !name.is_empty());
+            let extension = file_name
+                .as_deref()
+                .and_then(|name| Path::new(name).extension())
+                .map(|ext| ext.to_string_lossy().into_owned())
+                .or_else(|| Some(mime_type.as_deref()?.split_once('/')?.1.to_string()))
+                .unwrap_or_else(|| "bin".to_string());
+            let info = MediaInfo {
+                kind,
+                file_name,
+                mime_type,
+                size: Some(doc.size() as u64),
+                file: None,
+                sha256: None,
+                thumbnail: None,
+                skipped: None,
+                quarantined: None,
+            };
+            (info, doc.id(), extension)
+        }
+        _ => return None,
+    };
+    Some((info, id, extension))
+}
+
+/// Describes a pinned photo or document, downloading it into `download_dir` when its type
+/// is in `media_types` and it is not bigger than `max_media_size`. Other media is `None`.
+pub async fn media_info(
+    client: &Client,
+    media: &Media,
+    config: &MediaConfig,
+    download_dir: &Path,
+    max_bandwidth: Option<Bandwidth>,
+    progress: &Progress,
+) -> Result<Option<MediaInfo>> {
+    let Some((mut info, id, extension)) = describe(media) else {
+        return Ok(None);
+    };
+    if config.media_types.is_empty() {
+        return Ok(Some(info));
+    }
+
+    let too_large = config
+        .max_media_size
+        .filter(|max| info.size.is_some_and(|size| size > max.0));
+    if !config.media_types.contains(&info.kind) {
+        info.skipped = Some("type not in media_types".to_string());
+    } else if let Some(max) = too_large {
+        info.skipped = Some(format!("larger than max_media_size ({})", max));
+    } else {
+        fs::create_dir_all(download_dir)?;
+        let file_name = format!("{}.{}", id, extension);
+        let path = download_dir.join(&file_name);
+        // Photos are re-encoded by Telegram, so only documents can carry anything harmful.
+        let scan = config
+            .scan
+            .as_ref()
+            .filter(|_| info.kind != MediaType::Photo);
+        let quarantine_dir = scan.map(|scan| match &scan.quarantine_dir {
+            Some(dir) => dir.clone(),
+            None => download_dir.join("quarantine"),
+        });
+        if let Some(quarantine_dir) = &quarantine_dir {
+            info.quarantined = scan::quarantined(quarantine_dir, &file_name);
+            if info.quarantined.is_some() {
+                return Ok(Some(info));
+            }
+        }
+        if !path.exists() {
+            let size = info.size.unwrap_or_default();
+            download(client, media, &path, size, max_bandwidth, progress).await?;
+            if let (Some(scan), Some(quarantine_dir)) = (scan, &quarantine_dir) {
+                // Scanned once, when downloaded: clamscan takes seconds to load its database.
+                if let Some(signature) = scan::scan(&scan.scanner, &path).await? {
+                    scan::quarantine(&path, quarantine_dir, &signature)?;
+                    info.quarantined = Some(signature);
+                    return Ok(Some(info));
+                }
+            }
+        }
+        if config.strip_exif && strip_exif(&path)? {
+            log::debug!("Stripped metadata from {}", path.display());
+        }
+        info.sha256 = Some(deduplicate(download_dir, &path)?);
+        if config.thumbnails {
+            let thumbnail = thumbnail(client, media, info.kind, &path).await;
+            info.thumbnail = thumbnail
+                .inspect_err(|e| log::warn!("No thumbnail for {}: {}", path.display(), e))
+                .ok()
+                .flatten()
+                .map(|thumbnail| thumbnail.to_string_lossy().into_owned());
+        }
+        info.file = Some(path.to_string_lossy().into_owned());
+    }
+    Ok(Some(info))
+}
+
+/// Downloads the current profile photo of `sender`, in Telegram's small 160px size, as
+/// `profile-<sender>-<photo>.jpg` in the download directory. A new photo gets a new file and
+/// one downloaded before is reused. Returns its path, or `None` when the sender has no photo.
+pub async fn profile_photo(
+    client: &Client,
+    sender: &Chat,
+    download_dir: &Path,
+) -> Result<Option<String>> {
+    let photo_id = match sender {
+        Chat::User(user) => user.photo().map(|photo| photo.photo_id),
+        Chat::Group(group) => group.photo().map(|photo| photo.photo_id),
+        Chat::Channel(channel) => channel.photo().map(|photo| photo.photo_id),
+    };
+    let (Some(photo_id), Some(downloadable)) = (photo_id, sender.photo_downloadable(false)) else {
+        return Ok(None);
+    };
+    let path = download_dir.join(format!("profile-{}-{}.jpg", sender.id(), photo_id));
+    if !path.exists() {
+        fs::create_dir_all(download_dir)?;
+        let part = path.with_extension("jpg.part");
+        client.download_media(&downloadable, &part).await?;
+        fs::rename(&part, &path)?;
+    }
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+/// [`media_info`] for many pins at once, with up to `media.workers` downloads running
+/// concurrently. `max_bandwidth` is split evenly between them, and media pinned more than
+/// once is downloaded a single time.
+pub async fn media_infos(
+    client: &Client,
+    media: &[Media],
+    config: &MediaConfig,
+    download_dir: &Path,
+    max_bandwidth: Option<Bandwidth>,
+) -> Result<Vec<Option<MediaInfo>>> {
+    // Zero would never start a download.
+    let workers = config.workers.max(1);
+    let share = max_bandwidth.map(|max| Bandwidth(ByteSize((max.0 .0 / workers as u64).max(1))));
+    let keys: Vec<_> = media
+        .iter()
+        .map(|media| describe(media).map(|(_, id, extension)| (id, extension)))
+        .collect();
+    let mut first = HashMap::new();
+    for (index, key) in keys.iter().enumerate() {
+        if let Some(key) = key {
+            first.entry(key).or_insert(index);
+        }
+    }
+
+    let progress = &Progress::default();
+    let infos: HashMap<_, _> = futures_util::stream::iter(first)
+        .map(|(key, index)| async move {
+            let media = &media[index];
+            let info = media_info(client, media, config, download_dir, share, progress).await?;
+            Ok::<_, Box<dyn std::error::Error>>((key, info))
+        })
+        .buffer_unordered(workers)
+        .try_collect()
+        .await?;
+    Ok(keys
+        .iter()
+        .map(|key| infos.get(key.as_ref()?).cloned().flatten())
+        .collect())
+}
+
+/// One download of [`Progress`].
+struct Transfer {
+    id: u64,
+    name: String,
+    size: u64,
+    done: u64,
+    /// Bytes already on disk from an earlier, interrupted run.
+    resumed: u64,
+    started: Instant,
+}
+
+impl Transfer {
+    /// Bytes per second so far.
+    fn speed(&self) -> u64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        match elapsed > 0.0 {
+            true => ((self.done - self.resumed) as f64 / elapsed) as u64,
+            false => 0,
+        }
+    }
+}
+
+/// What [`Progress`] shares between the downloads.
+#[derive(Default)]
+struct Transfers {
+    active: Vec<Transfer>,
+    next_id: u64,
+    drawn: Option<Instant>,
+}
+
+/// Progress of the downloads running at the same time, e.g. the `media.workers` ones: redrawn
+/// in place on stderr as a single line adding them up when it is a terminal, and printed
+/// once per download as it finishes either way.
+#[derive(Clone)]
+pub struct Progress {
+    transfers: Arc<Mutex<Transfers>>,
+    terminal: bool,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress {
+            transfers: Default::default(),
+            terminal: io::stderr().is_terminal(),
+        }
+    }
+}
+
+impl Progress {
+    /// Starts tracking the download to `path` of `size` bytes, `resumed` of which are on disk
+    /// already. Returns its ID for [`Progress::advance`] and [`Progress::finish`].
+    fn start(&self, path: &Path, size: u64, resumed: u64) -> u64 {
+        let mut transfers = self.transfers.lock().unwrap();
+        let id = transfers.next_id;
+        transfers.next_id += 1;
+        transfers.active.push(Transfer {
+            id,
+            name: path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            size,
+            done: resumed,
+            resumed,
+            started: Instant::now(),
+        });
+        id
+    }
+
+    /// The download itself while it is the only one, or else all of them added up.
+    fn line(transfers: &[Transfer]) -> String {
+        let name = match transfers {
+            [transfer] => transfer.name.clone(),
+            _ => format!("{} files", transfers.len()),
+        };
+        let done: u64 = transfers.iter().map(|t| t.done).sum();
+        let size: u64 = transfers.iter().map(|t| t.size).sum();
+        let speed: u64 = transfers.iter().map(Transfer::speed).sum();
+        let mut line = format!(
+            "Downloading {}: {} / {}, {}/s",
+            name,
+            ByteSize(done),
+            ByteSize(size),
+            ByteSize(speed)
+        );
+        if let Some(percent) = (done * 100).checked_div(size) {
+            line.push_str(&format!(" ({}%)", percent));
+        }
+        if speed > 0 && done < size {
+            line.push_str(&format!(", ETA {}s", (size - done).div_ceil(speed)));
+        }
+        line
+    }
+
+    fn advance(&self, id: u64, bytes: usize) {
+        let mut transfers = self.transfers.lock().unwrap();
+        if let Some(transfer) = transfers.active.iter_mut().find(|t| t.id == id) {
+            transfer.done += bytes as u64;
+        }
+        let due = transfers
+            .drawn
+            .is_none_or(|drawn| drawn.elapsed() >= PROGRESS_INTERVAL);
+        if self.terminal && due {
+            eprint!("\r\x1b[2K{}", Self::line(&transfers.active));
+            transfers.drawn = Some(Instant::now());
+        }
+    }
+
+    /// Prints how the download went and stops tracking it.
+    fn finish(&self, id: u64) {
+        let mut transfers = self.transfers.lock().unwrap();
+        let Some(index) = transfers.active.iter().position(|t| t.id == id) else {
+            return;
+        };
+        let transfer = transfers.active.remove(index);
+        let line = Self::line(std::slice::from_ref(&transfer));
+        match self.terminal {
+            true => {
+                eprintln!("\r\x1b[2K{}", line);
+                // The others go on, back on their line.
+                if !transfers.active.is_empty() {
+                    eprint!("{}", Self::line(&transfers.active));
+                }
+            }
+            false => eprintln!("{}", line),
+        }
+    }
+}
+
+/// How far an interrupted download got, kept next to its `.part` file.
+#[derive(Serialize, Deserialize, Default)]
+struct Checkpoint {
+    /// Size of the complete file, so a checkpoint for different media is not resumed.
+    size: u64,
+    /// Bytes of the `.part` file known to be written.
+    offset: u64,
+}
+
+impl Versioned for Checkpoint {
+    const VERSION: u32 = 1;
+
+    fn migrate(_from: u32, _value: Value) -> Result<Value> {
+        // Checkpoints are always tagged; an untagged one can't be trusted to match the
+        // `.part` file, so `download` starts over.
+        Err(Box::new(StateError(
+            "not a download checkpoint, it has no version".to_string(),
+        )))
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Downloads `media` (`size` bytes) to `path`, pacing chunks to `max_bandwidth` when set and
+/// showing how far it got in `progress`.
+///
+/// Data goes to `<path>.part` and is only renamed to `path` once complete; the offset
+/// reached is saved in `<path>.checkpoint` after every chunk, so a download interrupted by
+/// an error or a restart continues from there on the next run.
+pub async fn download(
+    client: &Client,
+    media: &Media,
+    path: &Path,
+    size: u64,
+    max_bandwidth: Option<Bandwidth>,
+    progress: &Progress,
+) -> Result<()> {
+    let part = with_suffix(path, ".part");
+    let checkpoint_path = with_suffix(path, ".checkpoint");
+    let mut checkpoint = state::load::<Checkpoint>(&checkpoint_path)
+        .inspect_err(|e| log::warn!("Restarting download of {}: {}", path.display(), e))
+        .unwrap_or_default();
+    // Telegram serves whole chunks only, so resume from the last complete one.
+    let chunk_size = MAX_CHUNK_SIZE as u64;
+    let resumable = checkpoint.size == size && part.exists();
+    checkpoint.offset = match resumable {
+        true => checkpoint.offset / chunk_size * chunk_size,
+        false => 0,
+    };
+    checkpoint.size = size;
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(&part)?;
+    // Anything past the checkpoint may be a chunk that was only partly written.
+    file.set_len(checkpoint.offset)?;
+    file.seek(io::SeekFrom::End(0))?;
+    if checkpoint.offset > 0 {
+        log::info!(
+            "Resuming {} at {}",
+            path.display(),
+            ByteSize(checkpoint.offset)
+        );
+    }
+
+    let skip = (checkpoint.offset / chunk_size) as i32;
+    let mut download = client
+        .iter_download(&Downloadable::Media(media.clone()))
+        .skip_chunks(skip);
+    let mut throttle = max_bandwidth.map(Throttle::new);
+    let id = progress.start(path, size, checkpoint.offset);
+
+    while let Some(chunk) = download.next().await? {
+        file.write_all(&chunk)?;
+        checkpoint.offset += chunk.len() as u64;
+        state::save(&checkpoint_path, &checkpoint)?;
+        progress.advance(id, chunk.len());
+        if let Some(throttle) = &mut throttle {
+            throttle.wait(chunk.len()).await;
+        }
+    }
+    progress.finish(id);
+
+    drop(file);
+    fs::rename(&part, path)?;
+    fs::remove_file(&checkpoint_path)?;
+    Ok(())
+}
+
+/// Hashes a downloaded file and, if the same content was downloaded before under another
+/// name, replaces it with a hard link to the earlier copy so the bytes are stored once.
+/// Returns the hex SHA-256.
+fn deduplicate(download_dir: &Path, path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    let hash: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    let store = download_dir.join(STORE_DIR);
+    fs::create_dir_all(&store)?;
+    let stored = store.join(&hash);
+    if !stored.exists() {
+        fs::hard_link(path, &stored)?;
+    } else if !same_file(&stored, path)? {
+        // Link under a temporary name first so the file is never missing.
+        let temporary = path.with_extension("dedup");
+        fs::hard_link(&stored, &temporary)?;
+        fs::rename(&temporary, path)?;
+        log::debug!(
+            "{} is a copy of an earlier download, linked",
+            path.display()
+        );
+    }
+    Ok(hash)
+}
+
+fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    let (a, b) = (fs::metadata(a)?, fs::metadata(b)?);
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+/// Writes `<id>.thumb.jpg` next to the downloaded `file`: scaled down from photos, and from
+/// the preview Telegram keeps for videos. Other files get none.
+async fn thumbnail(
+    client: &Client,
+    media: &Media,
+    kind: MediaType,
+    file: &Path,
+) -> Result<Option<PathBuf>> {
+    let path = file.with_extension("thumb.jpg");
+    if path.exists() {
+        return Ok(Some(path));
+    }
+    let image = match kind {
+        MediaType::Photo => image::open(file)?,
+        MediaType::Video => match video_preview(client, media).await? {
+            Some(preview) => preview,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    // JPEG has no alpha channel.
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).into_rgb8();
+    thumbnail.save_with_format(&path, image::ImageFormat::Jpeg)?;
+    Ok(Some(path))
+}
+
+/// The biggest preview image Telegram has for a video document.
+async fn video_preview(client: &Client, media: &Media) -> Result<Option<DynamicImage>> {
+    let Some(doc) = stickers::raw_document(media) else {
+        return Ok(None);
+    };
+    let mut best: Option<(i32, &str)> = None;
+    for thumb in doc.thumbs.iter().flatten() {
+        match thumb {
+            // Small previews are sent along with the message.
+            tl::enums::PhotoSize::PhotoCachedSize(cached) => {
+                return Ok(Some(image::load_from_memory(&cached.bytes)?));
+            }
+            tl::enums::PhotoSize::Size(size) if best.is_none_or(|(w, _)| size.w > w) => {
+                best = Some((size.w, &size.r#type));
+            }
+            _ => {}
+        }
+    }
+    let Some((_, thumb_size)) = best else {
+        return Ok(None);
+    };
+    let request = tl::functions::upload::GetFile {
+        precise: false,
+        cdn_supported: false,
+        location: tl::types::InputDocumentFileLocation {
+            id: doc.id,
+            access_hash: doc.access_hash,
+            file_reference: doc.file_reference.clone(),
+            thumb_size: thumb_size.to_string(),
+        }
+        .into(),
+        offset: 0,
+        // Previews are far smaller than the 1 MiB a single request may return.
+        limit: 1 << 20,
+    };
+    match client.invoke(&request).await? {
+        tl::enums::upload::File::File(file) => Ok(Some(image::load_from_memory(&file.bytes)?)),
+        tl::enums::upload::File::CdnRedirect(_) => Ok(None),
+    }
+}
+
+/// Removes EXIF and XMP metadata (GPS position, camera, editing software) from a JPEG or
+/// PNG file in place, without re-encoding the image. Returns whether anything was removed;
+/// other formats are left alone.
+fn strip_exif(path: &Path) -> Result<bool> {
+    let data = fs::read(path)?;
+    let stripped = if data.starts_with(&[0xFF, 0xD8]) {
+        strip_jpeg(&data)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        strip_png(&data)
+    } else {
+        return Ok(false);
+    };
+    match stripped {
+        None => {
+            // Kept as downloaded, so the metadata is still there: worth knowing about.
+            log::warn!(
+                "Could not strip metadata from {}: unrecognised layout",
+                path.display()
+            );
+            Ok(false)
+        }
+        Some(stripped) if stripped.len() != data.len() => {
+            // Replaced rather than rewritten, as the file may be linked from other pins.
+            let temporary = path.with_extension("strip");
+            fs::write(&temporary, stripped)?;
+            fs::rename(&temporary, path)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Drops the APP1 (EXIF, XMP) and APP13 (IPTC) segments before the image data.
+/// `None` if the file is not a well-formed JPEG.
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = data[..2].to_vec();
+    let mut pos = 2;
+    loop {
+        let marker = *data.get(pos + 1)?;
+        if data[pos] != 0xFF {
+            return None;
+        }
+        if marker == 0xFF {
+            // Fill byte: any number of them may pad a marker.
+            pos += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            // TEM and RSTn stand alone, without a length.
+            out.extend_from_slice(&data[pos..pos + 2]);
+            pos += 2;
+            continue;
+        }
+        // Everything from the start of scan on is image data.
+        if marker == 0xDA {
+            out.extend_from_slice(&data[pos..]);
+            return Some(out);
+        }
+        let length = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        let end = pos + 2 + length;
+        let segment = data.get(pos..end)?;
+        if marker != 0xE1 && marker != 0xED {
+            out.extend_from_slice(segment);
+        }
+        pos = end;
+    }
+}
+
+/// Drops the `eXIf` chunk and the text chunks XMP is stored in.
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = data[..8].to_vec();
+    let mut pos = 8;
+    while pos < data.len() {
+        let length = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        // Length, type, data and CRC.
+        let end = pos + 12 + length;
+        let chunk = data.get(pos..end)?;
+        if !matches!(&chunk[4..8], b"eXIf" | b"iTXt" | b"tEXt" | b"zTXt") {
+            out.extend_from_slice(chunk);
+        }
+        pos = end;
+    }
+    Some(out)
+}
+
+/// Shown in place of a pin's text when it has none.
+pub fn placeholder(info: &MediaInfo) -> String {
+    let description = match (&info.file_name, info.size) {
+        (Some(name), Some(size)) => format!("{}, {}", name, ByteSize(size)),
+        (Some(name), None) => name.clone(),
+        (None, _) => format!("{:?}", info.kind).to_lowercase(),
+    };
+    match &info.quarantined {
+        Some(signature) => format!("[{}, quarantined: {}]", description, signature),
+        None => format!("[{}]", description),
+    }
+}
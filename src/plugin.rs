@@ -0,0 +1,301 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::export::{Envelope, Exporter, ExporterRegistry};
+use crate::units::ByteSize;
+use crate::{Message, Result};
+
+fn default_max_fuel() -> u64 {
+    1_000_000_000
+}
+
+fn default_max_memory() -> ByteSize {
+    ByteSize(256 * 1024 * 1024)
+}
+
+#[derive(Deserialize, Clone)]
+pub struct PluginsConfig {
+    /// Where `*.wasm` plugins are loaded from; defaults to `plugins` in the config directory.
+    pub dir: Option<PathBuf>,
+    /// Instructions, roughly, one call of a plugin may run before it is stopped.
+    #[serde(default = "default_max_fuel")]
+    pub max_fuel: u64,
+    /// Memory one instance of a plugin may grow to.
+    #[serde(default = "default_max_memory")]
+    pub max_memory: ByteSize,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        PluginsConfig {
+            dir: None,
+            max_fuel: default_max_fuel(),
+            max_memory: default_max_memory(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PluginError(String);
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// What an exporter plugin's `describe` returns.
+#[derive(Deserialize)]
+struct Description {
+    #[serde(default = "default_extension")]
+    extension: String,
+    #[serde(default = "default_content_type")]
+    content_type: String,
+}
+
+fn default_extension() -> String {
+    "txt".to_string()
+}
+
+fn default_content_type() -> String {
+    "application/octet-stream".to_string()
+}
+
+struct State {
+    limits: StoreLimits,
+}
+
+/// A WebAssembly module from the plugins directory, named after its file.
+///
+/// Plugins get no imports: no files, network, clock or environment, only the bytes they are
+/// called with. They export their `memory`, an `alloc(len: i32) -> i32` the host writes its
+/// input to, and any of these calls, which take a `(ptr: i32, len: i32)` input and return
+/// their output as `ptr << 32 | len` in an `i64`:
+///
+/// - `filter`: a message as JSON in, the message to keep as JSON out, or `null` to drop it.
+/// - `export`: the export's envelope as JSON in, the file's bytes out. An optional
+///   `describe() -> i64` returns `{"extension": ..., "content_type": ...}` for its files.
+/// - `upload` and `upload_response`: see [`crate::upload`]'s `plugin` provider.
+///
+/// Every call gets a fresh instance, so plugins keep no state between calls.
+#[derive(Clone)]
+pub struct Plugin {
+    pub name: String,
+    engine: Engine,
+    module: Module,
+    max_fuel: u64,
+    max_memory: usize,
+}
+
+impl Plugin {
+    /// Whether the plugin exports `name`.
+    pub fn has(&self, name: &str) -> bool {
+        self.module.get_export(name).is_some()
+    }
+
+    fn error(&self, call: &str, e: impl fmt::Display) -> Box<dyn std::error::Error> {
+        Box::new(PluginError(format!(
+            "Plugin {} failed in {}: {}",
+            self.name, call, e
+        )))
+    }
+
+    /// Calls `call` with `input` and returns its output.
+    pub fn call(&self, call: &str, input: Option<&[u8]>) -> Result<Vec<u8>> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.max_memory)
+            .build();
+        let mut store = Store::new(&self.engine, State { limits });
+        store.limiter(|state| &mut state.limits);
+        store
+            .set_fuel(self.max_fuel)
+            .map_err(|e| self.error(call, e))?;
+        let instance =
+            Instance::new(&mut store, &self.module, &[]).map_err(|e| self.error(call, e))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| self.error(call, "it exports no memory"))?;
+
+        let packed = match input {
+            Some(input) => {
+                let alloc = instance
+                    .get_typed_func::<i32, i32>(&mut store, "alloc")
+                    .map_err(|e| self.error(call, e))?;
+                let function = instance
+                    .get_typed_func::<(i32, i32), i64>(&mut store, call)
+                    .map_err(|e| self.error(call, e))?;
+                let len = i32::try_from(input.len()).map_err(|e| self.error(call, e))?;
+                let ptr = alloc
+                    .call(&mut store, len)
+                    .map_err(|e| self.error(call, e))?;
+                memory
+                    .write(&mut store, ptr as u32 as usize, input)
+                    .map_err(|e| self.error(call, e))?;
+                function.call(&mut store, (ptr, len))
+            }
+            None => instance
+                .get_typed_func::<(), i64>(&mut store, call)
+                .map_err(|e| self.error(call, e))?
+                .call(&mut store, ()),
+        }
+        .map_err(|e| self.error(call, e.root_cause()))?;
+        self.read(&store, memory, call, packed)
+    }
+
+    fn read(
+        &self,
+        store: &Store<State>,
+        memory: Memory,
+        call: &str,
+        packed: i64,
+    ) -> Result<Vec<u8>> {
+        let ptr = (packed as u64 >> 32) as usize;
+        let len = (packed as u64 & 0xffff_ffff) as usize;
+        memory
+            .data(store)
+            .get(ptr..ptr + len)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| self.error(call, "it returned bytes outside its memory"))
+    }
+
+    /// Calls `call` with `input` as JSON and parses its JSON output.
+    pub fn call_json<T: DeserializeOwned>(&self, call: &str, input: &impl Serialize) -> Result<T> {
+        let output = self.call(call, Some(&serde_json::to_vec(input)?))?;
+        serde_json::from_slice(&output)
+            .map_err(|e| self.error(call, format!("invalid JSON: {}", e)))
+    }
+}
+
+/// The plugins of a run, compiled once and in the order of their file names.
+pub struct Plugins {
+    plugins: Vec<Plugin>,
+}
+
+impl Plugins {
+    /// Compiles every `*.wasm` file in `dir`; a missing directory means no plugins.
+    pub fn load(dir: &Path, config: &PluginsConfig) -> Result<Self> {
+        let mut paths = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "wasm"));
+        paths.sort();
+        if paths.is_empty() {
+            return Ok(Plugins {
+                plugins: Vec::new(),
+            });
+        }
+
+        let mut engine_config = Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config).map_err(|e| PluginError(e.to_string()))?;
+        let mut plugins = Vec::new();
+        for path in paths {
+            let module = Module::from_file(&engine, &path)
+                .map_err(|e| PluginError(format!("Invalid plugin {}: {:#}", path.display(), e)))?;
+            if module.imports().len() > 0 {
+                return Err(Box::new(PluginError(format!(
+                    "Plugin {} imports from the host, which is not allowed",
+                    path.display()
+                ))));
+            }
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            plugins.push(Plugin {
+                name: name.to_string(),
+                engine: engine.clone(),
+                module,
+                max_fuel: config.max_fuel,
+                max_memory: usize::try_from(config.max_memory.0).unwrap_or(usize::MAX),
+            });
+        }
+        log::info!(
+            "Loaded plugins: {}",
+            plugins
+                .iter()
+                .map(|plugin| plugin.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        Ok(Plugins { plugins })
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Plugin> {
+        self.plugins
+            .iter()
+            .find(|plugin| plugin.name == name)
+            .ok_or_else(|| PluginError(format!("No plugin {}", name)).into())
+    }
+
+    /// Runs the messages through every plugin with a `filter`, in turn. Returns how many
+    /// were dropped.
+    pub fn filter(&self, messages: &mut Vec<Message>) -> Result<usize> {
+        let before = messages.len();
+        for plugin in self.plugins.iter().filter(|plugin| plugin.has("filter")) {
+            let mut kept = Vec::with_capacity(messages.len());
+            for message in messages.drain(..) {
+                let output: Option<Message> = plugin.call_json("filter", &message)?;
+                if let Some(mut output) = output {
+                    output.sender_id = message.sender_id;
+                    kept.push(output);
+                }
+            }
+            *messages = kept;
+        }
+        Ok(before - messages.len())
+    }
+
+    /// Registers every plugin with an `export` as a format of its name.
+    pub fn register_exporters(&self, registry: &mut ExporterRegistry) -> Result<()> {
+        for plugin in self.plugins.iter().filter(|plugin| plugin.has("export")) {
+            let description = match plugin.has("describe") {
+                true => serde_json::from_slice(&plugin.call("describe", None)?)
+                    .map_err(|e| plugin.error("describe", e))?,
+                false => Description {
+                    extension: default_extension(),
+                    content_type: default_content_type(),
+                },
+            };
+            let name = plugin.name.clone();
+            registry.register(
+                &name,
+                PluginExporter {
+                    plugin: plugin.clone(),
+                    description,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+struct PluginExporter {
+    plugin: Plugin,
+    description: Description,
+}
+
+impl Exporter for PluginExporter {
+    fn extension(&self) -> &str {
+        &self.description.extension
+    }
+
+    fn content_type(&self) -> &str {
+        &self.description.content_type
+    }
+
+    fn write(&self, export: &Envelope, out: &mut dyn Write) -> Result<()> {
+        let input = serde_json::to_vec(export)?;
+        out.write_all(&self.plugin.call("export", Some(&input))?)?;
+        Ok(())
+    }
+}
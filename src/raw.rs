@@ -0,0 +1,172 @@
+use serde_json::{Map, Number, Value};
+use std::fmt::Debug;
+
+/// A TL object as JSON, read back from its `Debug` rendering since the generated types have
+/// no serde support. Structs become objects with their type name under `_`; `Some(..)` and
+/// enum variants collapse into what they wrap, fields that are `None` are left out and byte
+/// strings become arrays of numbers.
+pub fn to_json<T: Debug>(value: &T) -> Value {
+    let text = format!("{:?}", value);
+    let mut parser = Parser {
+        text: &text,
+        pos: 0,
+    };
+    match parser.value() {
+        Some(value) if parser.rest().trim().is_empty() => value,
+        // Not expected from generated types; keep the text rather than nothing.
+        _ => Value::String(text),
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        let len = self.rest().find(|c| !f(c)).unwrap_or(self.rest().len());
+        self.pos += len;
+        &self.text[start..self.pos]
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        match self.peek()? {
+            '"' => self.string().map(Value::String),
+            '[' => {
+                self.pos += 1;
+                self.items(']').map(Value::Array)
+            }
+            '(' => {
+                self.pos += 1;
+                self.items(')').map(Value::Array)
+            }
+            c if c == '-' || c.is_ascii_digit() => self.number(),
+            c if c == '_' || c.is_alphabetic() => self.named(),
+            _ => None,
+        }
+    }
+
+    /// Comma separated values up to `close`, which Debug may end with a trailing comma.
+    fn items(&mut self, close: char) -> Option<Vec<Value>> {
+        let mut items = Vec::new();
+        while !self.eat(close) {
+            items.push(self.value()?);
+            if !self.eat(',') && self.peek() != Some(close) {
+                return None;
+            }
+        }
+        Some(items)
+    }
+
+    fn number(&mut self) -> Option<Value> {
+        let text = self.take_while(|c| c.is_ascii_alphanumeric() || "-+.".contains(c));
+        if let Ok(int) = text.parse::<i64>() {
+            return Some(Value::from(int));
+        }
+        if let Ok(int) = text.parse::<u64>() {
+            return Some(Value::from(int));
+        }
+        let float = text.parse::<f64>().ok()?;
+        Some(Number::from_f64(float).map_or(Value::Null, Value::Number))
+    }
+
+    /// A struct, tuple struct, enum variant or unit value.
+    fn named(&mut self) -> Option<Value> {
+        let name = self
+            .take_while(|c| c == '_' || c == ':' || c.is_alphanumeric())
+            .to_string();
+        if self.eat('{') {
+            let mut object = Map::new();
+            object.insert("_".to_string(), Value::String(name));
+            while !self.eat('}') {
+                self.skip_whitespace();
+                let field = self
+                    .take_while(|c| c == '_' || c.is_alphanumeric())
+                    .to_string();
+                if field.is_empty() || !self.eat(':') {
+                    return None;
+                }
+                // Left out rather than `null`, which TOML exports could not write.
+                match self.value()? {
+                    Value::Null => {}
+                    value => {
+                        object.insert(field, value);
+                    }
+                }
+                if !self.eat(',') && self.peek() != Some('}') {
+                    return None;
+                }
+            }
+            return Some(Value::Object(object));
+        }
+        if self.eat('(') {
+            let mut items = self.items(')')?;
+            return Some(match items.len() {
+                1 => items.remove(0),
+                _ => Value::Array(items),
+            });
+        }
+        Some(match name.as_str() {
+            "None" => Value::Null,
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => Value::String(name),
+        })
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.pos += 1;
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Some(out);
+                }
+                '\\' => match chars.next()?.1 {
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    '0' => out.push('\0'),
+                    'u' => {
+                        let hex: String = chars
+                            .by_ref()
+                            .map(|(_, c)| c)
+                            .skip_while(|&c| c == '{')
+                            .take_while(|&c| c != '}')
+                            .collect();
+                        out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                    }
+                    other => out.push(other),
+                },
+                c => out.push(c),
+            }
+        }
+        None
+    }
+}
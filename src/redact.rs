@@ -0,0 +1,187 @@
+use regex::{Captures, Regex};
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::{Message, Result};
+
+/// International numbers (`+34 612 345 678`) and `(555) 123-4567` style ones.
+const PHONE: &str = r"\+\d[\d .()-]{6,}\d|\(?\b\d{3}\)?[ .-]\d{3}[ .-]\d{4}\b";
+const EMAIL: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}";
+/// 13 to 19 digits, optionally grouped with spaces or dashes; only Luhn-valid ones are redacted.
+const CARD: &str = r"\b\d(?:[ -]?\d){12,18}\b";
+
+#[derive(Deserialize, Clone)]
+pub struct RedactRule {
+    pub pattern: String,
+    /// May refer to groups of the pattern as `$1` or `$name`.
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+}
+
+fn default_replacement() -> String {
+    "[redacted]".to_string()
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct RedactConfig {
+    #[serde(default)]
+    pub phones: bool,
+    #[serde(default)]
+    pub emails: bool,
+    /// Credit-card-like numbers.
+    #[serde(default)]
+    pub cards: bool,
+    /// Applied in order, after the built-in rules.
+    #[serde(default)]
+    pub rules: Vec<RedactRule>,
+}
+
+enum Rule {
+    Card(Regex),
+    Replace(Regex, String),
+}
+
+/// Rewrites message text before it is exported or uploaded.
+pub struct Redactor {
+    rules: Vec<Rule>,
+}
+
+fn luhn(digits: &str) -> bool {
+    let mut sum = 0;
+    for (i, digit) in digits
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+    {
+        sum += match i % 2 {
+            1 if digit > 4 => digit * 2 - 9,
+            1 => digit * 2,
+            _ => digit,
+        };
+    }
+    sum % 10 == 0
+}
+
+impl Redactor {
+    pub fn new(config: &RedactConfig) -> Result<Self> {
+        let mut rules = Vec::new();
+        // Cards first, so their digits are not taken for phone numbers.
+        if config.cards {
+            rules.push(Rule::Card(Regex::new(CARD)?));
+        }
+        if config.emails {
+            rules.push(Rule::Replace(Regex::new(EMAIL)?, "[email]".to_string()));
+        }
+        if config.phones {
+            rules.push(Rule::Replace(Regex::new(PHONE)?, "[phone]".to_string()));
+        }
+        for rule in &config.rules {
+            rules.push(Rule::Replace(
+                Regex::new(&rule.pattern)?,
+                rule.replacement.clone(),
+            ));
+        }
+        Ok(Redactor { rules })
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for rule in &self.rules {
+            text = match rule {
+                Rule::Card(regex) => regex
+                    .replace_all(&text, |captures: &Captures| match luhn(&captures[0]) {
+                        true => "[card]".to_string(),
+                        false => captures[0].to_string(),
+                    })
+                    .into_owned(),
+                Rule::Replace(regex, replacement) => {
+                    regex.replace_all(&text, replacement.as_str()).into_owned()
+                }
+            };
+        }
+        text
+    }
+
+    /// Redacts every string in a raw message, which repeats the text and may hold more.
+    fn redact_json(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.redact(text),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_json(item)),
+            Value::Object(object) => object.values_mut().for_each(|item| self.redact_json(item)),
+            _ => {}
+        }
+    }
+
+    pub fn apply(&self, messages: &mut [Message]) {
+        for message in messages {
+            message.text = self.redact(&message.text);
+            if let Some(quote) = &mut message.quote {
+                quote.text = self.redact(&quote.text);
+            }
+            if let Some(raw) = &mut message.raw {
+                self.redact_json(raw);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(phones: bool, emails: bool, cards: bool) -> Redactor {
+        Redactor::new(&RedactConfig {
+            phones,
+            emails,
+            cards,
+            rules: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn luhn_checks_the_digits_only() {
+        assert!(luhn("4111111111111111"));
+        assert!(luhn("4111 1111 1111 1111"));
+        assert!(luhn("5500-0000-0000-0004"));
+        assert!(!luhn("4111111111111112"));
+        assert!(!luhn("1234567812345678"));
+    }
+
+    #[test]
+    fn cards_must_pass_luhn() {
+        let redactor = redactor(false, false, true);
+        assert_eq!(
+            redactor.redact("card 4111 1111 1111 1111, ok?"),
+            "card [card], ok?"
+        );
+        assert_eq!(
+            redactor.redact("order 1234567812345678"),
+            "order 1234567812345678"
+        );
+        // Too short for a card.
+        assert_eq!(redactor.redact("ref 411111111111"), "ref 411111111111");
+    }
+
+    #[test]
+    fn phones_international_and_north_american() {
+        let redactor = redactor(true, false, false);
+        assert_eq!(redactor.redact("call +34 612 345 678"), "call [phone]");
+        assert_eq!(redactor.redact("or (555) 123-4567."), "or [phone].");
+        assert_eq!(redactor.redact("or 555.123.4567"), "or [phone]");
+        assert_eq!(
+            redactor.redact("meeting on 2024-06-12"),
+            "meeting on 2024-06-12"
+        );
+    }
+
+    #[test]
+    fn cards_go_before_phones() {
+        let redactor = redactor(true, true, true);
+        assert_eq!(
+            redactor.redact("+34 612 345 678, 4111-1111-1111-1111, admin@example.com"),
+            "[phone], [card], [email]"
+        );
+    }
+}
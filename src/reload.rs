@@ -0,0 +1,117 @@
+use notify::{RecursiveMode, Watcher as _};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::config::{self, FileConfig};
+use crate::shutdown::Shutdown;
+use crate::Result;
+
+/// Editors often write a file in several steps; changes within this window are applied once.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// The file at `path`, as a table to compare and as the config, checked as at startup.
+fn read(path: &Path) -> Result<(toml::Table, FileConfig)> {
+    let contents = fs::read_to_string(path)?;
+    let table: toml::Table = contents.parse()?;
+    let config = config::parse(path, &contents)?;
+    Ok((table, config))
+}
+
+fn usernames(config: &FileConfig) -> Vec<String> {
+    let chats = config.config.chats().into_iter();
+    chats.map(|chat| chat.username).collect()
+}
+
+/// Logs the differences between two versions of the config file.
+fn log_changes(
+    old: &toml::Table,
+    new: &toml::Table,
+    old_config: &FileConfig,
+    new_config: &FileConfig,
+) {
+    let (old_chats, new_chats) = (usernames(old_config), usernames(new_config));
+    for chat in new_chats.iter().filter(|c| !old_chats.contains(c)) {
+        log::info!("Config reload: added chat {}", chat);
+    }
+    for chat in old_chats.iter().filter(|c| !new_chats.contains(c)) {
+        log::info!("Config reload: removed chat {}", chat);
+    }
+
+    let keys = old
+        .keys()
+        .chain(new.keys().filter(|key| !old.contains_key(*key)));
+    for key in keys {
+        let (mut old, mut new) = (old.get(key).cloned(), new.get(key).cloned());
+        if key == "config" {
+            // Chat changes were logged above.
+            for table in [&mut old, &mut new].into_iter().flatten() {
+                if let Some(table) = table.as_table_mut() {
+                    table.remove("usernames");
+                    table.remove("chats");
+                }
+            }
+        }
+        if old == new {
+            continue;
+        }
+        match key.as_str() {
+            "telegram_api_creds" => {
+                log::warn!("Config reload: [telegram_api_creds] changed, restart to apply it")
+            }
+            _ => log::info!("Config reload: [{}] changed", key),
+        }
+    }
+}
+
+/// Replaces `config` whenever the file at `path` changes, until a shutdown is requested,
+/// with `overrides` from the command line applied to it again. A file that fails to parse
+/// or to check is reported and the previous config stays in use.
+pub async fn watch(
+    path: PathBuf,
+    config: Arc<Mutex<FileConfig>>,
+    overrides: impl Fn(&mut FileConfig),
+    shutdown: Shutdown,
+) -> Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let file = path.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if !event.kind.is_access() && event.paths.iter().any(|p| p == &file) {
+                let _ = tx.send(());
+            }
+        }
+    })?;
+    // The directory is watched since editors tend to replace the file instead of writing it.
+    let dir = path.parent().unwrap_or(Path::new("."));
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    let mut current = fs::read_to_string(&path)?.parse::<toml::Table>()?;
+    loop {
+        tokio::select! {
+            changed = rx.recv() => {
+                if changed.is_none() {
+                    break;
+                }
+            }
+            _ = shutdown.wait() => break,
+        }
+        tokio::time::sleep(DEBOUNCE).await;
+        while rx.try_recv().is_ok() {}
+
+        match read(&path) {
+            Ok((table, mut new_config)) if table != current => {
+                overrides(&mut new_config);
+                let mut config = config.lock().unwrap();
+                log_changes(&current, &table, &config, &new_config);
+                *config = new_config;
+                current = table;
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("Not reloading {}: {}", path.display(), e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{self, BufRead as _, IsTerminal as _, Write as _};
+use std::process::{Command, Stdio};
+
+use crate::{media, Message, Result};
+
+#[derive(Debug)]
+pub struct ReviewError(pub String);
+
+impl fmt::Display for ReviewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ReviewError {}
+
+/// One numbered entry per pin, its text indented below the chat, sender and date.
+fn listing(messages: &[Message]) -> String {
+    let mut out = String::new();
+    for (number, message) in messages.iter().enumerate() {
+        out.push_str(&format!(
+            "[{}] {}  {}  {}\n",
+            number + 1,
+            message.date,
+            message.chat_label(),
+            message.sender
+        ));
+        let mut body: Vec<String> = message.text.lines().map(str::to_string).collect();
+        if let Some(sticker) = &message.sticker {
+            body.push(format!("[sticker {}]", sticker.emoji));
+        }
+        if let Some(info) = &message.media {
+            body.push(media::placeholder(info));
+        }
+        for line in body {
+            out.push_str(&format!("    {}\n", line));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Shows `text` in `$PAGER`, `less` by default, or prints it when there is no pager.
+fn page(text: &str) -> Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next() else {
+        print!("{}", text);
+        return Ok(());
+    };
+    let mut command = Command::new(program);
+    command.args(words).stdin(Stdio::piped());
+    // Like git: quit when the list fits on the screen, and leave it there.
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let Ok(mut child) = command.spawn() else {
+        print!("{}", text);
+        return Ok(());
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may be quit before reading everything.
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+fn ask(question: &str) -> Result<String> {
+    print!("{}", question);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err(Box::new(ReviewError("Review aborted".to_string())));
+    }
+    Ok(line.trim().to_string())
+}
+
+/// The pin numbers, counted from 1, in a list like `2 5-7, 9`.
+fn parse_selection(input: &str, count: usize) -> std::result::Result<BTreeSet<usize>, String> {
+    let mut selected = BTreeSet::new();
+    for item in input.split([' ', ',']).filter(|item| !item.is_empty()) {
+        let (first, last) = item.split_once('-').unwrap_or((item, item));
+        let (Ok(first), Ok(last)) = (first.parse::<usize>(), last.parse::<usize>()) else {
+            return Err(format!("{:?} is not a pin number or range", item));
+        };
+        if first == 0 || first > last || last > count {
+            return Err(format!("{} is not between 1 and {}", item, count));
+        }
+        selected.extend(first..=last);
+    }
+    Ok(selected)
+}
+
+/// Fails unless `--review` has a terminal to ask on.
+pub fn check_terminal(non_interactive: bool) -> Result<()> {
+    match !non_interactive && io::stdin().is_terminal() {
+        true => Ok(()),
+        false => Err(Box::new(ReviewError(
+            "--review needs a terminal to ask which pins to leave out".to_string(),
+        ))),
+    }
+}
+
+/// Lists `messages` in the pager and asks which of them to leave out of the export, until
+/// the choice is confirmed. Returns the left-out pins; the others stay in `messages`.
+pub fn review(messages: &mut Vec<Message>) -> Result<Vec<Message>> {
+    let text = listing(messages);
+    page(&text)?;
+    loop {
+        let input = ask(&format!(
+            "Pins to leave out (e.g. 2 5-7), l to list them again, Enter to keep all {}: ",
+            messages.len()
+        ))?;
+        if input == "l" {
+            page(&text)?;
+            continue;
+        }
+        let left_out = match parse_selection(&input, messages.len()) {
+            Ok(left_out) => left_out,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        if left_out.is_empty() {
+            return Ok(Vec::new());
+        }
+        let answer = ask(&format!(
+            "Leave out {} and export {} pins? [Y/n] ",
+            left_out.len(),
+            messages.len() - left_out.len()
+        ))?;
+        if answer.eq_ignore_ascii_case("n") {
+            continue;
+        }
+        let (excluded, kept) = std::mem::take(messages)
+            .into_iter()
+            .enumerate()
+            .partition::<Vec<_>, _>(|(index, _)| left_out.contains(&(index + 1)));
+        *messages = kept.into_iter().map(|(_, message)| message).collect();
+        return Ok(excluded.into_iter().map(|(_, message)| message).collect());
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
+use uuid::Uuid;
+
+/// Identifies one run in file names, logs, summaries and upload payloads, so everything a
+/// run produced can be traced back to it and retried requests are recognized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunId(Uuid);
+
+impl RunId {
+    pub fn new() -> Self {
+        RunId(Uuid::new_v4())
+    }
+
+    /// The first 8 hex digits, short enough for file names.
+    pub fn short(&self) -> String {
+        self.0.simple().to_string()[..8].to_string()
+    }
+}
+
+impl Default for RunId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Serialize for RunId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.hyphenated())
+    }
+}
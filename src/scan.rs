@@ -0,0 +1,179 @@
+use serde_derive::Deserialize;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::process::Command;
+
+use crate::Result;
+
+/// clamd closes INSTREAM connections sending chunks bigger than its `StreamMaxLength`;
+/// this stays well below the default.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn default_command() -> String {
+    "clamscan".to_string()
+}
+
+fn default_socket() -> PathBuf {
+    PathBuf::from("/run/clamav/clamd.ctl")
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ClamscanConfig {
+    /// `clamscan`, or `clamdscan` to reuse a running daemon's loaded database.
+    #[serde(default = "default_command")]
+    pub command: String,
+    /// Extra arguments, e.g. `["--database=/var/lib/clamav"]`.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ClamdConfig {
+    /// `host:port` of a clamd listening on TCP; otherwise its Unix `socket` is used.
+    pub address: Option<String>,
+    #[serde(default = "default_socket")]
+    pub socket: PathBuf,
+}
+
+/// Virus scanner, selected by the `provider` key of `[media.scan]`.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum Scanner {
+    Clamscan(ClamscanConfig),
+    Clamd(ClamdConfig),
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ScanConfig {
+    #[serde(flatten)]
+    pub scanner: Scanner,
+    /// Where flagged files are moved; defaults to `quarantine` inside the media directory.
+    pub quarantine_dir: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct ScanError(String);
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+/// Scans `path`, returning the name of the signature it matched, if any.
+pub async fn scan(scanner: &Scanner, path: &Path) -> Result<Option<String>> {
+    match scanner {
+        Scanner::Clamscan(config) => clamscan(config, path).await,
+        Scanner::Clamd(config) => match &config.address {
+            Some(address) => instream(TcpStream::connect(address).await?, path).await,
+            None => instream(UnixStream::connect(&config.socket).await?, path).await,
+        },
+    }
+}
+
+async fn clamscan(config: &ClamscanConfig, path: &Path) -> Result<Option<String>> {
+    let output = Command::new(&config.command)
+        .args(&config.args)
+        .args(["--no-summary", "--infected", "--stdout", "--"])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| ScanError(format!("Could not run {}: {}", config.command, e)))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // 0 is clean, 1 is a match, anything else a failure to scan.
+    match output.status.code() {
+        Some(0) => Ok(None),
+        Some(1) => Ok(Some(signature(&stdout).unwrap_or("unknown").to_string())),
+        _ => Err(Box::new(ScanError(format!(
+            "{} failed on {}: {}",
+            config.command,
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))),
+    }
+}
+
+/// Streams the file to clamd with the INSTREAM command.
+async fn instream<S>(mut stream: S, path: &Path) -> Result<Option<String>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut chunk = vec![0; CHUNK_SIZE];
+    stream.write_all(b"zINSTREAM\0").await?;
+    loop {
+        let read = file.read(&mut chunk).await?;
+        stream.write_all(&(read as u32).to_be_bytes()).await?;
+        if read == 0 {
+            break;
+        }
+        stream.write_all(&chunk[..read]).await?;
+    }
+    // With the `z` prefix, the reply ends in a NUL.
+    let mut reply = Vec::new();
+    while reply.last() != Some(&0) {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        reply.extend_from_slice(&chunk[..read]);
+    }
+    let reply = String::from_utf8_lossy(&reply);
+    let reply = reply.trim_end_matches(['\0', '\n']);
+    match signature(reply) {
+        Some(signature) => Ok(Some(signature.to_string())),
+        None if reply.ends_with(" OK") => Ok(None),
+        None => Err(Box::new(ScanError(format!(
+            "clamd could not scan {}: {}",
+            path.display(),
+            reply
+        )))),
+    }
+}
+
+/// The signature in a `<file>: <signature> FOUND` report line.
+fn signature(report: &str) -> Option<&str> {
+    report.lines().find_map(|line| {
+        let (_, found) = line.rsplit_once(": ")?;
+        found.strip_suffix(" FOUND")
+    })
+}
+
+fn signature_file(quarantined: &Path) -> PathBuf {
+    let mut name = quarantined.as_os_str().to_owned();
+    name.push(".signature");
+    PathBuf::from(name)
+}
+
+/// Moves a flagged file into `quarantine_dir`, read-only, with the matched signature next to
+/// it so later runs neither download it again nor need to rescan it.
+pub fn quarantine(path: &Path, quarantine_dir: &Path, signature: &str) -> Result<()> {
+    fs::create_dir_all(quarantine_dir)?;
+    let target = quarantine_dir.join(path.file_name().unwrap_or_default());
+    fs::rename(path, &target)?;
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o400))?;
+    fs::write(signature_file(&target), signature)?;
+    log::warn!(
+        "{} matched {}, moved to {}",
+        path.display(),
+        signature,
+        target.display()
+    );
+    Ok(())
+}
+
+/// The signature a file in quarantine was flagged for, if `file_name` is there.
+pub fn quarantined(quarantine_dir: &Path, file_name: &str) -> Option<String> {
+    let target = quarantine_dir.join(file_name);
+    if !target.exists() {
+        return None;
+    }
+    let signature = fs::read_to_string(signature_file(&target)).unwrap_or_default();
+    Some(signature.trim().to_string())
+}
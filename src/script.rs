@@ -0,0 +1,143 @@
+use rhai::{Dynamic, Engine, Scope, AST};
+use serde_derive::Deserialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::{Message, Result};
+
+/// Name of the function every script defines.
+const ENTRY: &str = "transform";
+
+/// Keys of a serialized [`Message`], and `link`, which records derive from them; any other
+/// key a script sets ends up in `extra`.
+const MESSAGE_KEYS: &[&str] = &[
+    "link",
+    "id",
+    "chat",
+    "chat_alias",
+    "sender",
+    "sender_photo",
+    "pin_order",
+    "text",
+    "date",
+    "datetime",
+    "views",
+    "forwards",
+    "sticker",
+    "media",
+    "quote",
+    "links",
+    "language",
+    "text_translated",
+    "raw",
+    "extra",
+];
+
+#[derive(Deserialize, Clone)]
+pub struct ScriptConfig {
+    /// Rhai script defining `fn transform(message)`.
+    pub path: PathBuf,
+    /// Cap on the operations one call may run, so a runaway loop fails the run instead of
+    /// hanging it.
+    pub max_operations: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct ScriptError(String);
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// A user's Rhai script that sees every pin before it is exported or uploaded.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    name: String,
+}
+
+impl Script {
+    pub fn load(config: &ScriptConfig) -> Result<Self> {
+        let name = config.path.display().to_string();
+        let mut engine = Engine::new();
+        if let Some(max_operations) = config.max_operations {
+            engine.set_max_operations(max_operations);
+        }
+        let ast = engine
+            .compile_file(config.path.clone())
+            .map_err(|e| ScriptError(format!("Invalid script {}: {}", name, e)))?;
+        if !ast.iter_functions().any(|f| f.name == ENTRY) {
+            return Err(Box::new(ScriptError(format!(
+                "{} does not define fn {}(message)",
+                name, ENTRY
+            ))));
+        }
+        Ok(Script { engine, ast, name })
+    }
+
+    /// Calls `transform` with each message as a map. A map it returns replaces the message,
+    /// with keys that are not message fields kept in `extra`; `()` or `false` drops it.
+    /// Returns how many were dropped.
+    pub fn apply(&self, messages: &mut Vec<Message>) -> Result<usize> {
+        let before = messages.len();
+        let mut kept = Vec::with_capacity(before);
+        for message in messages.drain(..) {
+            if let Some(message) = self.transform(message)? {
+                kept.push(message);
+            }
+        }
+        *messages = kept;
+        Ok(before - messages.len())
+    }
+
+    fn transform(&self, message: Message) -> Result<Option<Message>> {
+        let argument = rhai::serde::to_dynamic(&message)?;
+        let result: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, ENTRY, (argument,))
+            .map_err(|e| {
+                ScriptError(format!(
+                    "{} failed on pin {} of {}: {}",
+                    self.name, message.id, message.chat, e
+                ))
+            })?;
+        if result.is_unit() || result.as_bool() == Ok(false) {
+            return Ok(None);
+        }
+        let Value::Object(mut fields) = rhai::serde::from_dynamic::<Value>(&result)? else {
+            return Err(Box::new(ScriptError(format!(
+                "{} returned {} for pin {} of {}, not a message map, () or false",
+                self.name,
+                result.type_name(),
+                message.id,
+                message.chat
+            ))));
+        };
+        // Unset fields are left out, as a `null` would not fit in every format.
+        fields.retain(|_, value| !value.is_null());
+        let mut extra = match fields.remove("extra") {
+            Some(Value::Object(extra)) => extra,
+            _ => Map::new(),
+        };
+        for (key, value) in fields.iter() {
+            if !MESSAGE_KEYS.contains(&key.as_str()) {
+                extra.insert(key.clone(), value.clone());
+            }
+        }
+        let mut transformed: Message =
+            serde_json::from_value(Value::Object(fields)).map_err(|e| {
+                ScriptError(format!(
+                    "{} returned an invalid message for pin {} of {}: {}",
+                    self.name, message.id, message.chat, e
+                ))
+            })?;
+        transformed.sender_id = message.sender_id;
+        transformed.extra = extra;
+        Ok(Some(transformed))
+    }
+}
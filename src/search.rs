@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Schema, Value as _, INDEXED, STORED, TEXT};
+use tantivy::{doc, Index, IndexWriter, TantivyDocument};
+
+use crate::encryption::{self, EncryptionConfig, EncryptionError};
+use crate::export;
+use crate::{Message, Result};
+
+/// Memory budget of the index writer; exports are small, so this is mostly a floor.
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// Messages of a JSON export, with or without the metadata envelope, decrypted first when
+/// its name ends in [`encryption::SUFFIX`]. `None` for tdesktop exports, which share the
+/// extension but not the layout.
+fn read_export(path: &Path, encryption: Option<&EncryptionConfig>) -> Result<Option<Vec<Message>>> {
+    let bytes = fs::read(path)?;
+    let bytes = match (
+        path.to_string_lossy().ends_with(encryption::SUFFIX),
+        encryption,
+    ) {
+        (true, Some(encryption)) => encryption.open(&bytes)?,
+        (true, None) => {
+            let reason = "it is encrypted and export.encryption is not configured";
+            return Err(Box::new(EncryptionError(reason.to_string())));
+        }
+        (false, _) => bytes,
+    };
+    let value: serde_json::Value = serde_json::from_slice(&bytes)?;
+    if export::is_tdesktop(&value) {
+        return Ok(None);
+    }
+    Ok(Some(export::read_json_value(value)?.1))
+}
+
+/// Every distinct message in the JSON exports found in `dir`, encrypted or not. The same pin
+/// turns up in every daily export, so it is only kept once.
+pub fn load_exports(dir: &Path, encryption: Option<&EncryptionConfig>) -> Result<Vec<Message>> {
+    let mut paths: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let name = path.to_string_lossy();
+                let name = name.strip_suffix(encryption::SUFFIX).unwrap_or(&name);
+                name.ends_with(".json") && !name.ends_with(".stats.json")
+            })
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    // Newest first, so the latest version of an edited pin wins.
+    paths.sort();
+    paths.reverse();
+
+    let mut seen = HashSet::new();
+    let mut messages = Vec::new();
+    for path in paths {
+        let export = match read_export(&path, encryption) {
+            Ok(Some(export)) => export,
+            Ok(None) => {
+                log::debug!("Skipping {}: a tdesktop export", path.display());
+                continue;
+            }
+            Err(e) => {
+                log::warn!("Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        for message in export {
+            let key = match message.id {
+                0 => format!("{}\0{}\0{}", message.sender, message.date, message.text),
+                id => format!("{}\0{}", message.chat.to_lowercase(), id),
+            };
+            if seen.insert(key) {
+                messages.push(message);
+            }
+        }
+    }
+    Ok(messages)
+}
+
+/// Full-text search over `messages`, best matches first. `query` uses tantivy's query syntax,
+/// e.g. `wifi password` or `sender:alice`.
+pub fn search(messages: Vec<Message>, query: &str, limit: usize) -> Result<Vec<Message>> {
+    let mut schema = Schema::builder();
+    let ordinal = schema.add_u64_field("ordinal", INDEXED | STORED);
+    let text = schema.add_text_field("text", TEXT);
+    let sender = schema.add_text_field("sender", TEXT);
+    let chat = schema.add_text_field("chat", TEXT);
+    let date = schema.add_text_field("date", TEXT);
+    let index = Index::create_in_ram(schema.build());
+
+    let mut writer: IndexWriter = index.writer(WRITER_MEMORY)?;
+    for (i, message) in messages.iter().enumerate() {
+        let sticker = message.sticker.as_ref().map(|s| s.emoji.as_str());
+        writer.add_document(doc!(
+            ordinal => i as u64,
+            text => format!("{} {}", message.text, sticker.unwrap_or_default()),
+            sender => message.sender.as_str(),
+            chat => message.chat.as_str(),
+            date => message.date.as_str(),
+        ))?;
+    }
+    writer.commit()?;
+
+    let searcher = index.reader()?.searcher();
+    let query = QueryParser::for_index(&index, vec![text, sender, chat]).parse_query(query)?;
+    let hits = searcher.search(&query, &TopDocs::with_limit(limit.max(1)).order_by_score())?;
+
+    let mut messages: Vec<Option<Message>> = messages.into_iter().map(Some).collect();
+    let mut found = Vec::new();
+    for (_score, address) in hits {
+        let document: TantivyDocument = searcher.doc(address)?;
+        let hit = document.get_first(ordinal).and_then(|value| value.as_u64());
+        if let Some(message) = hit.and_then(|i| messages.get_mut(i as usize)?.take()) {
+            found.push(message);
+        }
+    }
+    Ok(found)
+}
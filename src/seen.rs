@@ -0,0 +1,94 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::state::{self, Versioned};
+use crate::{Message, Result};
+
+/// What Telegram reported of a chat's pins: how many there are and the ID of the newest, 0
+/// without any.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PinCount {
+    pub count: usize,
+    pub top_id: i32,
+}
+
+/// IDs of the pins already uploaded, keyed by chat, for incremental runs. Every chat that a
+/// run uploaded has an entry, even without pins, so chats added to the config later are told
+/// apart.
+#[derive(Default, Serialize, Deserialize)]
+struct SeenFile {
+    chats: HashMap<String, BTreeSet<i32>>,
+    /// The pin count of every chat when its pins were last uploaded, for `skip_unchanged`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    counts: HashMap<String, PinCount>,
+}
+
+impl Versioned for SeenFile {
+    const VERSION: u32 = 1;
+
+    fn migrate(from: u32, value: Value) -> Result<Value> {
+        match from {
+            // Untagged files can only have been written by hand; read them as they are.
+            0 => Ok(value),
+            _ => unreachable!("no migration from seen pins version {}", from),
+        }
+    }
+}
+
+pub struct SeenPins {
+    path: PathBuf,
+    file: SeenFile,
+}
+
+fn chat_key(username: &str) -> String {
+    username.trim_start_matches('@').to_lowercase()
+}
+
+impl SeenPins {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(SeenPins {
+            path: path.to_path_buf(),
+            file: state::load(path)?,
+        })
+    }
+
+    pub fn contains(&self, message: &Message) -> bool {
+        self.file
+            .chats
+            .get(&chat_key(&message.chat))
+            .is_some_and(|ids| ids.contains(&message.id))
+    }
+
+    /// Whether an earlier run uploaded `chat`; if not, all its pins are new and the next upload
+    /// backfills it.
+    pub fn knows_chat(&self, chat: &str) -> bool {
+        self.file.chats.contains_key(&chat_key(chat))
+    }
+
+    pub fn add_chat(&mut self, chat: &str) {
+        self.file.chats.entry(chat_key(chat)).or_default();
+    }
+
+    pub fn insert(&mut self, message: &Message) {
+        self.file
+            .chats
+            .entry(chat_key(&message.chat))
+            .or_default()
+            .insert(message.id);
+    }
+
+    /// The pin count `chat` had when its pins were last uploaded.
+    pub fn count(&self, chat: &str) -> Option<PinCount> {
+        self.file.counts.get(&chat_key(chat)).copied()
+    }
+
+    pub fn set_count(&mut self, chat: &str, count: PinCount) {
+        self.file.counts.insert(chat_key(chat), count);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        state::save(&self.path, &self.file)
+    }
+}
@@ -0,0 +1,120 @@
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+use axum::response::{Html, IntoResponse};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::{media_url, thumbnail_url, Archive};
+use crate::{search, Message};
+
+pub type PinSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+#[derive(SimpleObject)]
+struct Pin {
+    id: i32,
+    chat: String,
+    sender: String,
+    text: String,
+    /// Day the message was sent, as `YYYY-MM-DD`.
+    date: String,
+    /// Date and time the message was sent in UTC, RFC 3339; empty for older exports.
+    datetime: String,
+    link: Option<String>,
+    sticker_emoji: Option<String>,
+    /// Viewer URL of the downloaded sticker or file, if any.
+    media: Option<String>,
+    /// Viewer URL of the file's thumbnail, if one was generated.
+    thumbnail: Option<String>,
+}
+
+impl From<Message> for Pin {
+    fn from(message: Message) -> Self {
+        Pin {
+            link: message.link(),
+            media: media_url(&message),
+            thumbnail: thumbnail_url(&message),
+            sticker_emoji: message.sticker.as_ref().map(|s| s.emoji.clone()),
+            id: message.id,
+            chat: message.chat,
+            sender: message.sender,
+            text: message.text,
+            date: message.date,
+            datetime: message.datetime,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct Chat {
+    name: String,
+    pins: usize,
+}
+
+pub struct QueryRoot;
+
+fn load(ctx: &Context<'_>) -> async_graphql::Result<Vec<Message>> {
+    let archive = ctx.data::<Arc<Archive>>()?;
+    search::load_exports(&archive.export_dir, archive.encryption.as_ref())
+        .map_err(|e| async_graphql::Error::new(e.to_string()))
+}
+
+#[Object]
+impl QueryRoot {
+    /// Every chat in the archive with its number of pins.
+    async fn chats(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Chat>> {
+        let mut counts = BTreeMap::<String, usize>::new();
+        for message in load(ctx)? {
+            *counts.entry(message.chat).or_default() += 1;
+        }
+        Ok(counts
+            .into_iter()
+            .map(|(name, pins)| Chat { name, pins })
+            .collect())
+    }
+
+    /// Archived pins, newest first, narrowed by every filter that is given. `from` and `to` are
+    /// inclusive `YYYY-MM-DD` dates; `search` ranks by relevance instead.
+    #[allow(clippy::too_many_arguments)]
+    async fn pins(
+        &self,
+        ctx: &Context<'_>,
+        chat: Option<String>,
+        sender: Option<String>,
+        from: Option<String>,
+        to: Option<String>,
+        search: Option<String>,
+        #[graphql(default = 100)] limit: usize,
+    ) -> async_graphql::Result<Vec<Pin>> {
+        let mut messages = load(ctx)?;
+        messages.retain(|m| {
+            chat.as_ref()
+                .is_none_or(|chat| m.chat.eq_ignore_ascii_case(chat))
+                && sender
+                    .as_ref()
+                    .is_none_or(|sender| m.sender.eq_ignore_ascii_case(sender))
+                && from.as_ref().is_none_or(|from| m.date >= *from)
+                && to.as_ref().is_none_or(|to| m.date <= *to)
+        });
+        let messages = match search.as_deref().map(str::trim) {
+            Some(query) if !query.is_empty() => search::search(messages, query, limit)
+                .map_err(|e| async_graphql::Error::new(e.to_string()))?,
+            _ => {
+                messages.sort_by_cached_key(|m| std::cmp::Reverse(m.sort_key()));
+                messages.truncate(limit);
+                messages
+            }
+        };
+        Ok(messages.into_iter().map(Pin::from).collect())
+    }
+}
+
+pub fn schema(archive: Arc<Archive>) -> PinSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(archive)
+        .finish()
+}
+
+/// In-browser editor for trying out queries.
+pub async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
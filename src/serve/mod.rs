@@ -0,0 +1,201 @@
+mod graphql;
+
+use async_graphql_axum::GraphQL;
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::encryption::EncryptionConfig;
+use crate::shutdown::Shutdown;
+use crate::{search, Message, Result};
+
+const INDEX: &str = include_str!("index.html");
+
+/// Where the viewer reads pins and media from.
+pub struct Archive {
+    pub export_dir: PathBuf,
+    /// Reads the encrypted exports in `export_dir`.
+    pub encryption: Option<EncryptionConfig>,
+    pub media_dir: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct PinsQuery {
+    #[serde(default)]
+    chat: String,
+    #[serde(default)]
+    q: String,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    500
+}
+
+#[derive(Serialize)]
+struct ChatSummary {
+    chat: String,
+    pins: usize,
+}
+
+#[derive(Serialize)]
+struct Pin {
+    #[serde(flatten)]
+    message: Message,
+    link: Option<String>,
+    /// Viewer URL of the downloaded sticker or file, if any. Not `media`, which the
+    /// message itself uses.
+    media_url: Option<String>,
+    /// Viewer URL of the file's thumbnail, if one was generated.
+    thumbnail_url: Option<String>,
+    /// Viewer URL of the sender's profile photo, with `media.profile_photos`.
+    sender_photo_url: Option<String>,
+}
+
+fn file_url(file: Option<&str>) -> Option<String> {
+    let name = Path::new(file?).file_name()?;
+    Some(format!("/media/{}", name.to_string_lossy()))
+}
+
+/// Viewer URL of the downloaded sticker or file.
+fn media_url(message: &Message) -> Option<String> {
+    let sticker = message.sticker.as_ref().and_then(|s| s.file.as_deref());
+    let file = message.media.as_ref().and_then(|m| m.file.as_deref());
+    file_url(sticker.or(file))
+}
+
+fn thumbnail_url(message: &Message) -> Option<String> {
+    file_url(message.media.as_ref().and_then(|m| m.thumbnail.as_deref()))
+}
+
+impl From<Message> for Pin {
+    fn from(message: Message) -> Self {
+        Pin {
+            link: message.link(),
+            media_url: media_url(&message),
+            thumbnail_url: thumbnail_url(&message),
+            sender_photo_url: file_url(message.sender_photo.as_deref()),
+            message,
+        }
+    }
+}
+
+/// Errors are shown to the user as plain text; a bad query is their mistake, anything else ours.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, self.1).into_response()
+    }
+}
+
+fn internal(e: Box<dyn std::error::Error>) -> ApiError {
+    ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+async fn index() -> Html<&'static str> {
+    Html(INDEX)
+}
+
+async fn chats(
+    State(archive): State<Arc<Archive>>,
+) -> std::result::Result<Json<Vec<ChatSummary>>, ApiError> {
+    let messages =
+        search::load_exports(&archive.export_dir, archive.encryption.as_ref()).map_err(internal)?;
+    let mut counts = BTreeMap::<String, usize>::new();
+    for message in messages {
+        *counts.entry(message.chat).or_default() += 1;
+    }
+    Ok(Json(
+        counts
+            .into_iter()
+            .map(|(chat, pins)| ChatSummary { chat, pins })
+            .collect(),
+    ))
+}
+
+async fn pins(
+    State(archive): State<Arc<Archive>>,
+    Query(query): Query<PinsQuery>,
+) -> std::result::Result<Json<Vec<Pin>>, ApiError> {
+    let mut messages =
+        search::load_exports(&archive.export_dir, archive.encryption.as_ref()).map_err(internal)?;
+    if !query.chat.is_empty() {
+        messages.retain(|message| message.chat.eq_ignore_ascii_case(&query.chat));
+    }
+    let messages = match query.q.trim() {
+        "" => {
+            messages.sort_by_cached_key(|m| std::cmp::Reverse(m.sort_key()));
+            messages.truncate(query.limit);
+            messages
+        }
+        q => search::search(messages, q, query.limit)
+            .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))?,
+    };
+    Ok(Json(messages.into_iter().map(Pin::from).collect()))
+}
+
+async fn media(
+    State(archive): State<Arc<Archive>>,
+    UrlPath(name): UrlPath<String>,
+) -> std::result::Result<Response, ApiError> {
+    let not_found = || ApiError(StatusCode::NOT_FOUND, "No such media file".to_string());
+    // Only plain file names, so requests cannot leave the media directory.
+    if name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(not_found());
+    }
+    let bytes = fs::read(archive.media_dir.join(&name)).map_err(|_| not_found())?;
+    let content_type = match Path::new(&name).extension().and_then(|ext| ext.to_str()) {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("mp4") => "video/mp4",
+        Some("webp") => "image/webp",
+        Some("webm") => "video/webm",
+        Some("tgs") => "application/x-tgsticker",
+        _ => "application/octet-stream",
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], bytes).into_response())
+}
+
+/// Serves the viewer on `addr` until a shutdown is requested. With `graphql`, `/graphql` also
+/// answers GraphQL queries and shows GraphiQL in the browser.
+pub async fn serve(
+    addr: SocketAddr,
+    archive: Archive,
+    graphql: bool,
+    shutdown: Shutdown,
+) -> Result<()> {
+    let archive = Arc::new(archive);
+    let mut app = Router::new()
+        .route("/", get(index))
+        .route("/api/chats", get(chats))
+        .route("/api/pins", get(pins))
+        .route("/media/{name}", get(media));
+    if graphql {
+        let schema = graphql::schema(archive.clone());
+        app = app.route(
+            "/graphql",
+            get(graphql::graphiql).post_service(GraphQL::new(schema)),
+        );
+    }
+    let app = app.with_state(archive);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!(
+        "Serving pinned messages on http://{}",
+        listener.local_addr()?
+    );
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move { shutdown.wait().await })
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,345 @@
+use base64::engine::general_purpose::{URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine as _;
+use grammers_session::Session;
+use rusqlite::{Connection, OpenFlags};
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+use crate::config::{ConfigPaths, FileConfig};
+use crate::telegram;
+use crate::Result;
+
+#[derive(Debug)]
+pub struct SessionImportError(String);
+
+impl fmt::Display for SessionImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SessionImportError {}
+
+fn error(message: impl Into<String>) -> Box<dyn std::error::Error> {
+    Box::new(SessionImportError(message.into()))
+}
+
+/// The authorization of another library's session: the key of the account's home data
+/// center, and the account when the session records it.
+pub struct ImportedSession {
+    pub dc_id: i32,
+    /// Telethon keeps the address it last used; Pyrogram only the data center.
+    pub addr: Option<SocketAddr>,
+    pub auth_key: [u8; 256],
+    /// User ID and whether it is a bot.
+    pub user: Option<(i64, bool)>,
+    /// Whether it is for the test data centers, when the session says.
+    pub test_mode: Option<bool>,
+}
+
+impl ImportedSession {
+    /// Reads a session: the path of a Telethon or Pyrogram `.session` SQLite file, or a
+    /// Telethon or Pyrogram string session.
+    pub fn read(source: &str) -> Result<Self> {
+        let path = Path::new(source);
+        match path.is_file() {
+            true => Self::from_sqlite(path),
+            false => Self::from_string(source.trim()),
+        }
+    }
+
+    fn from_string(session: &str) -> Result<Self> {
+        // Telethon strings start with their version, `1`; Pyrogram's with the base64 of a
+        // small data center ID, always `A`.
+        if let Some(data) = session.strip_prefix('1') {
+            let bytes = URL_SAFE
+                .decode(data)
+                .map_err(|e| error(format!("Not a Telethon string session: {}", e)))?;
+            return Self::telethon_string(&bytes);
+        }
+        let bytes = URL_SAFE_NO_PAD
+            .decode(session.trim_end_matches('='))
+            .map_err(|e| {
+                error(format!(
+                    "Neither a session file nor a string session: {}",
+                    e
+                ))
+            })?;
+        Self::pyrogram_string(&bytes)
+    }
+
+    /// `dc_id: u8`, IPv4 or IPv6 address, `port: u16`, `auth_key`.
+    fn telethon_string(bytes: &[u8]) -> Result<Self> {
+        let ip = match bytes.len() {
+            263 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(&bytes[1..5])?)),
+            275 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&bytes[1..17])?)),
+            len => {
+                return Err(error(format!(
+                    "Unknown Telethon string session of {} bytes",
+                    len
+                )))
+            }
+        };
+        let rest = &bytes[bytes.len() - 258..];
+        Ok(ImportedSession {
+            dc_id: bytes[0].into(),
+            addr: Some(SocketAddr::new(ip, u16::from_be_bytes([rest[0], rest[1]]))),
+            auth_key: rest[2..].try_into()?,
+            user: None,
+            test_mode: None,
+        })
+    }
+
+    /// The layouts of Pyrogram 2 (`dc_id`, `api_id`, `test_mode`, `auth_key`, 64-bit
+    /// `user_id`, `is_bot`) and of Pyrogram 1, without `api_id` and with a 32 or 64-bit
+    /// `user_id`.
+    fn pyrogram_string(bytes: &[u8]) -> Result<Self> {
+        let (test_mode, key_at, user_id) = match bytes.len() {
+            271 => (bytes[5], 6, i64::from_be_bytes(bytes[262..270].try_into()?)),
+            267 => (bytes[1], 2, i64::from_be_bytes(bytes[258..266].try_into()?)),
+            263 => (
+                bytes[1],
+                2,
+                u32::from_be_bytes(bytes[258..262].try_into()?).into(),
+            ),
+            len => {
+                return Err(error(format!(
+                    "Unknown Pyrogram string session of {} bytes",
+                    len
+                )))
+            }
+        };
+        Ok(ImportedSession {
+            dc_id: bytes[0].into(),
+            addr: None,
+            auth_key: bytes[key_at..key_at + 256].try_into()?,
+            user: Some((user_id, bytes[bytes.len() - 1] != 0)),
+            test_mode: Some(test_mode != 0),
+        })
+    }
+
+    /// Both libraries keep a `sessions` table; Telethon's has the server address, Pyrogram's
+    /// the account.
+    fn from_sqlite(path: &Path) -> Result<Self> {
+        let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut statement = db.prepare("SELECT * FROM sessions LIMIT 1")?;
+        let columns: Vec<String> = statement
+            .column_names()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let column = |name: &str| columns.iter().position(|c| c == name);
+        let mut rows = statement.query([])?;
+        let row = rows
+            .next()?
+            .ok_or_else(|| error(format!("{} holds no session", path.display())))?;
+
+        let key_column = column("auth_key").ok_or_else(|| error("No auth_key in sessions"))?;
+        let auth_key: Vec<u8> = row.get(key_column)?;
+        let auth_key = auth_key
+            .try_into()
+            .map_err(|_| error(format!("{} is not logged in", path.display())))?;
+        let dc_column = column("dc_id").ok_or_else(|| error("No dc_id in sessions"))?;
+        let dc_id: i32 = row.get(dc_column)?;
+
+        if let (Some(address), Some(port)) = (column("server_address"), column("port")) {
+            let address: String = row.get(address)?;
+            let port: u16 = row.get(port)?;
+            return Ok(ImportedSession {
+                dc_id,
+                addr: address.parse().ok().map(|ip| SocketAddr::new(ip, port)),
+                auth_key,
+                user: None,
+                test_mode: None,
+            });
+        }
+        let user = match (column("user_id"), column("is_bot")) {
+            (Some(user_id), Some(is_bot)) => {
+                let user_id: Option<i64> = row.get(user_id)?;
+                let is_bot: Option<bool> = row.get(is_bot)?;
+                user_id.map(|id| (id, is_bot.unwrap_or_default()))
+            }
+            _ => None,
+        };
+        let test_mode = match column("test_mode") {
+            Some(test_mode) => row.get(test_mode)?,
+            None => None,
+        };
+        Ok(ImportedSession {
+            dc_id,
+            addr: None,
+            auth_key,
+            user,
+            test_mode,
+        })
+    }
+
+    fn to_session(&self, test_dc: bool) -> Result<Session> {
+        let session = Session::new();
+        let addr = match self.addr {
+            Some(addr) => addr,
+            None => telegram::dc_addr(self.dc_id, test_dc)?,
+        };
+        session.insert_dc(self.dc_id, addr, self.auth_key);
+        // The library connects to the data center of the session's user; when the session
+        // does not know the account, a placeholder does until it is asked.
+        let (id, bot) = self.user.unwrap_or((0, false));
+        session.set_user(id, self.dc_id, bot);
+        Ok(session)
+    }
+}
+
+/// Replaces the stored session with one imported from `source`, see
+/// [`ImportedSession::read`], and checks that it is authorized. Refuses to replace an
+/// existing session unless `force` is set.
+pub async fn import(
+    config: &FileConfig,
+    paths: &ConfigPaths,
+    source: &str,
+    force: bool,
+) -> Result<()> {
+    let imported = ImportedSession::read(source)?;
+    let test_dc = config.telegram_api_creds.test_dc;
+    if imported
+        .test_mode
+        .is_some_and(|test_mode| test_mode != test_dc)
+    {
+        return Err(error(match test_dc {
+            false => "The session is for Telegram's test data centers; pass --test-dc",
+            true => "The session is not for Telegram's test data centers; drop --test-dc",
+        }));
+    }
+    if paths.session_file.exists() && !force {
+        return Err(error(format!(
+            "{} already exists; log out first or pass --force to replace it",
+            paths.session_file.display()
+        )));
+    }
+
+    fs::write(&paths.session_file, imported.to_session(test_dc)?.save())?;
+    let client = telegram::connect(config, &paths.session_file, false).await?;
+    if !client.is_authorized().await? {
+        fs::remove_file(&paths.session_file)?;
+        return Err(error("The imported session is not logged in"));
+    }
+    let me = client.get_me().await?;
+    client
+        .session()
+        .set_user(me.id(), imported.dc_id, me.is_bot());
+    client.session().save_to_file(&paths.session_file)?;
+    // Access hashes are per account; the cache may hold those of another one.
+    match fs::remove_file(&paths.cache_file) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    match me.username() {
+        Some(username) => println!("Imported the session of @{}", username),
+        None => println!("Imported the session of {}", me.full_name()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth_key() -> Vec<u8> {
+        (0..=255).collect()
+    }
+
+    #[test]
+    fn telethon_ipv4() {
+        let mut bytes = vec![2, 149, 154, 167, 51];
+        bytes.extend_from_slice(&443u16.to_be_bytes());
+        bytes.extend_from_slice(&auth_key());
+        let session = ImportedSession::telethon_string(&bytes).unwrap();
+        assert_eq!(session.dc_id, 2);
+        assert_eq!(session.addr, Some("149.154.167.51:443".parse().unwrap()));
+        assert_eq!(session.auth_key.to_vec(), auth_key());
+        assert_eq!(session.user, None);
+    }
+
+    #[test]
+    fn telethon_ipv6() {
+        let ip: Ipv6Addr = "2001:67c:4e8:f002::a".parse().unwrap();
+        let mut bytes = vec![4];
+        bytes.extend_from_slice(&ip.octets());
+        bytes.extend_from_slice(&80u16.to_be_bytes());
+        bytes.extend_from_slice(&auth_key());
+        let session = ImportedSession::telethon_string(&bytes).unwrap();
+        assert_eq!(session.dc_id, 4);
+        assert_eq!(session.addr, Some(SocketAddr::new(ip.into(), 80)));
+    }
+
+    #[test]
+    fn telethon_string_session() {
+        let mut bytes = vec![2, 149, 154, 167, 51];
+        bytes.extend_from_slice(&443u16.to_be_bytes());
+        bytes.extend_from_slice(&auth_key());
+        let session = format!("1{}", URL_SAFE.encode(&bytes));
+        let session = ImportedSession::from_string(&session).unwrap();
+        assert_eq!(session.dc_id, 2);
+    }
+
+    #[test]
+    fn pyrogram_2() {
+        let mut bytes = vec![5];
+        bytes.extend_from_slice(&12345u32.to_be_bytes());
+        bytes.push(1);
+        bytes.extend_from_slice(&auth_key());
+        bytes.extend_from_slice(&5_000_000_000i64.to_be_bytes());
+        bytes.push(0);
+        assert_eq!(bytes.len(), 271);
+        let session = ImportedSession::pyrogram_string(&bytes).unwrap();
+        assert_eq!(session.dc_id, 5);
+        assert_eq!(session.test_mode, Some(true));
+        assert_eq!(session.auth_key.to_vec(), auth_key());
+        assert_eq!(session.user, Some((5_000_000_000, false)));
+        assert_eq!(session.addr, None);
+    }
+
+    #[test]
+    fn pyrogram_1_with_64_bit_user() {
+        let mut bytes = vec![2, 0];
+        bytes.extend_from_slice(&auth_key());
+        bytes.extend_from_slice(&(-42i64).to_be_bytes());
+        bytes.push(1);
+        let session = ImportedSession::pyrogram_string(&bytes).unwrap();
+        assert_eq!(session.test_mode, Some(false));
+        assert_eq!(session.user, Some((-42, true)));
+    }
+
+    #[test]
+    fn pyrogram_1_with_32_bit_user() {
+        let mut bytes = vec![1, 0];
+        bytes.extend_from_slice(&auth_key());
+        bytes.extend_from_slice(&777_000u32.to_be_bytes());
+        bytes.push(0);
+        let session = ImportedSession::pyrogram_string(&bytes).unwrap();
+        assert_eq!(session.dc_id, 1);
+        assert_eq!(session.auth_key.to_vec(), auth_key());
+        assert_eq!(session.user, Some((777_000, false)));
+    }
+
+    #[test]
+    fn pyrogram_string_session() {
+        let mut bytes = vec![5];
+        bytes.extend_from_slice(&12345u32.to_be_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&auth_key());
+        bytes.extend_from_slice(&99i64.to_be_bytes());
+        bytes.push(0);
+        let session = URL_SAFE_NO_PAD.encode(&bytes);
+        let session = ImportedSession::from_string(&session).unwrap();
+        assert_eq!(session.user, Some((99, false)));
+    }
+
+    #[test]
+    fn unknown_lengths_are_refused() {
+        assert!(ImportedSession::telethon_string(&[0; 100]).is_err());
+        assert!(ImportedSession::pyrogram_string(&[0; 100]).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Notify;
+
+/// Set once SIGINT or SIGTERM arrives. Long-running work checks it between chats so the
+/// in-flight chat finishes and state gets flushed before exiting.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl Shutdown {
+    /// Starts listening for termination signals in the background.
+    pub fn listen() -> std::io::Result<Self> {
+        let shutdown = Shutdown::default();
+        let mut sigint = signal(SignalKind::interrupt())?;
+        let mut sigterm = signal(SignalKind::terminate())?;
+
+        let handle = shutdown.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = sigint.recv() => log::info!("Received SIGINT, shutting down"),
+                _ = sigterm.recv() => log::info!("Received SIGTERM, shutting down"),
+            }
+            handle.requested.store(true, Ordering::SeqCst);
+            handle.notify.notify_waiters();
+        });
+
+        Ok(shutdown)
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once shutdown has been requested.
+    pub async fn wait(&self) {
+        let notified = self.notify.notified();
+        if self.is_requested() {
+            return;
+        }
+        notified.await;
+    }
+}
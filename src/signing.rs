@@ -0,0 +1,136 @@
+use minisign::SecretKeyBox;
+use serde_derive::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt as _;
+use tokio::process::Command;
+
+use crate::export::ExportFile;
+use crate::Result;
+
+fn default_command() -> String {
+    "gpg".to_string()
+}
+
+#[derive(Deserialize, Clone)]
+pub struct GpgConfig {
+    #[serde(default = "default_command")]
+    pub command: String,
+    /// Key to sign with, as `--local-user` takes it; gpg's default key otherwise.
+    pub key: Option<String>,
+    /// Extra arguments, e.g. `["--homedir", "/path/to/gnupg"]`.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MinisignConfig {
+    /// Secret key, as written by `minisign -G`.
+    pub key_file: PathBuf,
+    /// File holding the key's password; leave it out for a key made with `-W`.
+    pub password_file: Option<PathBuf>,
+}
+
+/// Signing tool, selected by the `provider` key of `[signing]`.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum SigningConfig {
+    Gpg(GpgConfig),
+    Minisign(MinisignConfig),
+}
+
+#[derive(Debug)]
+pub struct SigningError(String);
+
+impl fmt::Display for SigningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SigningError {}
+
+impl SigningConfig {
+    /// A detached signature of `file`, to upload next to it: `<name>.asc` from gpg,
+    /// `<name>.minisig` from minisign.
+    pub async fn sign(&self, file: &ExportFile) -> Result<ExportFile> {
+        match self {
+            SigningConfig::Gpg(config) => Ok(ExportFile {
+                name: format!("{}.asc", file.name),
+                content_type: "application/pgp-signature".to_string(),
+                bytes: gpg(config, &file.bytes).await?,
+            }),
+            SigningConfig::Minisign(config) => Ok(ExportFile {
+                name: format!("{}.minisig", file.name),
+                content_type: "text/plain".to_string(),
+                bytes: minisign(config, file)?.into_bytes(),
+            }),
+        }
+    }
+}
+
+async fn gpg(config: &GpgConfig, bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut command = Command::new(&config.command);
+    command.args(&config.args);
+    if let Some(key) = &config.key {
+        command.arg("--local-user").arg(key);
+    }
+    let mut child = command
+        .args([
+            "--batch",
+            "--yes",
+            "--armor",
+            "--detach-sign",
+            "--output",
+            "-",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| SigningError(format!("Could not run {}: {}", config.command, e)))?;
+    // gpg reads all of its input before writing the signature, so this cannot block on a
+    // full stdout. When it stops reading early, its error tells why.
+    let written = match child.stdin.take() {
+        Some(mut stdin) => stdin.write_all(bytes).await,
+        None => Ok(()),
+    };
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(Box::new(SigningError(format!(
+            "{} failed to sign ({}): {}",
+            config.command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    written?;
+    Ok(output.stdout)
+}
+
+/// Signs with the file name in the trusted comment, so a signature can't vouch for another
+/// file renamed to it.
+fn minisign(config: &MinisignConfig, file: &ExportFile) -> Result<String> {
+    let path = &config.key_file;
+    let key_box = fs::read_to_string(path)
+        .map_err(|e| SigningError(format!("Could not read {}: {}", path.display(), e)))?;
+    let key_box = SecretKeyBox::from_string(&key_box)?;
+    let key = match &config.password_file {
+        Some(password_file) => {
+            let password = fs::read_to_string(password_file)?;
+            let password = password.trim_end_matches(['\r', '\n']).to_string();
+            key_box.into_secret_key(Some(password))
+        }
+        None => key_box.into_unencrypted_secret_key(),
+    }
+    .map_err(|e| SigningError(format!("Invalid key {}: {}", path.display(), e)))?;
+    let comment = format!(
+        "timestamp:{}\tfile:{}",
+        chrono::Utc::now().timestamp(),
+        file.name
+    );
+    let signature = minisign::sign(None, &key, &file.bytes[..], Some(&comment), None)?;
+    Ok(signature.into_string())
+}
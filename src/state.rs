@@ -0,0 +1,78 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::Result;
+
+/// A JSON state file that records its layout version, so files written by older releases
+/// are migrated forward instead of being misread.
+pub trait Versioned: Serialize + DeserializeOwned + Default {
+    /// Version written by this release.
+    const VERSION: u32;
+
+    /// Upgrades the raw contents of a version `from` file to version `from + 1`, or fails if
+    /// such a file can't be read; [`load`] names the file in the error.
+    fn migrate(from: u32, value: Value) -> Result<Value>;
+}
+
+#[derive(Debug)]
+pub struct StateError(pub(crate) String);
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Loads the state at `path`, migrating it to the current version. A missing file
+/// yields the default state; files without a `version` are treated as version 0.
+pub fn load<T: Versioned>(path: &Path) -> Result<T> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut value: Value = serde_json::from_str(&contents)?;
+    let mut version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+
+    if version > T::VERSION {
+        return Err(Box::new(StateError(format!(
+            "{} was written by a newer release (version {}, this release understands up to {})",
+            path.display(),
+            version,
+            T::VERSION
+        ))));
+    }
+
+    while version < T::VERSION {
+        value = T::migrate(version, value)
+            .map_err(|e| StateError(format!("{}: {}", path.display(), e)))?;
+        version += 1;
+        log::info!("Migrated {} to version {}", path.display(), version);
+    }
+
+    if let Value::Object(map) = &mut value {
+        map.remove("version");
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Writes `state` to `path` tagged with the current version, replacing the old file
+/// atomically so an interrupted run can't leave it half-written.
+pub fn save<T: Versioned>(path: &Path, state: &T) -> Result<()> {
+    let mut value = serde_json::to_value(state)?;
+    if let Value::Object(map) = &mut value {
+        map.insert("version".to_string(), Value::from(T::VERSION));
+    }
+
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(&value)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
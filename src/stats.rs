@@ -0,0 +1,82 @@
+use serde_derive::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use crate::Message;
+
+#[derive(Serialize, Debug)]
+pub struct SenderStats {
+    pub sender: String,
+    pub pins: usize,
+    pub words: usize,
+    pub characters: usize,
+    pub first_pin: String,
+    pub last_pin: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Stats {
+    pub messages: usize,
+    pub words: usize,
+    pub characters: usize,
+    pub senders: Vec<SenderStats>,
+}
+
+impl Stats {
+    pub fn from_messages(messages: &[Message]) -> Self {
+        let mut senders = BTreeMap::<&str, SenderStats>::new();
+        let mut stats = Stats {
+            messages: messages.len(),
+            ..Default::default()
+        };
+
+        for message in messages {
+            let words = message.text.split_whitespace().count();
+            let characters = message.text.chars().count();
+            stats.words += words;
+            stats.characters += characters;
+            // Left out of the export with `fields`.
+            if message.sender.is_empty() {
+                continue;
+            }
+
+            let sender = senders
+                .entry(message.sender.as_str())
+                .or_insert_with(|| SenderStats {
+                    sender: message.sender.clone(),
+                    pins: 0,
+                    words: 0,
+                    characters: 0,
+                    first_pin: message.date.clone(),
+                    last_pin: message.date.clone(),
+                });
+            sender.pins += 1;
+            sender.words += words;
+            sender.characters += characters;
+            // ISO dates compare correctly as strings.
+            if message.date < sender.first_pin {
+                sender.first_pin = message.date.clone();
+            }
+            if message.date > sender.last_pin {
+                sender.last_pin = message.date.clone();
+            }
+        }
+
+        stats.senders = senders.into_values().collect();
+        stats.senders.sort_by_key(|sender| Reverse(sender.pins));
+        stats
+    }
+
+    pub fn print(&self) {
+        println!(
+            "Stats: {} messages, {} words, {} characters",
+            self.messages, self.words, self.characters
+        );
+        for sender in &self.senders {
+            println!(
+                "  {:<24} {:>5} pins {:>7} words  {} .. {}",
+                sender.sender, sender.pins, sender.words, sender.first_pin, sender.last_pin
+            );
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use grammers_client::types::Media;
+use grammers_client::Client;
+use grammers_tl_types as tl;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::media::{self, Progress};
+use crate::throttle::Bandwidth;
+use crate::Result;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StickerInfo {
+    pub set_name: Option<String>,
+    pub emoji: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Size of the sticker file in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// Looks up sticker sets and custom emoji, remembering answers for the rest of the run.
+#[derive(Default)]
+pub struct StickerResolver {
+    set_names: HashMap<i64, Option<String>>,
+    emoji_alts: HashMap<i64, String>,
+}
+
+pub(crate) fn raw_document(media: &Media) -> Option<tl::types::Document> {
+    match tl::enums::MessageMedia::from(media.clone()) {
+        tl::enums::MessageMedia::Document(tl::types::MessageMediaDocument {
+            document: Some(tl::enums::Document::Document(doc)),
+            ..
+        }) => Some(doc),
+        _ => None,
+    }
+}
+
+fn file_extension(mime_type: &str) -> &'static str {
+    match mime_type {
+        "application/x-tgsticker" => "tgs",
+        "video/webm" => "webm",
+        _ => "webp",
+    }
+}
+
+impl StickerResolver {
+    async fn set_name(
+        &mut self,
+        client: &Client,
+        stickerset: &tl::enums::InputStickerSet,
+    ) -> Result<Option<String>> {
+        let set = match stickerset {
+            tl::enums::InputStickerSet::Id(set) => set,
+            tl::enums::InputStickerSet::ShortName(set) => return Ok(Some(set.short_name.clone())),
+            _ => return Ok(None),
+        };
+
+        if let Some(name) = self.set_names.get(&set.id) {
+            return Ok(name.clone());
+        }
+
+        let request = tl::functions::messages::GetStickerSet {
+            stickerset: stickerset.clone(),
+            hash: 0,
+        };
+        let name = match client.invoke(&request).await? {
+            tl::enums::messages::StickerSet::Set(set) => {
+                let tl::enums::StickerSet::Set(set) = set.set;
+                Some(set.short_name)
+            }
+            tl::enums::messages::StickerSet::NotModified => None,
+        };
+        self.set_names.insert(set.id, name.clone());
+
+        Ok(name)
+    }
+
+    /// Builds the export record for a sticker, downloading the file into `download_dir`
+    /// when one is given.
+    pub async fn sticker_info(
+        &mut self,
+        client: &Client,
+        media: &Media,
+        download_dir: Option<&Path>,
+        max_bandwidth: Option<Bandwidth>,
+    ) -> Result<StickerInfo> {
+        let doc = raw_document(media);
+
+        let mut set_name = None;
+        let mut emoji = String::new();
+        if let Some(doc) = &doc {
+            for attr in &doc.attributes {
+                if let tl::enums::DocumentAttribute::Sticker(attr) = attr {
+                    set_name = self.set_name(client, &attr.stickerset).await?;
+                    emoji = attr.alt.clone();
+                }
+            }
+        }
+
+        let mut file = None;
+        let size = doc.as_ref().map(|doc| doc.size as u64);
+        if let (Some(dir), Some(doc)) = (download_dir, &doc) {
+            fs::create_dir_all(dir)?;
+            let path = dir.join(format!("{}.{}", doc.id, file_extension(&doc.mime_type)));
+            if !path.exists() {
+                let size = doc.size as u64;
+                let progress = Progress::default();
+                media::download(client, media, &path, size, max_bandwidth, &progress).await?;
+            }
+            file = Some(path.to_string_lossy().into_owned());
+        }
+
+        Ok(StickerInfo {
+            set_name,
+            emoji,
+            file,
+            size,
+        })
+    }
+
+    /// Replaces every custom emoji in `text` with its plain alt emoji, so exports read
+    /// correctly outside of Telegram.
+    pub async fn resolve_custom_emoji(
+        &mut self,
+        client: &Client,
+        text: &str,
+        entities: Option<&Vec<tl::enums::MessageEntity>>,
+    ) -> Result<String> {
+        let spans: Vec<_> = entities
+            .into_iter()
+            .flatten()
+            .filter_map(|entity| match entity {
+                tl::enums::MessageEntity::CustomEmoji(emoji) => Some(emoji.clone()),
+                _ => None,
+            })
+            .collect();
+
+        if spans.is_empty() {
+            return Ok(text.to_string());
+        }
+
+        let missing: Vec<i64> = spans
+            .iter()
+            .map(|span| span.document_id)
+            .filter(|id| !self.emoji_alts.contains_key(id))
+            .collect();
+        if !missing.is_empty() {
+            let request = tl::functions::messages::GetCustomEmojiDocuments {
+                document_id: missing,
+            };
+            for doc in client.invoke(&request).await? {
+                let tl::enums::Document::Document(doc) = doc else {
+                    continue;
+                };
+                for attr in doc.attributes {
+                    if let tl::enums::DocumentAttribute::CustomEmoji(attr) = attr {
+                        self.emoji_alts.insert(doc.id, attr.alt);
+                    }
+                }
+            }
+        }
+
+        // Entity offsets are measured in UTF-16 code units.
+        let utf16: Vec<u16> = text.encode_utf16().collect();
+        let mut resolved = Vec::with_capacity(utf16.len());
+        let mut cursor = 0;
+        for span in spans {
+            let start = (span.offset as usize).min(utf16.len());
+            let end = (start + span.length as usize).min(utf16.len());
+            if start < cursor {
+                continue;
+            }
+            resolved.extend_from_slice(&utf16[cursor..start]);
+            match self.emoji_alts.get(&span.document_id) {
+                Some(alt) => resolved.extend(alt.encode_utf16()),
+                None => resolved.extend_from_slice(&utf16[start..end]),
+            }
+            cursor = end;
+        }
+        resolved.extend_from_slice(&utf16[cursor..]);
+
+        Ok(String::from_utf16_lossy(&resolved))
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::run::RunId;
+use crate::stats::Stats;
+use crate::telegram::{CountMismatch, SkippedChat};
+use crate::upload::{AccountInfo, UploadResult};
+
+/// Totals gathered over a run, printed once everything is done.
+#[derive(Default)]
+pub struct RunSummary {
+    pub run_id: RunId,
+    pub chats: usize,
+    pub skipped: Vec<SkippedChat>,
+    /// Chats whose pins changed while they were fetched; not an error, they are exported as
+    /// listed.
+    pub mismatches: Vec<CountMismatch>,
+    /// Chats left out with `skip_unchanged`.
+    pub unchanged: Vec<String>,
+    /// Chats new to an incremental setup, exported in full this once, with their pin count.
+    pub backfilled: Vec<(String, usize)>,
+    pub messages: usize,
+    /// Export files written locally.
+    pub exported: Vec<PathBuf>,
+    pub uploads: Vec<UploadResult>,
+    /// Whether an upload failed, leaving the pins to retry next run.
+    pub upload_failed: bool,
+    /// Quota of the upload accounts, checked before the run.
+    pub accounts: Vec<AccountInfo>,
+    pub stats: Option<Stats>,
+}
+
+impl RunSummary {
+    pub fn print(&self) {
+        println!("Summary:");
+        println!("  run:      {}", self.run_id);
+        println!("  chats:    {}", self.chats);
+        for chat in &self.skipped {
+            println!("  skipped:  {} ({})", chat.name, chat.reason);
+        }
+        for mismatch in &self.mismatches {
+            println!("  changed:  {}", mismatch);
+        }
+        if !self.unchanged.is_empty() {
+            println!("  unchanged: {}", self.unchanged.join(", "));
+        }
+        for (chat, pins) in &self.backfilled {
+            println!("  backfill: {} ({} pins)", chat, pins);
+        }
+        println!("  messages: {}", self.messages);
+        for path in &self.exported {
+            println!("  exported: {}", path.display());
+        }
+        if self.uploads.is_empty() {
+            println!("  uploaded: no");
+        }
+        for upload in &self.uploads {
+            println!("  uploaded: {}", upload);
+        }
+        for account in &self.accounts {
+            println!("  account:  {}", account);
+        }
+        if let Some(stats) = &self.stats {
+            stats.print();
+        }
+    }
+
+    /// The run's outcome as an error, so it maps to an exit code: a failed upload is worse
+    /// than chats left out.
+    pub fn into_result(self) -> crate::Result<()> {
+        if self.upload_failed {
+            return Err(Box::new(UploadFailedError));
+        }
+        match self.skipped.len() {
+            0 => Ok(()),
+            skipped => Err(Box::new(PartialFetchError(skipped))),
+        }
+    }
+}
+
+/// Some chats could not be fetched; the export only has the others.
+#[derive(Debug)]
+pub struct PartialFetchError(pub usize);
+
+impl fmt::Display for PartialFetchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} chats could not be fetched", self.0)
+    }
+}
+
+impl std::error::Error for PartialFetchError {}
+
+/// The export was made but could not be uploaded.
+#[derive(Debug)]
+pub struct UploadFailedError;
+
+impl fmt::Display for UploadFailedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Uploading the export failed")
+    }
+}
+
+impl std::error::Error for UploadFailedError {}
@@ -0,0 +1,205 @@
+use chrono::{Days, NaiveDate, Utc};
+use std::time::Instant;
+
+use crate::config::{ChatConfig, FileConfig, TopicRef};
+use crate::export::{Envelope, ExportFile, ExporterRegistry};
+use crate::redact::Redactor;
+use crate::run::RunId;
+use crate::stats::Stats;
+use crate::stickers::StickerInfo;
+use crate::telegram::{self, FetchedPins, MockChat, MockPin};
+use crate::{translate, ChatInfo, Message, Result};
+
+const CHATS: &[&str] = &[
+    "announcements",
+    "building_a",
+    "@neighbours",
+    "tenants_board",
+];
+/// Titles of [`CHATS`]; the last has no username and is only reached by its title.
+const TITLES: &[&str] = &["Announcements", "Building A", "Neighbours", "Tenants board"];
+/// Forum topics of `building_a`.
+const TOPICS: &[(i32, &str)] = &[(1, "General"), (7, "Repairs")];
+const REPAIRS_TOPIC: i32 = 7;
+const SENDERS: &[&str] = &["Ane", "Iker", "Maite", "Jon", "Lucía", "Pedro", "Amaia"];
+const TEXTS: &[&str] = &[
+    "Water will be cut on Tuesday from 9:00 to 13:00 for maintenance.",
+    "Reminder: the community meeting is on 2024-06-12 at 19:30 in the lobby.",
+    "Lost keys found near the mailboxes, ask the concierge.",
+    "Mañana pasa el técnico del ascensor, disculpad las molestias.",
+    "Call +34 612 345 678 or write to admin@example.com for the garage remotes.",
+    "Bihar goizean garbiketa egingo da lorategian.",
+    "New recycling bins 🗑️ are in the courtyard, please sort glass and paper.",
+];
+
+/// `count` made-up pins spread over chats, senders and two years of dates, with text that
+/// exercises redaction, language detection and the exporters, for benchmarks that should not
+/// need Telegram. The same count always gives the same pins.
+pub fn messages(count: usize) -> Vec<Message> {
+    let start = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap_or_default();
+    (0..count)
+        .map(|i| {
+            let date = start + Days::new((i * 730 / count.max(1)) as u64);
+            let text = TEXTS[i % TEXTS.len()];
+            Message {
+                id: i as i32 + 1,
+                chat: CHATS[i % CHATS.len()].to_string(),
+                chat_alias: None,
+                chat_id: None,
+                chat_kind: None,
+                sender: SENDERS[i % SENDERS.len()].to_string(),
+                sender_photo: None,
+                // Newest at the top, as Telegram lists them.
+                pin_order: Some(((count - 1 - i) / CHATS.len()) as u32 + 1),
+                sender_id: (i % SENDERS.len()) as i64 + 1000,
+                text: format!("{} #{}", text, i),
+                date: date.to_string(),
+                datetime: format!("{}T{:02}:{:02}:00Z", date, 8 + i % 12, i % 60),
+                views: (i % 3 == 0).then_some(i as i32 * 7),
+                forwards: (i % 3 == 0).then_some(i as i32 % 11),
+                sticker: (i % 50 == 0).then(|| StickerInfo {
+                    set_name: Some("synthetic".to_string()),
+                    emoji: "👍".to_string(),
+                    file: None,
+                    size: None,
+                }),
+                media: None,
+                quote: None,
+                links: Vec::new(),
+                language: None,
+                text_translated: None,
+                raw: None,
+                extra: Default::default(),
+            }
+        })
+        .collect()
+}
+
+/// The chats [`messages`] puts pins in.
+pub fn chats() -> Vec<ChatInfo> {
+    CHATS
+        .iter()
+        .enumerate()
+        .map(|(i, name)| ChatInfo {
+            name: name.to_string(),
+            id: i as i64 + 1,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// [`chats`] as the mock fetch source has them, with `count` pins between them: those of
+/// [`messages`], but dated back from today so that `max_age_days` leaves some out, and half
+/// of those in `building_a` posted in its Repairs topic.
+pub fn dialogs(count: usize) -> Vec<MockChat> {
+    let today = Utc::now();
+    let mut dialogs: Vec<_> = CHATS
+        .iter()
+        .zip(TITLES)
+        .enumerate()
+        .map(|(i, (name, title))| MockChat {
+            id: i as i64 + 1,
+            title: title.to_string(),
+            username: (i + 1 < CHATS.len()).then(|| name.trim_start_matches('@').to_string()),
+            topics: match i {
+                1 => TOPICS
+                    .iter()
+                    .map(|(id, title)| (*id, title.to_string()))
+                    .collect(),
+                _ => Vec::new(),
+            },
+            pins: Vec::new(),
+        })
+        .collect();
+    // Newest first, as Telegram lists them.
+    for (i, message) in messages(count).into_iter().enumerate().rev() {
+        let age = ((count - 1 - i) * 730 / count.max(1)) as u64;
+        let chat = &mut dialogs[i % CHATS.len()];
+        chat.pins.push(MockPin {
+            id: message.id,
+            sender: message.sender,
+            sender_id: message.sender_id,
+            text: message.text,
+            date: today - Days::new(age),
+            // Messages in General carry no topic.
+            topic: (!chat.topics.is_empty() && i / CHATS.len() % 2 == 1).then_some(REPAIRS_TOPIC),
+            views: message.views,
+            forwards: message.forwards,
+        });
+    }
+    dialogs
+}
+
+/// The chats a synthetic run fetches from [`dialogs`], and its `chat_title_patterns`: one
+/// with `max_age_days`, one with a topic, one configured twice, and one matched by title.
+pub fn chat_configs() -> (Vec<ChatConfig>, Vec<String>) {
+    let chat = |username: &str, topic, max_age_days| ChatConfig {
+        username: username.to_string(),
+        alias: None,
+        topic,
+        max_age_days,
+    };
+    let chats = vec![
+        chat("announcements", None, Some(365)),
+        chat(
+            "building_a",
+            Some(TopicRef::Title("Repairs".to_string())),
+            None,
+        ),
+        chat("@neighbours", None, None),
+        chat("neighbours", None, None),
+    ];
+    (chats, vec!["Tenants .*".to_string()])
+}
+
+/// The pins a synthetic run fetches from `dialogs`, configured as [`chat_configs`] has it.
+pub fn fetch(dialogs: &[MockChat]) -> Result<FetchedPins> {
+    let (chats, patterns) = chat_configs();
+    telegram::collect_mock(chats, &patterns, dialogs)
+}
+
+fn timed<T>(stage: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let value = f();
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+    println!("  {:<14} {:>10.2} ms", stage, elapsed);
+    value
+}
+
+/// Runs everything a run does, except uploading, over `count` generated pins fetched from
+/// the mock source, with the settings of `config`, and prints how long each stage took.
+/// Nothing is written.
+pub fn bench(config: &FileConfig, count: usize) -> Result<()> {
+    println!("Synthetic run over {} pins:", count);
+    let dialogs = timed("generate", || dialogs(count));
+    let fetched = timed("fetch", || fetch(&dialogs))?;
+    let mut messages = fetched.messages;
+    println!("  {:<14} {:>10} pins kept", "", messages.len());
+    let redactor = Redactor::new(&config.redact)?;
+    timed("redact", || redactor.apply(&mut messages));
+    timed("languages", || translate::detect_languages(&mut messages));
+    timed("stats", || Stats::from_messages(&messages));
+
+    let envelope = Envelope::new(
+        RunId::new(),
+        None,
+        fetched.chats,
+        &messages,
+        &config.export.fields,
+    );
+    let registry = ExporterRegistry::builtin(&config.export)?;
+    for name in registry.names() {
+        let exporter = registry.get(name)?;
+        let files = timed(name, || {
+            ExportFile::render_partitioned(
+                exporter,
+                &envelope,
+                config.export.partition,
+                config.export.max_size,
+            )
+        })?;
+        let bytes: usize = files.iter().map(|file| file.bytes.len()).sum();
+        println!("  {:<14} {:>10} bytes in {} files", "", bytes, files.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use sd_notify::NotifyState;
+use std::collections::HashMap;
+use std::os::fd::{FromRawFd as _, OwnedFd};
+use std::os::unix::net;
+use tokio::net::{TcpListener, UnixListener};
+
+use crate::shutdown::Shutdown;
+use crate::Result;
+
+/// Listening sockets passed by systemd socket activation, by their `FileDescriptorName=`.
+#[derive(Default)]
+pub struct ActivatedSockets {
+    fds: HashMap<String, OwnedFd>,
+}
+
+impl ActivatedSockets {
+    /// Takes over the sockets passed to this process, if any. Must be called only once.
+    pub fn take() -> Result<Self> {
+        let fds = sd_notify::listen_fds_with_names()?
+            // SAFETY: systemd hands these descriptors to this process, and nothing else
+            // in it takes ownership of them.
+            .map(|(fd, name)| (name, unsafe { OwnedFd::from_raw_fd(fd) }))
+            .collect();
+        Ok(ActivatedSockets { fds })
+    }
+
+    pub fn tcp(&mut self, name: &str) -> Result<Option<TcpListener>> {
+        let Some(fd) = self.fds.remove(name) else {
+            return Ok(None);
+        };
+        let listener = std::net::TcpListener::from(fd);
+        listener.set_nonblocking(true)?;
+        Ok(Some(TcpListener::from_std(listener)?))
+    }
+
+    pub fn unix(&mut self, name: &str) -> Result<Option<UnixListener>> {
+        let Some(fd) = self.fds.remove(name) else {
+            return Ok(None);
+        };
+        let listener = net::UnixListener::from(fd);
+        listener.set_nonblocking(true)?;
+        Ok(Some(UnixListener::from_std(listener)?))
+    }
+
+    /// Names of the sockets no server asked for.
+    pub fn unused(&self) -> impl Iterator<Item = &str> {
+        self.fds.keys().map(String::as_str)
+    }
+}
+
+/// Does nothing unless running as a systemd service with `Type=notify`.
+fn notify(state: NotifyState) {
+    if let Err(e) = sd_notify::notify(&[state]) {
+        log::warn!("Could not notify systemd: {}", e);
+    }
+}
+
+pub fn notify_ready() {
+    notify(NotifyState::Ready);
+}
+
+pub fn notify_stopping() {
+    notify(NotifyState::Stopping);
+}
+
+/// Pings the systemd watchdog at half its `WatchdogSec=` until a shutdown is requested.
+/// Pings stop when the runtime is stuck, so systemd restarts a hung instance.
+pub async fn watchdog(shutdown: &Shutdown) {
+    let Some(timeout) = sd_notify::watchdog_enabled() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(timeout / 2);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => notify(NotifyState::Watchdog),
+            _ = shutdown.wait() => break,
+        }
+    }
+}
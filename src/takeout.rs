@@ -0,0 +1,161 @@
+use grammers_client::types::ChatMap;
+use grammers_client::Client;
+use grammers_session::PackedChat;
+use grammers_tl_types as tl;
+use std::sync::Arc;
+
+use crate::seen::PinCount;
+use crate::Result;
+
+/// Messages requested per page, the most `messages.search` returns at once.
+const PAGE_SIZE: i32 = 100;
+
+/// A takeout session: Telegram's sanctioned way to bulk-export data, with friendlier rate
+/// limits than regular requests.
+pub struct Takeout {
+    id: i64,
+}
+
+/// Every pinned message of a chat, with the users and chats they refer to.
+pub struct PinnedMessages {
+    pub total: usize,
+    pub messages: Vec<tl::types::Message>,
+    pub chats: Arc<ChatMap>,
+}
+
+impl Takeout {
+    /// Fails with `TAKEOUT_INIT_DELAY_X` until the user allowed the export in the service
+    /// notification Telegram sends to their other sessions.
+    pub async fn start(client: &Client) -> Result<Self> {
+        let request = tl::functions::account::InitTakeoutSession {
+            contacts: false,
+            message_users: true,
+            message_chats: true,
+            message_megagroups: true,
+            message_channels: true,
+            files: false,
+            file_max_size: None,
+        };
+        let tl::enums::account::Takeout::Takeout(takeout) = client.invoke(&request).await?;
+        Ok(Takeout { id: takeout.id })
+    }
+
+    async fn invoke<R: tl::RemoteCall>(&self, client: &Client, query: R) -> Result<R::Return> {
+        let request = tl::functions::InvokeWithTakeout {
+            takeout_id: self.id,
+            query,
+        };
+        Ok(client.invoke(&request).await?)
+    }
+
+    /// Ends the session; `success` tells Telegram whether the export completed.
+    pub async fn finish(self, client: &Client, success: bool) -> Result<()> {
+        let request = tl::functions::account::FinishTakeoutSession { success };
+        self.invoke(client, request).await?;
+        Ok(())
+    }
+}
+
+fn search(
+    chat: PackedChat,
+    min_date: i32,
+    offset_id: i32,
+    limit: i32,
+) -> tl::functions::messages::Search {
+    tl::functions::messages::Search {
+        peer: chat.to_input_peer(),
+        q: String::new(),
+        from_id: None,
+        top_msg_id: None,
+        filter: tl::enums::MessagesFilter::InputMessagesFilterPinned,
+        min_date,
+        max_date: 0,
+        offset_id,
+        add_offset: 0,
+        limit,
+        max_id: 0,
+        min_id: 0,
+        hash: 0,
+    }
+}
+
+async fn invoke(
+    client: &Client,
+    takeout: Option<&Takeout>,
+    request: tl::functions::messages::Search,
+) -> Result<tl::enums::messages::Messages> {
+    match takeout {
+        Some(takeout) => takeout.invoke(client, request).await,
+        None => Ok(client.invoke(&request).await?),
+    }
+}
+
+/// How many pins `chat` has since `min_date` and which is the newest, in one request for a
+/// single message.
+pub async fn pin_count(
+    client: &Client,
+    takeout: Option<&Takeout>,
+    chat: PackedChat,
+    min_date: i32,
+) -> Result<PinCount> {
+    let (messages, count) = match invoke(client, takeout, search(chat, min_date, 0, 1)).await? {
+        tl::enums::messages::Messages::Messages(m) => (m.messages, None),
+        tl::enums::messages::Messages::Slice(m) => (m.messages, Some(m.count)),
+        tl::enums::messages::Messages::ChannelMessages(m) => (m.messages, Some(m.count)),
+        tl::enums::messages::Messages::NotModified(m) => (Vec::new(), Some(m.count)),
+    };
+    let top_id = messages.first().map_or(0, |message| match message {
+        tl::enums::Message::Message(message) => message.id,
+        tl::enums::Message::Service(message) => message.id,
+        tl::enums::Message::Empty(message) => message.id,
+    });
+    Ok(PinCount {
+        count: count.map_or(messages.len(), |count| count as usize),
+        top_id,
+    })
+}
+
+/// Every pin of `chat`, requested through `takeout` when given. Pins of messages sent
+/// before `min_date` (a Unix timestamp, 0 for none) are left out.
+pub async fn pinned_messages(
+    client: &Client,
+    takeout: Option<&Takeout>,
+    chat: PackedChat,
+    min_date: i32,
+) -> Result<PinnedMessages> {
+    let mut total = None;
+    let mut messages = Vec::new();
+    let (mut users, mut chats) = (Vec::new(), Vec::new());
+    loop {
+        let offset_id = messages.last().map_or(0, |m: &tl::types::Message| m.id);
+        let request = search(chat, min_date, offset_id, PAGE_SIZE);
+        let response = invoke(client, takeout, request).await?;
+        let (page, page_users, page_chats, count) = match response {
+            tl::enums::messages::Messages::Messages(m) => (m.messages, m.users, m.chats, None),
+            tl::enums::messages::Messages::Slice(m) => {
+                (m.messages, m.users, m.chats, Some(m.count))
+            }
+            tl::enums::messages::Messages::ChannelMessages(m) => {
+                (m.messages, m.users, m.chats, Some(m.count))
+            }
+            tl::enums::messages::Messages::NotModified(_) => break,
+        };
+        let page_len = page.len();
+        total = total.or(count);
+        users.extend(page_users);
+        chats.extend(page_chats);
+        messages.extend(page.into_iter().filter_map(|message| match message {
+            tl::enums::Message::Message(message) => Some(message),
+            _ => None,
+        }));
+        if page_len < PAGE_SIZE as usize {
+            break;
+        }
+    }
+
+    Ok(PinnedMessages {
+        total: total.map_or(messages.len(), |count| count as usize),
+        messages,
+        chats: ChatMap::new(users, chats),
+    })
+}
@@ -0,0 +1,1349 @@
+use chrono::{DateTime, Days, FixedOffset, SecondsFormat, Utc};
+use grammers_client::types::{Chat, ChatMap, Media, Message as TgMessage};
+use grammers_client::{Client, Config, InitParams, SignInError, Update};
+use grammers_mtsender::InvocationError;
+use grammers_session::{PackedChat, PackedType, Session};
+use grammers_tl_types as tl;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead as _, Write as _};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::cache::ChatCache;
+use crate::config::{ChatConfig, ConfigPaths, FileConfig, TopicRef};
+use crate::media;
+use crate::raw;
+use crate::seen::{PinCount, SeenPins};
+use crate::shutdown::Shutdown;
+use crate::stickers::StickerResolver;
+use crate::takeout::{self, Takeout};
+use crate::topics;
+use crate::{ChatInfo, ChatKind, Message, QuoteInfo, Result};
+
+#[derive(Debug)]
+pub struct AuthRequiredError;
+
+impl fmt::Display for AuthRequiredError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Session is not authorized. Run `telegram-pin-fetcher login` interactively \
+             to sign in."
+        )
+    }
+}
+
+impl std::error::Error for AuthRequiredError {}
+
+/// No chat goes by a configured name: no such username, contact or shared phone number.
+#[derive(Debug)]
+pub struct ChatNotFound(pub String);
+
+impl fmt::Display for ChatNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Chat {} could not be found", self.0)
+    }
+}
+
+impl std::error::Error for ChatNotFound {}
+
+fn prompt(message: &str) -> Result<String> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    stdout.write_all(message.as_bytes())?;
+    stdout.flush()?;
+
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+
+    let mut line = String::new();
+    stdin.read_line(&mut line)?;
+    Ok(line)
+}
+
+/// The digits of `+<international number>` chat names, which refer to a contact.
+fn phone_number(chat_name: &str) -> Option<String> {
+    let number = chat_name.strip_prefix('+')?;
+    let digits: String = number
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '(' | ')'))
+        .collect();
+    (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())).then_some(digits)
+}
+
+/// The name in `contact:<first and last name>` chat names, for people known only by the
+/// name they are saved under in the contact list.
+fn contact_name(chat_name: &str) -> Option<&str> {
+    chat_name.strip_prefix("contact:").map(str::trim)
+}
+
+fn full_name(user: &tl::types::User) -> String {
+    let names = [&user.first_name, &user.last_name];
+    let names: Vec<&str> = names.into_iter().flatten().map(String::as_str).collect();
+    names.join(" ")
+}
+
+/// The first contact of the account for which `matches` holds.
+async fn find_contact(
+    client: &Client,
+    matches: impl Fn(&tl::types::User) -> bool,
+) -> Result<Option<PackedChat>> {
+    let request = tl::functions::contacts::GetContacts { hash: 0 };
+    let tl::enums::contacts::Contacts::Contacts(contacts) = client.invoke(&request).await? else {
+        return Ok(None);
+    };
+    let found: Vec<i64> = contacts
+        .users
+        .iter()
+        .filter_map(|user| match user {
+            tl::enums::User::User(user) if matches(user) => Some(user.id),
+            _ => None,
+        })
+        .collect();
+    if found.len() > 1 {
+        log::warn!("{} contacts match, using the first one", found.len());
+    }
+    let chats = ChatMap::new(contacts.users, Vec::new());
+    Ok(found.first().and_then(|&user_id| {
+        let peer = tl::enums::Peer::User(tl::types::PeerUser { user_id });
+        chats.get(&peer).map(|chat| chat.pack())
+    }))
+}
+
+/// The user with this phone number, if they share it with everyone.
+async fn resolve_phone(client: &Client, phone: String) -> Result<Option<PackedChat>> {
+    let request = tl::functions::contacts::ResolvePhone { phone };
+    let resolved = match client.invoke(&request).await {
+        Ok(tl::enums::contacts::ResolvedPeer::Peer(resolved)) => resolved,
+        Err(e) if e.is("PHONE_NOT_OCCUPIED") => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let chats = ChatMap::new(resolved.users, resolved.chats);
+    Ok(chats.get(&resolved.peer).map(|chat| chat.pack()))
+}
+
+async fn resolve_chat(
+    client: &Client,
+    cache: &mut ChatCache,
+    chat_name: &str,
+) -> Result<PackedChat> {
+    if let Some(chat) = cache.get(chat_name) {
+        return Ok(chat);
+    }
+
+    let maybe_chat = if let Some(name) = contact_name(chat_name) {
+        let name = name.to_lowercase();
+        find_contact(client, |user| full_name(user).to_lowercase() == name).await?
+    } else if let Some(phone) = phone_number(chat_name) {
+        let contact = find_contact(client, |user| user.phone.as_ref() == Some(&phone)).await?;
+        match contact {
+            Some(contact) => Some(contact),
+            None => resolve_phone(client, phone).await?,
+        }
+    } else {
+        client
+            .resolve_username(chat_name)
+            .await?
+            .map(|chat| chat.pack())
+    };
+    let Some(chat) = maybe_chat else {
+        return Err(Box::new(ChatNotFound(chat_name.to_string())));
+    };
+    cache.insert(chat_name, chat);
+
+    Ok(chat)
+}
+
+/// Who sent a pin, as exported.
+struct Sender {
+    name: String,
+    id: i64,
+    /// To download their profile photo; mock pins have none.
+    chat: Option<Chat>,
+}
+
+impl Sender {
+    fn of(chat: Chat) -> Self {
+        Sender {
+            name: display_name(&chat),
+            id: chat.id(),
+            chat: Some(chat),
+        }
+    }
+}
+
+/// The parts of a pinned message the export needs, however it was fetched.
+struct Pinned {
+    id: i32,
+    sender: Option<Sender>,
+    /// Sent by the account itself; private chats and channel posts name no sender then.
+    out: bool,
+    text: String,
+    entities: Option<Vec<tl::enums::MessageEntity>>,
+    date: String,
+    datetime: String,
+    /// Channel posts only.
+    views: Option<i32>,
+    forwards: Option<i32>,
+    media: Option<Media>,
+    reply_header: Option<tl::enums::MessageReplyHeader>,
+    /// The full TL message as JSON, with `export.include_raw`.
+    raw: Option<serde_json::Value>,
+}
+
+impl Pinned {
+    fn from_message(msg: &TgMessage) -> Self {
+        Pinned {
+            id: msg.id(),
+            sender: msg.sender().map(Sender::of),
+            out: msg.outgoing(),
+            text: msg.text().to_string(),
+            entities: msg.fmt_entities().cloned(),
+            date: msg.date().date_naive().to_string(),
+            datetime: msg.date().to_rfc3339_opts(SecondsFormat::Secs, true),
+            views: msg.view_count(),
+            forwards: msg.forward_count(),
+            media: msg.media(),
+            reply_header: msg.reply_header(),
+            raw: None,
+        }
+    }
+
+    /// Same as [`Pinned::from_message`] for a message from a raw response, which is kept
+    /// as JSON too when `include_raw` is set.
+    fn from_raw(
+        client: &Client,
+        msg: tl::types::Message,
+        chats: &ChatMap,
+        include_raw: bool,
+    ) -> Self {
+        let raw = include_raw.then(|| raw::to_json(&msg));
+        // Incoming private messages carry no `from_id`; the sender is the chat itself.
+        let private = !msg.out && matches!(msg.peer_id, tl::enums::Peer::User(_));
+        let sender = msg.from_id.as_ref().or(private.then_some(&msg.peer_id));
+        let date = chrono::DateTime::from_timestamp(msg.date.into(), 0).unwrap_or_default();
+        Pinned {
+            id: msg.id,
+            sender: sender
+                .and_then(|peer| chats.get(peer))
+                .cloned()
+                .map(Sender::of),
+            out: msg.out,
+            text: msg.message,
+            entities: msg.entities,
+            date: date.date_naive().to_string(),
+            datetime: date.to_rfc3339_opts(SecondsFormat::Secs, true),
+            views: msg.views,
+            forwards: msg.forwards,
+            media: msg
+                .media
+                .and_then(|media| Media::from_raw(media, client.clone())),
+            reply_header: msg.reply_to,
+            raw,
+        }
+    }
+}
+
+/// The bare ID of the chat `peer` refers to, as [`PackedChat`] has it.
+fn peer_id(peer: &tl::enums::Peer) -> i64 {
+    match peer {
+        tl::enums::Peer::User(user) => user.user_id,
+        tl::enums::Peer::Chat(chat) => chat.chat_id,
+        tl::enums::Peer::Channel(channel) => channel.channel_id,
+    }
+}
+
+/// The fragment quoted by a reply; plain replies, which quote nothing, have none.
+fn quote(reply_header: Option<&tl::enums::MessageReplyHeader>) -> Option<QuoteInfo> {
+    let Some(tl::enums::MessageReplyHeader::Header(header)) = reply_header else {
+        return None;
+    };
+    let text = header.quote_text.clone()?;
+    let chat_id = header.reply_to_peer_id.as_ref().map(peer_id);
+    Some(QuoteInfo {
+        text,
+        message_id: header.reply_to_msg_id,
+        chat_id,
+    })
+}
+
+/// Turns the pins of one resolved chat into messages, numbered from the top of its pinned
+/// list down, as Telegram lists them.
+struct ChatPins<'a> {
+    name: &'a str,
+    config: &'a ChatConfig,
+    chat: PackedChat,
+    info: &'a ChatInfo,
+    me: &'a Sender,
+    topic_id: Option<i32>,
+    pin_order: u32,
+}
+
+impl ChatPins<'_> {
+    /// Who sent `pin`; channel posts have no sender, they are signed by the channel itself.
+    fn sender<'p>(&'p self, pin: &'p Pinned) -> Option<&'p Sender> {
+        match (&pin.sender, pin.out) {
+            (Some(sender), _) => Some(sender),
+            // Private chats and channel posts name no sender for the account's own pins.
+            (None, true) => Some(self.me),
+            (None, false) => None,
+        }
+    }
+
+    /// The message `pin` is exported as, or `None` if it is outside the configured topic or
+    /// its media is not exported. Stickers, media, profile photos and custom emoji take
+    /// requests of their own and are left to the caller.
+    fn message(&mut self, pin: &mut Pinned) -> Option<Message> {
+        if let Some(topic_id) = self.topic_id {
+            if !topics::in_topic(pin.reply_header.as_ref(), topic_id) {
+                return None;
+            }
+        }
+        // Counted all the same: the order is the place in the pinned list.
+        self.pin_order += 1;
+        let exported = |media: &Media| {
+            matches!(
+                media,
+                Media::Sticker(_) | Media::Photo(_) | Media::Document(_)
+            )
+        };
+        if pin.media.as_ref().is_some_and(|media| !exported(media)) {
+            return None;
+        }
+        let (sender, sender_id) = match self.sender(pin) {
+            Some(sender) => (sender.name.clone(), sender.id),
+            None => {
+                let name = self.info.username.as_ref().or(self.info.title.as_ref());
+                (
+                    name.map_or(self.name, String::as_str).to_string(),
+                    self.chat.id,
+                )
+            }
+        };
+        Some(Message {
+            id: pin.id,
+            chat: self.name.to_string(),
+            chat_alias: self.config.alias.clone(),
+            chat_id: Some(self.chat.id),
+            chat_kind: Some(chat_kind(self.chat, self.info)),
+            sender,
+            sender_photo: None,
+            pin_order: Some(self.pin_order),
+            sender_id,
+            text: pin.text.clone(),
+            date: pin.date.clone(),
+            datetime: pin.datetime.clone(),
+            views: pin.views,
+            forwards: pin.forwards,
+            sticker: None,
+            media: None,
+            quote: quote(pin.reply_header.as_ref()),
+            links: Vec::new(),
+            language: None,
+            text_translated: None,
+            raw: pin.raw.take(),
+            extra: Default::default(),
+        })
+    }
+}
+
+/// What to fetch of a chat's pins.
+#[derive(Clone, Copy)]
+struct PinQuery {
+    /// Only pins of messages sent since then.
+    min_date: Option<DateTime<FixedOffset>>,
+    include_raw: bool,
+    /// The chat's pin count when its pins were last uploaded, to skip a chat still at it.
+    unchanged_from: Option<PinCount>,
+}
+
+/// Fetches every pin of `chat` the query asks for, through the takeout session when there
+/// is one. Returns the pin count Telegram reports before listing them, and no pins when it
+/// is still the query's `unchanged_from`.
+async fn fetch_pins(
+    client: &Client,
+    takeout: Option<&Takeout>,
+    chat: PackedChat,
+    query: PinQuery,
+) -> Result<(PinCount, Option<Vec<Pinned>>)> {
+    let PinQuery {
+        min_date,
+        include_raw,
+        unchanged_from,
+    } = query;
+    let timestamp = min_date.map_or(0, |date| date.timestamp() as i32);
+    let count = takeout::pin_count(client, takeout, chat, timestamp).await?;
+    if unchanged_from == Some(count) {
+        return Ok((count, None));
+    }
+
+    // Only raw responses give access to the whole message.
+    if takeout.is_some() || include_raw {
+        let pinned = takeout::pinned_messages(client, takeout, chat, timestamp).await?;
+        let chats = &pinned.chats;
+        let pins = pinned.messages.into_iter();
+        let pins = pins
+            .map(|msg| Pinned::from_raw(client, msg, chats, include_raw))
+            .collect();
+        return Ok((count, Some(pins)));
+    }
+
+    let mut pinned_messages = client
+        .search_messages(chat)
+        .filter(tl::enums::MessagesFilter::InputMessagesFilterPinned);
+    if let Some(min_date) = &min_date {
+        pinned_messages = pinned_messages.min_date(min_date);
+    }
+    let mut pins = Vec::new();
+    while let Some(msg) = pinned_messages.next().await? {
+        pins.push(Pinned::from_message(&msg));
+    }
+    Ok((count, Some(pins)))
+}
+
+/// Starts a takeout session, or explains why fetching goes on without one.
+async fn start_takeout(client: &Client) -> Option<Takeout> {
+    match Takeout::start(client).await {
+        Ok(takeout) => Some(takeout),
+        Err(e) => {
+            let delayed = e
+                .downcast_ref::<InvocationError>()
+                .is_some_and(|e| e.is("TAKEOUT_INIT_DELAY"));
+            if delayed {
+                log::warn!(
+                    "Telegram wants the data export confirmed first: allow it in the \
+                     notification sent to your other sessions. Fetching without takeout."
+                );
+            } else {
+                log::warn!(
+                    "Could not start a takeout session ({}), fetching without it",
+                    e
+                );
+            }
+            None
+        }
+    }
+}
+
+/// Errors meaning the account can't read a chat anymore: it was removed or banned, or the
+/// chat is not available in its country.
+const INACCESSIBLE: &[&str] = &[
+    "CHANNEL_PRIVATE",
+    "CHAT_FORBIDDEN",
+    "CHANNEL_PUBLIC_GROUP_NA",
+];
+
+/// Why a chat that failed can be skipped rather than fail the run, if it can.
+fn inaccessible(e: &(dyn std::error::Error + 'static)) -> Option<&'static str> {
+    // A typo in one name should not cost the pins of every other chat.
+    if e.is::<ChatNotFound>() {
+        return Some("not found");
+    }
+    let e = e.downcast_ref::<InvocationError>()?;
+    INACCESSIBLE.iter().copied().find(|name| e.is(name))
+}
+
+/// A configured chat left out of the run because the account can't read or find it.
+pub struct SkippedChat {
+    pub name: String,
+    /// The Telegram error, e.g. `CHANNEL_PRIVATE`, or `not found`.
+    pub reason: String,
+}
+
+/// A chat whose pins changed while they were fetched: Telegram counts them before they
+/// are listed, so pins deleted or unpinned in between are counted but never listed, and
+/// new ones the other way around. The pins listed are exported as usual.
+pub struct CountMismatch {
+    pub name: String,
+    /// The count Telegram gave before listing the pins.
+    pub reported: usize,
+    pub fetched: usize,
+}
+
+impl fmt::Display for CountMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (Telegram counted {} pins, {} were fetched)",
+            self.name, self.reported, self.fetched
+        )
+    }
+}
+
+/// The pins of a run and the chats they were fetched from.
+pub struct FetchedPins {
+    pub messages: Vec<Message>,
+    pub chats: Vec<ChatInfo>,
+    pub skipped: Vec<SkippedChat>,
+    pub mismatches: Vec<CountMismatch>,
+    /// Chats left out with `skip_unchanged`, as their pin count had not changed.
+    pub unchanged: Vec<String>,
+    /// The pin count of every chat reached, by configured name, to remember once its pins
+    /// are uploaded.
+    pub counts: Vec<(String, PinCount)>,
+    /// IDs of every chat reached, unchanged ones too, whose pins `watch` listens for.
+    pub chat_ids: HashSet<i64>,
+}
+
+/// Username of `chat`, or its name for users and private groups without one.
+fn display_name(chat: &Chat) -> String {
+    chat.username().unwrap_or(chat.name()).to_string()
+}
+
+/// How pins of `chat` can be linked to, from its details in `info`.
+fn chat_kind(chat: PackedChat, info: &ChatInfo) -> ChatKind {
+    match chat.ty {
+        PackedType::User | PackedType::Bot => ChatKind::User,
+        PackedType::Chat => ChatKind::Group,
+        PackedType::Megagroup | PackedType::Broadcast | PackedType::Gigagroup => {
+            match info.username {
+                Some(_) => ChatKind::Public,
+                None => ChatKind::Private,
+            }
+        }
+    }
+}
+
+/// Title, username, member count and description of `chat`.
+async fn chat_info(client: &Client, name: &str, chat: PackedChat) -> Result<ChatInfo> {
+    let unpacked = client.unpack_chat(chat).await?;
+    let mut info = ChatInfo {
+        name: name.to_string(),
+        id: chat.id,
+        title: Some(unpacked.name().to_string()).filter(|title| !title.is_empty()),
+        username: unpacked.username().map(str::to_string),
+        ..Default::default()
+    };
+    if let Some(channel) = chat.try_to_input_channel() {
+        let request = tl::functions::channels::GetFullChannel { channel };
+        let tl::enums::messages::ChatFull::Full(full) = client.invoke(&request).await?;
+        if let tl::enums::ChatFull::ChannelFull(full) = full.full_chat {
+            info.members = full.participants_count;
+            info.description = Some(full.about);
+        }
+    } else if let Some(chat_id) = chat.try_to_chat_id() {
+        let request = tl::functions::messages::GetFullChat { chat_id };
+        let tl::enums::messages::ChatFull::Full(full) = client.invoke(&request).await?;
+        if let tl::enums::ChatFull::Full(full) = full.full_chat {
+            if let tl::enums::ChatParticipants::Participants(participants) = full.participants {
+                info.members = Some(participants.participants.len() as i32);
+            }
+            info.description = Some(full.about);
+        }
+    } else if let Some(id) = chat.try_to_input_user() {
+        let request = tl::functions::users::GetFullUser { id };
+        let tl::enums::users::UserFull::Full(full) = client.invoke(&request).await?;
+        let tl::enums::UserFull::Full(full) = full.full_user;
+        info.description = full.about;
+    }
+    info.description = info.description.filter(|about| !about.is_empty());
+    Ok(info)
+}
+
+/// Fetches the pins of every configured chat. When Telegram asks to continue on another
+/// data center, `client` is replaced by one connected there and the fetch starts over.
+pub async fn get_pinned_messages(
+    client: &mut Client,
+    creds_toml: &FileConfig,
+    cache: &mut ChatCache,
+    paths: &ConfigPaths,
+    shutdown: &Shutdown,
+    deadline: Option<Instant>,
+) -> Result<FetchedPins> {
+    match fetch_pinned_messages(client, creds_toml, cache, paths, shutdown, deadline).await {
+        Err(e) => match migrate_dc(&*e) {
+            Some(dc_id) => {
+                *client = migrate(client, creds_toml, dc_id).await?;
+                fetch_pinned_messages(client, creds_toml, cache, paths, shutdown, deadline).await
+            }
+            None => Err(e),
+        },
+        fetched => fetched,
+    }
+}
+
+async fn fetch_pinned_messages(
+    client: &Client,
+    creds_toml: &FileConfig,
+    cache: &mut ChatCache,
+    paths: &ConfigPaths,
+    shutdown: &Shutdown,
+    deadline: Option<Instant>,
+) -> Result<FetchedPins> {
+    let takeout = match creds_toml.config.takeout {
+        true => start_takeout(client).await,
+        false => None,
+    };
+    let result = collect_pinned_messages(
+        client,
+        creds_toml,
+        cache,
+        paths,
+        shutdown,
+        deadline,
+        takeout.as_ref(),
+    )
+    .await;
+    if let Some(takeout) = takeout {
+        if let Err(e) = takeout.finish(client, result.is_ok()).await {
+            log::warn!("Could not finish the takeout session: {}", e);
+        }
+    }
+    result
+}
+
+/// Dialogs whose title matches one of `patterns` as a whole, named by their username or,
+/// for private groups without one, their title.
+async fn dialogs_matching(
+    client: &Client,
+    patterns: &[String],
+) -> Result<Vec<(String, PackedChat)>> {
+    if patterns.is_empty() {
+        return Ok(Vec::new());
+    }
+    let patterns = title_patterns(patterns)?;
+
+    let mut matching = Vec::new();
+    let mut dialogs = client.iter_dialogs();
+    while let Some(dialog) = dialogs.next().await? {
+        let chat = dialog.chat();
+        let title = chat.name();
+        if patterns.iter().any(|pattern| pattern.is_match(title)) {
+            let name = chat.username().unwrap_or(title).to_string();
+            log::info!("Chat {} matches chat_title_patterns", name);
+            matching.push((name, chat.pack()));
+        }
+    }
+    Ok(matching)
+}
+
+/// `chat_title_patterns`, each matching a whole title.
+fn title_patterns(patterns: &[String]) -> Result<Vec<Regex>> {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| Regex::new(&format!("^(?:{})$", pattern)))
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(patterns)
+}
+
+/// The configured chats, then the dialogs matching `chat_title_patterns` that are not
+/// configured already, which need no resolving.
+fn with_dialogs(
+    chats: Vec<ChatConfig>,
+    dialogs: Vec<(String, PackedChat)>,
+) -> Vec<(ChatConfig, Option<PackedChat>)> {
+    let mut chats: Vec<_> = chats
+        .into_iter()
+        .map(|chat_config| (chat_config, None))
+        .collect();
+    for (name, chat) in dialogs {
+        if !chats
+            .iter()
+            .any(|(chat_config, _)| chat_config.username == name)
+        {
+            let chat_config = ChatConfig {
+                username: name,
+                alias: None,
+                topic: None,
+                max_age_days: None,
+            };
+            chats.push((chat_config, Some(chat)));
+        }
+    }
+    chats
+}
+
+/// The oldest a pinned message of the chat may be, with `max_age_days`.
+fn min_date(chat_config: &ChatConfig) -> Option<DateTime<FixedOffset>> {
+    chat_config
+        .max_age_days
+        .and_then(|days| Utc::now().checked_sub_days(Days::new(days.into())))
+        .map(|date| date.fixed_offset())
+}
+
+/// Resolves `chat_name`, unless it came from the dialogs already, and fetches its pins.
+async fn fetch_chat(
+    client: &Client,
+    cache: &mut ChatCache,
+    takeout: Option<&Takeout>,
+    chat_name: &str,
+    dialog: Option<PackedChat>,
+    query: PinQuery,
+) -> Result<(PackedChat, PinCount, Option<Vec<Pinned>>)> {
+    let was_cached = dialog.is_none() && cache.get(chat_name).is_some();
+    let chat = match dialog {
+        Some(chat) => chat,
+        None => resolve_chat(client, cache, chat_name).await?,
+    };
+    match fetch_pins(client, takeout, chat, query).await {
+        Ok((count, pins)) => Ok((chat, count, pins)),
+        Err(e) if was_cached => {
+            // The cached access hash may have gone stale; resolve again and retry once.
+            log::warn!("Cached chat {} failed ({}), resolving again", chat_name, e);
+            cache.invalidate(chat_name);
+            let chat = resolve_chat(client, cache, chat_name).await?;
+            let (count, pins) = fetch_pins(client, takeout, chat, query).await?;
+            Ok((chat, count, pins))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Tells configured chats apart once resolved: by chat, and topics as chats of their own.
+fn fetch_key(chat: PackedChat, chat_config: &ChatConfig) -> (i64, String) {
+    (chat.id, format!("{:?}", chat_config.topic))
+}
+
+fn warn_duplicate(chat_name: &str, first: &str) {
+    log::warn!(
+        "{} is the same chat as {}, fetching it only once",
+        chat_name,
+        first
+    );
+}
+
+async fn collect_pinned_messages(
+    client: &Client,
+    creds_toml: &FileConfig,
+    cache: &mut ChatCache,
+    paths: &ConfigPaths,
+    shutdown: &Shutdown,
+    deadline: Option<Instant>,
+    takeout: Option<&Takeout>,
+) -> Result<FetchedPins> {
+    let mut messages = Vec::<Message>::new();
+    let mut infos = Vec::new();
+    let mut skipped = Vec::new();
+    let mut mismatches = Vec::new();
+    let mut stickers = StickerResolver::default();
+    // Per sender ID, so each is looked up once however many pins they have.
+    let mut profile_photos: HashMap<i64, Option<String>> = HashMap::new();
+    let download_dir = creds_toml.media.dir.as_deref().unwrap_or(&paths.media_dir);
+    // Only incremental runs can leave a chat out: its pins up to the last run are uploaded.
+    let seen = match creds_toml.config.incremental && creds_toml.config.skip_unchanged {
+        true => Some(SeenPins::load(&paths.seen_file)?),
+        false => None,
+    };
+    let mut counts = Vec::new();
+    let mut unchanged = Vec::new();
+    let sticker_dir = creds_toml.media.download_stickers.then_some(download_dir);
+    let max_bandwidth = creds_toml.config.max_bandwidth;
+
+    let patterns = &creds_toml.config.chat_title_patterns;
+    let dialogs = dialogs_matching(client, patterns).await?;
+    let chats = with_dialogs(creds_toml.config.chats(), dialogs);
+
+    let me = Sender::of(Chat::User(client.get_me().await?));
+    let mut pending = Vec::new();
+    // The same chat can be configured twice, e.g. by username and by phone number; it is
+    // fetched once, under the first name.
+    let mut fetched_chats: HashMap<(i64, String), String> = HashMap::new();
+    for (chat_config, dialog) in chats {
+        if shutdown.is_requested() {
+            break;
+        }
+        let chat_name = chat_config.username.as_str();
+        let label = chat_config.alias.as_deref().unwrap_or(chat_name);
+        // The chats fetched so far are still exported; the others wait for the next run.
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            println!("Skipping chat {}: max_run_duration exceeded", label);
+            skipped.push(SkippedChat {
+                name: label.to_string(),
+                reason: "max_run_duration exceeded".to_string(),
+            });
+            continue;
+        }
+        let query = PinQuery {
+            min_date: min_date(&chat_config),
+            include_raw: creds_toml.export.include_raw,
+            unchanged_from: seen.as_ref().and_then(|seen| seen.count(chat_name)),
+        };
+        let known = dialog.or_else(|| cache.get(chat_name));
+        let first = known.and_then(|chat| fetched_chats.get(&fetch_key(chat, &chat_config)));
+        if let Some(first) = first {
+            warn_duplicate(chat_name, first);
+            continue;
+        }
+        let fetched = fetch_chat(client, cache, takeout, chat_name, dialog, query).await;
+        let (chat, count, pins) = match fetched {
+            Ok(fetched) => fetched,
+            Err(e) => match inaccessible(&*e) {
+                Some(reason) => {
+                    println!("Skipping chat {}: {}", label, reason);
+                    skipped.push(SkippedChat {
+                        name: label.to_string(),
+                        reason: reason.to_string(),
+                    });
+                    continue;
+                }
+                None => return Err(e),
+            },
+        };
+
+        // Names not in the cache yet are only known once resolved.
+        if let Some(first) = fetched_chats.get(&fetch_key(chat, &chat_config)) {
+            warn_duplicate(chat_name, first);
+            continue;
+        }
+        fetched_chats.insert(fetch_key(chat, &chat_config), chat_name.to_string());
+        counts.push((chat_name.to_string(), count));
+        let Some(pins) = pins else {
+            println!("Chat {} is unchanged since the last run, skipped.", label);
+            unchanged.push(label.to_string());
+            continue;
+        };
+        println!("Chat {} has {} total pinned messages.", label, pins.len());
+        if count.count != pins.len() {
+            let mismatch = CountMismatch {
+                name: label.to_string(),
+                reported: count.count,
+                fetched: pins.len(),
+            };
+            log::warn!("Pins changed while fetching chat {}", mismatch);
+            mismatches.push(mismatch);
+        }
+
+        let mut info = match chat_info(client, chat_name, chat).await {
+            Ok(info) => info,
+            Err(e) => {
+                log::warn!("Could not fetch the details of chat {}: {}", chat_name, e);
+                ChatInfo {
+                    name: chat_name.to_string(),
+                    id: chat.id,
+                    ..Default::default()
+                }
+            }
+        };
+        info.alias = chat_config.alias.clone();
+        let topic_id = match &chat_config.topic {
+            Some(topic) => Some(topics::resolve_topic(client, chat, topic).await?),
+            None => None,
+        };
+
+        let mut chat_pins = ChatPins {
+            name: chat_name,
+            config: &chat_config,
+            chat,
+            info: &info,
+            me: &me,
+            topic_id,
+            pin_order: 0,
+        };
+        for mut pin in pins {
+            let Some(mut message) = chat_pins.message(&mut pin) else {
+                continue;
+            };
+            match &pin.media {
+                Some(media @ Media::Sticker(_)) => {
+                    let sticker = stickers
+                        .sticker_info(client, media, sticker_dir, max_bandwidth)
+                        .await?;
+                    message.sticker = Some(sticker);
+                }
+                Some(media @ (Media::Photo(_) | Media::Document(_))) => {
+                    // Downloaded together once all chats are fetched.
+                    pending.push((messages.len(), media.clone()));
+                }
+                _ => {}
+            }
+            // Pseudonyms would be pointless next to the sender's face.
+            let profile_photos_wanted =
+                creds_toml.media.profile_photos && !creds_toml.config.anonymize;
+            let sender_chat = chat_pins
+                .sender(&pin)
+                .and_then(|sender| sender.chat.as_ref());
+            message.sender_photo = match sender_chat.filter(|_| profile_photos_wanted) {
+                Some(sender_chat) => match profile_photos.get(&message.sender_id) {
+                    Some(photo) => photo.clone(),
+                    None => {
+                        let photo = media::profile_photo(client, sender_chat, download_dir)
+                            .await
+                            .inspect_err(|e| {
+                                log::warn!("No profile photo of {}: {}", message.sender, e)
+                            })
+                            .ok()
+                            .flatten();
+                        profile_photos.insert(message.sender_id, photo.clone());
+                        photo
+                    }
+                },
+                None => None,
+            };
+            message.text = stickers
+                .resolve_custom_emoji(client, &pin.text, pin.entities.as_ref())
+                .await?;
+            messages.push(message);
+        }
+        infos.push(info);
+    }
+
+    let (indices, media): (Vec<_>, Vec<_>) = pending.into_iter().unzip();
+    let config = &creds_toml.media;
+    let described = media::media_infos(client, &media, config, download_dir, max_bandwidth).await?;
+    for (index, info) in indices.into_iter().zip(described) {
+        messages[index].media = info;
+    }
+
+    Ok(FetchedPins {
+        messages,
+        chats: infos,
+        skipped,
+        mismatches,
+        unchanged,
+        counts,
+        chat_ids: fetched_chats.keys().map(|(id, _)| *id).collect(),
+    })
+}
+
+/// A chat of the mock fetch source, with the pins Telegram would list for it.
+pub struct MockChat {
+    pub id: i64,
+    pub title: String,
+    pub username: Option<String>,
+    /// Forum topics, by ID and title.
+    pub topics: Vec<(i32, String)>,
+    /// Newest first.
+    pub pins: Vec<MockPin>,
+}
+
+/// A pin of a [`MockChat`].
+pub struct MockPin {
+    pub id: i32,
+    pub sender: String,
+    pub sender_id: i64,
+    pub text: String,
+    pub date: DateTime<Utc>,
+    /// The forum topic it was posted in, by ID.
+    pub topic: Option<i32>,
+    pub views: Option<i32>,
+    pub forwards: Option<i32>,
+}
+
+impl MockChat {
+    fn pack(&self) -> PackedChat {
+        PackedChat {
+            ty: PackedType::Megagroup,
+            id: self.id,
+            access_hash: Some(0),
+        }
+    }
+
+    /// Whether the chat is `name`, as configured: by username or, without one, by title.
+    fn is(&self, name: &str) -> bool {
+        match &self.username {
+            Some(username) => username == name.trim_start_matches('@'),
+            None => self.title == name,
+        }
+    }
+}
+
+impl MockPin {
+    fn pinned(&self) -> Pinned {
+        let reply_header = self.topic.map(|topic| {
+            tl::types::MessageReplyHeader {
+                reply_to_scheduled: false,
+                forum_topic: true,
+                quote: false,
+                reply_to_msg_id: Some(topic),
+                reply_to_peer_id: None,
+                reply_from: None,
+                reply_media: None,
+                reply_to_top_id: None,
+                quote_text: None,
+                quote_entities: None,
+            }
+            .into()
+        });
+        Pinned {
+            id: self.id,
+            sender: Some(Sender {
+                name: self.sender.clone(),
+                id: self.sender_id,
+                chat: None,
+            }),
+            out: false,
+            text: self.text.clone(),
+            entities: None,
+            date: self.date.date_naive().to_string(),
+            datetime: self.date.to_rfc3339_opts(SecondsFormat::Secs, true),
+            views: self.views,
+            forwards: self.forwards,
+            media: None,
+            reply_header,
+            raw: None,
+        }
+    }
+}
+
+/// Fetches the pins of the configured `chats`, and of the `dialogs` whose title matches one
+/// of `patterns`, the way a run does, with `dialogs` standing in for Telegram: chats are
+/// looked up by name, fetched once however often they are configured, and their pins
+/// filtered by `max_age_days` and topic. Neither media nor custom emoji are resolved.
+pub fn collect_mock(
+    chats: Vec<ChatConfig>,
+    patterns: &[String],
+    dialogs: &[MockChat],
+) -> Result<FetchedPins> {
+    let patterns = title_patterns(patterns)?;
+    let matching = dialogs
+        .iter()
+        .filter(|dialog| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&dialog.title))
+        })
+        .map(|dialog| {
+            let name = dialog.username.as_ref().unwrap_or(&dialog.title);
+            (name.clone(), dialog.pack())
+        })
+        .collect();
+    let me = Sender {
+        name: "me".to_string(),
+        id: 0,
+        chat: None,
+    };
+
+    let mut fetched = FetchedPins {
+        messages: Vec::new(),
+        chats: Vec::new(),
+        skipped: Vec::new(),
+        mismatches: Vec::new(),
+        unchanged: Vec::new(),
+        counts: Vec::new(),
+        chat_ids: HashSet::new(),
+    };
+    let mut fetched_chats: HashMap<(i64, String), String> = HashMap::new();
+    for (chat_config, _) in with_dialogs(chats, matching) {
+        let chat_name = chat_config.username.as_str();
+        let Some(dialog) = dialogs.iter().find(|dialog| dialog.is(chat_name)) else {
+            fetched.skipped.push(SkippedChat {
+                name: chat_config
+                    .alias
+                    .as_deref()
+                    .unwrap_or(chat_name)
+                    .to_string(),
+                reason: "not found".to_string(),
+            });
+            continue;
+        };
+        let chat = dialog.pack();
+        if let Some(first) = fetched_chats.get(&fetch_key(chat, &chat_config)) {
+            warn_duplicate(chat_name, first);
+            continue;
+        }
+        fetched_chats.insert(fetch_key(chat, &chat_config), chat_name.to_string());
+        fetched.chat_ids.insert(chat.id);
+
+        // Left out by Telegram itself, which is asked for nothing older.
+        let min_date = min_date(&chat_config);
+        let mut pins: Vec<_> = dialog
+            .pins
+            .iter()
+            .filter(|pin| min_date.is_none_or(|min_date| pin.date >= min_date))
+            .map(MockPin::pinned)
+            .collect();
+        let count = PinCount {
+            count: pins.len(),
+            top_id: pins.first().map_or(0, |pin| pin.id),
+        };
+        fetched.counts.push((chat_name.to_string(), count));
+
+        let info = ChatInfo {
+            name: chat_name.to_string(),
+            id: chat.id,
+            title: Some(dialog.title.clone()),
+            username: dialog.username.clone(),
+            alias: chat_config.alias.clone(),
+            ..Default::default()
+        };
+        let topic_id = match &chat_config.topic {
+            Some(TopicRef::Id(id)) => Some(*id),
+            Some(TopicRef::Title(title)) => {
+                let topics = dialog.topics.iter();
+                let topics = topics.map(|(id, title)| (*id, title.as_str()));
+                Some(topics::find_topic(topics, title)?)
+            }
+            None => None,
+        };
+        let mut chat_pins = ChatPins {
+            name: chat_name,
+            config: &chat_config,
+            chat,
+            info: &info,
+            me: &me,
+            topic_id,
+            pin_order: 0,
+        };
+        let messages = pins.iter_mut().filter_map(|pin| chat_pins.message(pin));
+        fetched.messages.extend(messages);
+        fetched.chats.push(info);
+    }
+    Ok(fetched)
+}
+
+/// The ID of the chat a message was pinned or unpinned in, if `update` is such an event.
+pub fn pin_update_chat(update: &Update) -> Option<i64> {
+    match update {
+        Update::Raw(tl::enums::Update::PinnedMessages(update)) => Some(peer_id(&update.peer)),
+        Update::Raw(tl::enums::Update::PinnedChannelMessages(update)) => Some(update.channel_id),
+        // Groups also announce pins with a service message.
+        Update::NewMessage(message)
+            if matches!(message.action(), Some(tl::enums::MessageAction::PinMessage)) =>
+        {
+            Some(message.chat().id())
+        }
+        _ => None,
+    }
+}
+
+/// Data center test accounts start on; test numbers `99966XYYYY` go through DC `X`.
+const TEST_DC: i32 = 2;
+
+/// Address of data center `dc_id`, either a real one or one of the test data centers.
+pub fn dc_addr(dc_id: i32, test: bool) -> Result<SocketAddr> {
+    let ip = match (dc_id, test) {
+        (1, false) => [149, 154, 175, 53],
+        (2, false) => [149, 154, 167, 51],
+        (3, false) => [149, 154, 175, 100],
+        (4, false) => [149, 154, 167, 92],
+        (5, false) => [91, 108, 56, 190],
+        (1, true) => [149, 154, 175, 10],
+        (2, true) => [149, 154, 167, 40],
+        (3, true) => [149, 154, 175, 117],
+        _ => return Err(format!("Telegram has no data center {}", dc_id).into()),
+    };
+    Ok(SocketAddr::from((ip, 443)))
+}
+
+/// Connection parameters for data center `dc_id`, with the configured device details. Only
+/// the test data centers need their address given; the library knows the real ones.
+fn params(config: &FileConfig, dc_id: i32) -> Result<InitParams> {
+    let creds = &config.telegram_api_creds;
+    let mut params = InitParams::default();
+    if creds.test_dc {
+        params.server_addr = Some(dc_addr(dc_id, true)?);
+    }
+    if let Some(device_model) = &creds.device_model {
+        params.device_model = device_model.clone();
+    }
+    if let Some(system_version) = &creds.system_version {
+        params.system_version = system_version.clone();
+    }
+    if let Some(app_version) = &creds.app_version {
+        params.app_version = app_version.clone();
+    }
+    Ok(params)
+}
+
+/// Connects with the session in `session_file`. With `catch_up`, updates missed since the
+/// session was last saved are delivered too, see [`pin_update_chat`].
+pub async fn connect(config: &FileConfig, session_file: &Path, catch_up: bool) -> Result<Client> {
+    let session = Session::load_file_or_create(session_file)?;
+    let dc_id = session.get_user().map_or(TEST_DC, |user| user.dc);
+    let client = Client::connect(Config {
+        session,
+        api_id: config.telegram_api_creds.api_id,
+        api_hash: config.telegram_api_creds.api_hash.clone(),
+        params: InitParams {
+            catch_up,
+            ..params(config, dc_id)?
+        },
+    })
+    .await?;
+
+    Ok(client)
+}
+
+/// The data center a `*_MIGRATE_X` error, e.g. `USER_MIGRATE_4` or `NETWORK_MIGRATE_4`,
+/// asks to repeat the request on.
+fn migrate_dc(error: &(dyn std::error::Error + 'static)) -> Option<i32> {
+    match error.downcast_ref::<InvocationError>()? {
+        InvocationError::Rpc(rpc) if rpc.code == 303 && rpc.name.ends_with("_MIGRATE") => {
+            rpc.value.map(|dc_id| dc_id as i32)
+        }
+        _ => None,
+    }
+}
+
+/// Moves the signed in session to data center `dc_id` and returns a client connected
+/// there. The authorization is exported from the current data center and imported into
+/// the new one, so no new login is needed.
+async fn migrate(client: &Client, config: &FileConfig, dc_id: i32) -> Result<Client> {
+    let Some(user) = client.session().get_user() else {
+        // Logging in already follows `PHONE_MIGRATE_X` on its own.
+        return Err(format!(
+            "Telegram asked to move to data center {} before login",
+            dc_id
+        )
+        .into());
+    };
+    log::warn!(
+        "Telegram moved the account to data center {}, reconnecting",
+        dc_id
+    );
+
+    let request = tl::functions::auth::ExportAuthorization { dc_id };
+    let tl::enums::auth::ExportedAuthorization::Authorization(exported) =
+        client.invoke(&request).await?;
+
+    client.session().set_user(user.id, dc_id, user.bot);
+    let migrated = Client::connect(Config {
+        session: Session::load(&client.session().save())?,
+        api_id: config.telegram_api_creds.api_id,
+        api_hash: config.telegram_api_creds.api_hash.clone(),
+        params: params(config, dc_id)?,
+    })
+    .await?;
+
+    let request = tl::functions::auth::ImportAuthorization {
+        id: exported.id,
+        bytes: exported.bytes,
+    };
+    migrated.invoke(&request).await?;
+
+    Ok(migrated)
+}
+
+/// Prints the account the session is logged in as, its data center and when the session
+/// was created.
+pub async fn whoami(client: &Client, config: &FileConfig, session_file: &Path) -> Result<()> {
+    if !client.is_authorized().await? {
+        return Err(Box::new(AuthRequiredError));
+    }
+    let me = client.get_me().await?;
+    match me.username() {
+        Some(username) => println!(
+            "Logged in as @{} ({}, ID {})",
+            username,
+            me.full_name(),
+            me.id()
+        ),
+        None => println!("Logged in as {} (ID {})", me.full_name(), me.id()),
+    }
+    if let Some(user) = client.session().get_user() {
+        let test = if config.telegram_api_creds.test_dc {
+            " (test)"
+        } else {
+            ""
+        };
+        println!("Data center: {}{}", user.dc, test);
+    }
+    match fs::metadata(session_file).and_then(|metadata| metadata.created()) {
+        Ok(created) => {
+            let created: DateTime<Utc> = created.into();
+            println!(
+                "Session: {}, created {} ({} days ago)",
+                session_file.display(),
+                created.format("%Y-%m-%d %H:%M UTC"),
+                (Utc::now() - created).num_days()
+            );
+        }
+        Err(_) => println!("Session: {}", session_file.display()),
+    }
+    Ok(())
+}
+
+/// Logs the session out on Telegram's side, so it cannot be used again, and deletes it along
+/// with the chat cache, whose access hashes only work for this account.
+pub async fn log_out(client: &Client, session_file: &Path, cache_file: &Path) -> Result<()> {
+    match client.is_authorized().await? {
+        true => {
+            client.sign_out().await?;
+            println!("Logged out.");
+        }
+        false => println!("Not logged in."),
+    }
+    for file in [session_file, cache_file] {
+        match fs::remove_file(file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The data center `X` of a test number `99966XYYYY`.
+fn test_number_dc(phone: &str) -> Option<char> {
+    let digits: String = phone.chars().filter(char::is_ascii_digit).collect();
+    digits.strip_prefix("99966")?.chars().next()
+}
+
+/// Test numbers `99966XYYYY` have no SIM to receive a code; theirs is always `X` five
+/// times.
+fn test_login_code(phone: &str) -> Option<String> {
+    Some(test_number_dc(phone)?.to_string().repeat(5))
+}
+
+pub async fn sign_in(
+    client: &mut Client,
+    config: &FileConfig,
+    session_file: &Path,
+    interactive: bool,
+) -> Result<()> {
+    let authorized = match client.is_authorized().await {
+        Err(e) => match migrate_dc(&e) {
+            Some(dc_id) => {
+                *client = migrate(client, config, dc_id).await?;
+                client.is_authorized().await?
+            }
+            None => return Err(e.into()),
+        },
+        Ok(authorized) => authorized,
+    };
+    if authorized {
+        return Ok(());
+    }
+
+    let creds = &config.telegram_api_creds;
+    let test_phone = creds.test_phone.as_deref().filter(|_| creds.test_dc);
+    if !interactive && test_phone.is_none() {
+        return Err(Box::new(AuthRequiredError));
+    }
+
+    println!("Signing in...");
+    let phone = match test_phone {
+        Some(phone) => phone.to_string(),
+        None => prompt("Enter your phone number (international format): ")?,
+    };
+    // Logging in follows `PHONE_MIGRATE_X` with the address the client was connected with,
+    // which for the test data centers is always that of `TEST_DC`.
+    let test_dc = char::from_digit(TEST_DC as u32, 10);
+    if let Some(dc) = test_number_dc(&phone).filter(|dc| creds.test_dc && Some(*dc) != test_dc) {
+        return Err(format!(
+            "Test number {} belongs to data center {}; only test numbers 99966{}YYYY can log in",
+            phone.trim(),
+            dc,
+            TEST_DC
+        )
+        .into());
+    }
+    let token = client.request_login_code(&phone).await?;
+    let code = match test_login_code(&phone) {
+        Some(code) if creds.test_dc => code,
+        _ => prompt("Enter the code you received: ")?,
+    };
+    let signed_in = client.sign_in(&token, &code).await;
+    match signed_in {
+        Err(SignInError::PasswordRequired(password_token)) => {
+            // Note: this `prompt` method will echo the password in the console.
+            //       Real code might want to use a better way to handle this.
+            let hint = password_token.hint().unwrap_or("None");
+            let prompt_message = format!("Enter the password (hint {}): ", &hint);
+            let password = prompt(prompt_message.as_str())?;
+
+            client
+                .check_password(password_token, password.trim())
+                .await?;
+        }
+        Ok(_) => (),
+        Err(e) => panic!("{}", e),
+    };
+    client.session().save_to_file(session_file)?;
+    println!("Signed in!");
+
+    Ok(())
+}
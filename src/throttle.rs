@@ -0,0 +1,109 @@
+use futures_util::stream;
+use serde::{Deserialize, Deserializer};
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+use crate::units::ByteSize;
+
+/// Bytes per chunk handed to the network while throttling.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// A transfer rate limit, written in config as e.g. `"2MiB/s"`.
+#[derive(Clone, Copy, Debug)]
+pub struct Bandwidth(pub ByteSize);
+
+impl<'de> Deserialize<'de> for Bandwidth {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        let size = raw.trim().trim_end_matches("/s");
+        ByteSize::parse(size)
+            .filter(|size| size.0 > 0)
+            .map(Bandwidth)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid bandwidth {:?}", raw)))
+    }
+}
+
+/// Keeps a single transfer at or under its bandwidth by sleeping between chunks.
+pub struct Throttle {
+    bytes_per_sec: u64,
+    started: Instant,
+    transferred: u64,
+}
+
+impl Throttle {
+    pub fn new(bandwidth: Bandwidth) -> Self {
+        Throttle {
+            bytes_per_sec: bandwidth.0 .0,
+            started: Instant::now(),
+            transferred: 0,
+        }
+    }
+
+    /// Records `bytes` more and returns how long to pause to stay under the limit.
+    fn delay(&mut self, bytes: usize) -> Duration {
+        self.transferred += bytes as u64;
+        let due = Duration::from_secs_f64(self.transferred as f64 / self.bytes_per_sec as f64);
+        due.saturating_sub(self.started.elapsed())
+    }
+
+    pub async fn wait(&mut self, bytes: usize) {
+        let delay = self.delay(bytes);
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    pub fn wait_blocking(&mut self, bytes: usize) {
+        let delay = self.delay(bytes);
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+/// Request body for `bytes`, streamed in chunks at no more than `bandwidth` when set.
+pub fn body(bytes: Vec<u8>, bandwidth: Option<Bandwidth>) -> reqwest::Body {
+    let Some(bandwidth) = bandwidth else {
+        return bytes.into();
+    };
+
+    let chunks = stream::unfold(
+        (bytes, 0, Throttle::new(bandwidth)),
+        |(bytes, offset, mut throttle)| async move {
+            if offset >= bytes.len() {
+                return None;
+            }
+            let end = (offset + CHUNK_SIZE).min(bytes.len());
+            let chunk = bytes[offset..end].to_vec();
+            throttle.wait(chunk.len()).await;
+            Some((Ok::<_, io::Error>(chunk), (bytes, end, throttle)))
+        },
+    );
+    reqwest::Body::wrap_stream(chunks)
+}
+
+/// Blocking reader that paces reads to at most `bandwidth`.
+pub struct ThrottledReader<R> {
+    inner: R,
+    throttle: Option<Throttle>,
+}
+
+impl<R: Read> ThrottledReader<R> {
+    pub fn new(inner: R, bandwidth: Option<Bandwidth>) -> Self {
+        ThrottledReader {
+            inner,
+            throttle: bandwidth.map(Throttle::new),
+        }
+    }
+}
+
+impl<R: Read> Read for ThrottledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(CHUNK_SIZE);
+        let read = self.inner.read(&mut buf[..len])?;
+        if let Some(throttle) = &mut self.throttle {
+            throttle.wait_blocking(read);
+        }
+        Ok(read)
+    }
+}
@@ -0,0 +1,69 @@
+use grammers_client::Client;
+use grammers_session::PackedChat;
+use grammers_tl_types as tl;
+use std::fmt;
+
+use crate::config::TopicRef;
+use crate::Result;
+
+/// ID of the "General" topic every forum has, which messages reach without a reply header.
+const GENERAL_TOPIC_ID: i32 = 1;
+
+#[derive(Debug)]
+struct TopicNotFound(String);
+
+impl fmt::Display for TopicNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Topic {} could not be found", self.0)
+    }
+}
+
+impl std::error::Error for TopicNotFound {}
+
+/// Turns a configured topic into its ID, looking titles up among the chat's forum topics.
+pub async fn resolve_topic(client: &Client, chat: PackedChat, topic: &TopicRef) -> Result<i32> {
+    let title = match topic {
+        TopicRef::Id(id) => return Ok(*id),
+        TopicRef::Title(title) => title,
+    };
+
+    let channel = chat
+        .try_to_input_channel()
+        .ok_or_else(|| TopicNotFound(format!("{} (chat is not a forum)", title)))?;
+    let request = tl::functions::channels::GetForumTopics {
+        channel,
+        q: Some(title.clone()),
+        offset_date: 0,
+        offset_id: 0,
+        offset_topic: 0,
+        limit: 100,
+    };
+    let tl::enums::messages::ForumTopics::Topics(topics) = client.invoke(&request).await?;
+
+    let topics = topics.topics.iter().filter_map(|topic| match topic {
+        tl::enums::ForumTopic::Topic(topic) => Some((topic.id, topic.title.as_str())),
+        _ => None,
+    });
+    find_topic(topics, title)
+}
+
+/// The ID of the topic titled `title` among `topics`, as ID and title pairs.
+pub fn find_topic<'a>(
+    mut topics: impl Iterator<Item = (i32, &'a str)>,
+    title: &str,
+) -> Result<i32> {
+    topics
+        .find(|(_, topic)| topic.eq_ignore_ascii_case(title))
+        .map(|(id, _)| id)
+        .ok_or_else(|| TopicNotFound(title.to_string()).into())
+}
+
+/// Whether a message with `reply_header` was posted inside the forum topic `topic_id`.
+pub fn in_topic(reply_header: Option<&tl::enums::MessageReplyHeader>, topic_id: i32) -> bool {
+    match reply_header {
+        Some(tl::enums::MessageReplyHeader::Header(header)) if header.forum_topic => {
+            header.reply_to_top_id.or(header.reply_to_msg_id) == Some(topic_id)
+        }
+        _ => topic_id == GENERAL_TOPIC_ID,
+    }
+}
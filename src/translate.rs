@@ -0,0 +1,176 @@
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+
+use crate::{Message, Result};
+
+/// DeepL accepts at most this many texts per request.
+const DEEPL_BATCH: usize = 50;
+
+fn default_deepl_url() -> String {
+    "https://api-free.deepl.com".to_string()
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DeeplConfig {
+    pub api_key: String,
+    /// `https://api.deepl.com` for Pro accounts.
+    #[serde(default = "default_deepl_url")]
+    pub url: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LibreTranslateConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+}
+
+/// Translation service, selected by the `provider` key of `[translate]`.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum TranslateProvider {
+    Deepl(DeeplConfig),
+    LibreTranslate(LibreTranslateConfig),
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TranslateConfig {
+    #[serde(flatten)]
+    pub provider: TranslateProvider,
+    /// Language to translate into, as the service names it (`EN-GB` for DeepL, `en` for
+    /// LibreTranslate).
+    pub target: String,
+}
+
+#[derive(Debug)]
+pub struct TranslateError(String);
+
+impl fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TranslateError {}
+
+/// A translation and the source language the service detected.
+struct Translation {
+    text: String,
+    source: String,
+}
+
+/// ISO 639-3 code of the language of `text`, if whatlang is confident about it.
+pub fn detect_language(text: &str) -> Option<String> {
+    let info = whatlang::detect(text)?;
+    info.is_reliable().then(|| info.lang().code().to_string())
+}
+
+pub fn detect_languages(messages: &mut [Message]) {
+    for message in messages {
+        message.language = detect_language(&message.text);
+    }
+}
+
+async fn json_response(res: reqwest::Response, service: &str) -> Result<Value> {
+    let status = res.status();
+    if !status.is_success() {
+        return Err(Box::new(TranslateError(format!(
+            "{} answered {}: {}",
+            service,
+            status,
+            res.text().await?
+        ))));
+    }
+    Ok(res.json().await?)
+}
+
+fn field<'a>(value: &'a Value, key: &str, service: &str) -> Result<&'a str> {
+    value[key]
+        .as_str()
+        .ok_or_else(|| Box::new(TranslateError(format!("{} reply has no {}", service, key))) as _)
+}
+
+async fn deepl(
+    http_client: &reqwest::Client,
+    config: &DeeplConfig,
+    target: &str,
+    texts: &[&str],
+) -> Result<Vec<Translation>> {
+    let url = format!("{}/v2/translate", config.url.trim_end_matches('/'));
+    let mut translations = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(DEEPL_BATCH) {
+        let res = http_client
+            .post(&url)
+            .header(
+                "Authorization",
+                format!("DeepL-Auth-Key {}", config.api_key),
+            )
+            .json(&json!({ "text": batch, "target_lang": target }))
+            .send()
+            .await?;
+        let reply = json_response(res, "DeepL").await?;
+        let Some(results) = reply["translations"].as_array() else {
+            return Err(Box::new(TranslateError(
+                "DeepL reply has no translations".to_string(),
+            )));
+        };
+        for result in results {
+            translations.push(Translation {
+                text: field(result, "text", "DeepL")?.to_string(),
+                source: field(result, "detected_source_language", "DeepL")?.to_string(),
+            });
+        }
+    }
+    Ok(translations)
+}
+
+async fn libretranslate(
+    http_client: &reqwest::Client,
+    config: &LibreTranslateConfig,
+    target: &str,
+    texts: &[&str],
+) -> Result<Vec<Translation>> {
+    let url = format!("{}/translate", config.url.trim_end_matches('/'));
+    let mut translations = Vec::with_capacity(texts.len());
+    for text in texts {
+        let mut body = json!({ "q": text, "source": "auto", "target": target, "format": "text" });
+        if let Some(api_key) = &config.api_key {
+            body["api_key"] = json!(api_key);
+        }
+        let res = http_client.post(&url).json(&body).send().await?;
+        let reply = json_response(res, "LibreTranslate").await?;
+        translations.push(Translation {
+            text: field(&reply, "translatedText", "LibreTranslate")?.to_string(),
+            source: field(&reply["detectedLanguage"], "language", "LibreTranslate")?.to_string(),
+        });
+    }
+    Ok(translations)
+}
+
+/// Fills in `text_translated` for every message with text, except those already in the
+/// target language.
+pub async fn translate(config: &TranslateConfig, messages: &mut [Message]) -> Result<()> {
+    let http_client = reqwest::Client::new();
+    let mut pending: Vec<&mut Message> = messages
+        .iter_mut()
+        .filter(|m| !m.text.trim().is_empty())
+        .collect();
+    let texts: Vec<&str> = pending.iter().map(|m| m.text.as_str()).collect();
+    let translations = match &config.provider {
+        TranslateProvider::Deepl(deepl_config) => {
+            deepl(&http_client, deepl_config, &config.target, &texts).await?
+        }
+        TranslateProvider::LibreTranslate(libre_config) => {
+            libretranslate(&http_client, libre_config, &config.target, &texts).await?
+        }
+    };
+
+    // `EN-GB` targets match an `EN` source.
+    let target = config.target.split('-').next().unwrap_or_default();
+    for (message, translation) in pending.iter_mut().zip(translations) {
+        if !translation.source.eq_ignore_ascii_case(target) {
+            message.text_translated = Some(translation.text);
+        }
+    }
+    Ok(())
+}
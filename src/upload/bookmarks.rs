@@ -0,0 +1,269 @@
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::links;
+use crate::{Message, Result};
+
+const RAINDROP_API: &str = "https://api.raindrop.io/rest/v1";
+
+#[derive(Deserialize, Clone)]
+pub struct LinkdingConfig {
+    /// Base URL of the Linkding instance, e.g. `https://links.example.com`.
+    pub url: String,
+    /// REST API token, from the instance's settings page.
+    pub token: String,
+    /// Tags added to every bookmark besides the chat's.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WallabagConfig {
+    /// Base URL of the wallabag instance, e.g. `https://app.wallabag.it`.
+    pub url: String,
+    /// API client, created under "API clients management".
+    pub client_id: String,
+    pub client_secret: String,
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RaindropConfig {
+    /// Test token of an integration, from the app's settings.
+    pub token: String,
+    /// Collection the bookmarks go to; "Unsorted" when unset.
+    pub collection: Option<i64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A link found in a pin, with what the services store next to it.
+struct Bookmark<'a> {
+    url: String,
+    tags: Vec<String>,
+    message: &'a Message,
+}
+
+impl Bookmark<'_> {
+    /// The pin's text and link, kept as the bookmark's note; the services fetch the title
+    /// and description of the page themselves.
+    fn notes(&self) -> String {
+        let notes = format!(
+            "{}\n\nPinned in {}",
+            self.message.text.trim(),
+            self.message.chat_label()
+        );
+        match self.message.link() {
+            Some(link) => format!("{}: {}", notes, link),
+            None => notes,
+        }
+    }
+}
+
+/// Tags can't contain spaces in Linkding, nor commas in wallabag.
+fn tag(name: &str) -> String {
+    name.trim_start_matches('@')
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Every distinct `http(s)` link in the texts, tagged with the chat it was pinned in.
+fn bookmarks<'a>(messages: &'a [Message], extra_tags: &[String]) -> Vec<Bookmark<'a>> {
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    for message in messages {
+        for url in links::urls(&message.text) {
+            if bookmarks.iter().any(|bookmark| bookmark.url == url) {
+                continue;
+            }
+            let mut tags = vec![tag(message.chat_label())];
+            for name in extra_tags.iter().map(|name| tag(name)) {
+                if !tags.contains(&name) {
+                    tags.push(name);
+                }
+            }
+            bookmarks.push(Bookmark {
+                url: url.to_string(),
+                tags,
+                message,
+            });
+        }
+    }
+    bookmarks
+}
+
+async fn check(res: reqwest::Response, service: &str) -> Result<Value> {
+    let status = res.status();
+    let body = res.text().await?;
+    if !status.is_success() {
+        return Err(Box::new(UploadError(format!(
+            "{} answered {}: {}",
+            service, status, body
+        ))));
+    }
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Adds the links of the pins to Linkding, leaving those it already has alone.
+pub async fn linkding(
+    upload: &UploadContext<'_>,
+    config: &LinkdingConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+    let base = config.url.trim_end_matches('/');
+    let authorization = format!("Token {}", config.token);
+
+    let mut added = 0;
+    for bookmark in bookmarks(messages, &config.tags) {
+        let res = http_client
+            .get(format!("{}/api/bookmarks/check/", base))
+            .header("Authorization", &authorization)
+            .query(&[("url", &bookmark.url)])
+            .send()
+            .await?;
+        if !check(res, "Linkding").await?["bookmark"].is_null() {
+            continue;
+        }
+        let body = json!({
+            "url": bookmark.url,
+            "notes": bookmark.notes(),
+            "tag_names": bookmark.tags,
+        });
+        let res = http_client
+            .post(format!("{}/api/bookmarks/", base))
+            .header("Authorization", &authorization)
+            .json(&body)
+            .send()
+            .await?;
+        check(res, "Linkding").await?;
+        added += 1;
+    }
+    println!("Added {} new bookmarks to Linkding", added);
+
+    Ok(UploadResult {
+        url: base.to_string(),
+        id: None,
+        size: None,
+    })
+}
+
+/// Saves the links of the pins as wallabag entries, leaving those it already has alone.
+pub async fn wallabag(
+    upload: &UploadContext<'_>,
+    config: &WallabagConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+    let base = config.url.trim_end_matches('/');
+    let form = [
+        ("grant_type", "password"),
+        ("client_id", &config.client_id),
+        ("client_secret", &config.client_secret),
+        ("username", &config.username),
+        ("password", &config.password),
+    ];
+    let res = http_client
+        .post(format!("{}/oauth/v2/token", base))
+        .form(&form)
+        .send()
+        .await?;
+    let token = check(res, "wallabag").await?;
+    let Some(token) = token["access_token"].as_str() else {
+        return Err(Box::new(UploadError(
+            "wallabag answered without an access_token".to_string(),
+        )));
+    };
+
+    let mut added = 0;
+    for bookmark in bookmarks(messages, &config.tags) {
+        let res = http_client
+            .get(format!("{}/api/entries/exists.json", base))
+            .bearer_auth(token)
+            .query(&[("url", &bookmark.url)])
+            .send()
+            .await?;
+        if check(res, "wallabag").await?["exists"]
+            .as_bool()
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let body = json!({
+            "url": bookmark.url,
+            "tags": bookmark.tags.join(","),
+        });
+        let res = http_client
+            .post(format!("{}/api/entries.json", base))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await?;
+        check(res, "wallabag").await?;
+        added += 1;
+    }
+    println!("Added {} new entries to wallabag", added);
+
+    Ok(UploadResult {
+        url: base.to_string(),
+        id: None,
+        size: None,
+    })
+}
+
+/// Adds the links of the pins to Raindrop.io, leaving those it already has alone.
+pub async fn raindrop(
+    upload: &UploadContext<'_>,
+    config: &RaindropConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+
+    let mut added = 0;
+    for bookmark in bookmarks(messages, &config.tags) {
+        let res = http_client
+            .post(format!("{}/import/url/exists", RAINDROP_API))
+            .bearer_auth(&config.token)
+            .json(&json!({ "urls": [bookmark.url] }))
+            .send()
+            .await?;
+        if check(res, "Raindrop.io").await?["result"]
+            .as_bool()
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let mut body = json!({
+            "link": bookmark.url,
+            "note": bookmark.notes(),
+            "tags": bookmark.tags,
+            "pleaseParse": {},
+        });
+        if let Some(collection) = config.collection {
+            body["collection"] = json!({ "$id": collection });
+        }
+        let res = http_client
+            .post(format!("{}/raindrop", RAINDROP_API))
+            .bearer_auth(&config.token)
+            .json(&body)
+            .send()
+            .await?;
+        check(res, "Raindrop.io").await?;
+        added += 1;
+        // Stay below Raindrop.io's 120 requests per minute.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    println!("Added {} new bookmarks to Raindrop.io", added);
+
+    Ok(UploadResult {
+        url: "https://app.raindrop.io".to_string(),
+        id: config.collection.map(|id| id.to_string()),
+        size: None,
+    })
+}
@@ -0,0 +1,90 @@
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::{Message, Result};
+
+/// Discord rejects embed descriptions longer than this many characters.
+const MAX_DESCRIPTION: usize = 4096;
+
+#[derive(Deserialize, Clone)]
+pub struct DiscordConfig {
+    pub webhook_url: String,
+    /// Name the webhook posts under, instead of the one set in Discord.
+    pub username: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RateLimited {
+    retry_after: f64,
+}
+
+fn embed(message: &Message) -> Value {
+    let description = match (&message.sticker, message.text.is_empty()) {
+        (Some(sticker), true) => sticker.emoji.clone(),
+        _ => message.text.chars().take(MAX_DESCRIPTION).collect(),
+    };
+    let mut embed = json!({
+        "description": description,
+        "timestamp": format!("{}T00:00:00Z", message.date),
+        "footer": { "text": message.chat_label() },
+    });
+    if !message.sender.is_empty() {
+        embed["author"] = json!({ "name": message.sender });
+    }
+    if let Some(link) = message.link() {
+        embed["url"] = json!(link);
+    }
+    embed
+}
+
+/// Posts each message as an embed to the webhook, waiting out rate limits as Discord asks.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &DiscordConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+
+    for message in messages {
+        let mut body = json!({ "embeds": [embed(message)] });
+        if let Some(username) = &config.username {
+            body["username"] = json!(username);
+        }
+
+        loop {
+            let res = http_client
+                .post(&config.webhook_url)
+                .json(&body)
+                .send()
+                .await?;
+            let status = res.status();
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                let limit: RateLimited = res.json().await?;
+                tokio::time::sleep(Duration::from_secs_f64(limit.retry_after)).await;
+                continue;
+            }
+            if !status.is_success() {
+                return Err(Box::new(UploadError(format!(
+                    "Discord answered {}: {}",
+                    status,
+                    res.text().await?
+                ))));
+            }
+            break;
+        }
+    }
+    println!("Posted {} messages to Discord", messages.len());
+
+    // The last path segment is the webhook token, which should not end up in logs.
+    let url = match config.webhook_url.rsplit_once('/') {
+        Some((base, _)) => base.to_string(),
+        None => config.webhook_url.clone(),
+    };
+    Ok(UploadResult {
+        url,
+        id: None,
+        size: None,
+    })
+}
@@ -0,0 +1,193 @@
+use serde_derive::Deserialize;
+use std::fs;
+use std::io::Cursor;
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+use suppaftp::native_tls::{Certificate, TlsConnector};
+use suppaftp::{FtpError, Mode, NativeTlsConnector, NativeTlsFtpStream, Status};
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::config::UploadConfig;
+use crate::export::Collision;
+use crate::export::ExportFile;
+use crate::throttle::{Bandwidth, ThrottledReader};
+use crate::Result;
+
+fn default_port() -> u16 {
+    21
+}
+
+fn default_passive() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone)]
+pub struct FtpConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    /// Directory the export is stored in, created if missing.
+    #[serde(default)]
+    pub remote_dir: String,
+    #[serde(default = "default_passive")]
+    pub passive: bool,
+    /// Upgrade the control connection with `AUTH TLS` (explicit FTPS).
+    #[serde(default)]
+    pub tls: bool,
+}
+
+fn tls_connector(upload: &UploadConfig) -> Result<NativeTlsConnector> {
+    let mut builder = TlsConnector::builder();
+    if let Some(path) = &upload.ca_certificate {
+        builder.add_root_certificate(Certificate::from_pem(&fs::read(path)?)?);
+    }
+    builder.danger_accept_invalid_certs(upload.danger_accept_invalid_certs);
+    Ok(NativeTlsConnector::from(builder.build()?))
+}
+
+type BlockingResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Logs in and changes into `remote_dir`, creating it if missing.
+fn connect(
+    config: &FtpConfig,
+    connect_timeout: Option<Duration>,
+    tls: Option<NativeTlsConnector>,
+) -> BlockingResult<NativeTlsFtpStream> {
+    let addr = (config.host.as_str(), config.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| UploadError(format!("Could not resolve FTP host {}", config.host)))?;
+
+    let mut ftp = match connect_timeout {
+        Some(timeout) => NativeTlsFtpStream::connect_timeout(addr, timeout)?,
+        None => NativeTlsFtpStream::connect(addr)?,
+    };
+    if let Some(tls) = tls {
+        ftp = ftp.into_secure(tls, &config.host)?;
+    }
+
+    ftp.login(config.username.as_str(), config.password.as_str())?;
+    ftp.set_mode(if config.passive {
+        Mode::Passive
+    } else {
+        Mode::Active
+    });
+
+    for dir in config.remote_dir.split('/').filter(|d| !d.is_empty()) {
+        if ftp.cwd(dir).is_err() {
+            ftp.mkdir(dir)?;
+            ftp.cwd(dir)?;
+        }
+    }
+    Ok(ftp)
+}
+
+/// Whether `remote_dir` has a file called `name`, going by whether the server knows its size.
+fn exists(ftp: &mut NativeTlsFtpStream, name: &str) -> BlockingResult<bool> {
+    match ftp.size(name) {
+        Ok(_) => Ok(true),
+        Err(FtpError::UnexpectedResponse(res)) if res.status == Status::FileUnavailable => {
+            Ok(false)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn upload_blocking(
+    config: FtpConfig,
+    connect_timeout: Option<Duration>,
+    tls: Option<NativeTlsConnector>,
+    max_bandwidth: Option<Bandwidth>,
+    collision: Collision,
+    file: ExportFile,
+) -> BlockingResult<UploadResult> {
+    let mut ftp = connect(&config, connect_timeout, tls)?;
+    let name = collision.resolve(&file.name, |name| exists(&mut ftp, name))?;
+    let size = match &name {
+        Some(name) => {
+            let mut reader = ThrottledReader::new(Cursor::new(file.bytes), max_bandwidth);
+            Some(ftp.put_file(name, &mut reader)?)
+        }
+        None => None,
+    };
+    ftp.quit()?;
+
+    let name = name.unwrap_or_else(|| {
+        log::info!("{} is already on the FTP server, skipped", file.name);
+        file.name
+    });
+    let scheme = if config.tls { "ftps" } else { "ftp" };
+    let dir = config.remote_dir.trim_matches('/');
+    let path = if dir.is_empty() {
+        name
+    } else {
+        format!("{}/{}", dir, name)
+    };
+
+    Ok(UploadResult {
+        url: format!("{}://{}:{}/{}", scheme, config.host, config.port, path),
+        id: None,
+        size,
+    })
+}
+
+fn download_blocking(
+    config: FtpConfig,
+    connect_timeout: Option<Duration>,
+    tls: Option<NativeTlsConnector>,
+    name: String,
+) -> BlockingResult<Option<Vec<u8>>> {
+    let mut ftp = connect(&config, connect_timeout, tls)?;
+    let bytes = match ftp.retr_as_buffer(&name) {
+        Ok(cursor) => Some(cursor.into_inner()),
+        Err(FtpError::UnexpectedResponse(res)) if res.status == Status::FileUnavailable => None,
+        Err(e) => return Err(e.into()),
+    };
+    ftp.quit()?;
+    Ok(bytes)
+}
+
+fn tls(upload: &UploadContext<'_>, config: &FtpConfig) -> Result<Option<NativeTlsConnector>> {
+    match config.tls {
+        true => Ok(Some(tls_connector(upload.upload)?)),
+        false => Ok(None),
+    }
+}
+
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &FtpConfig,
+    mut file: ExportFile,
+) -> Result<UploadResult> {
+    let tls = tls(upload, config)?;
+    let connect_timeout = upload.upload.connect_timeout.map(Duration::from_secs);
+    let max_bandwidth = upload.max_bandwidth;
+    let (collision, name) = Collision::apply(upload.collision, &file.name, upload.run_id);
+    file.name = name;
+    let config = config.clone();
+
+    // suppaftp is blocking, so keep it off the runtime thread.
+    tokio::task::spawn_blocking(move || {
+        upload_blocking(config, connect_timeout, tls, max_bandwidth, collision, file)
+    })
+    .await?
+    .map_err(|e| e as Box<dyn std::error::Error>)
+}
+
+/// Fetches `name` from `remote_dir`, `None` if it does not exist yet.
+pub async fn download(
+    upload: &UploadContext<'_>,
+    config: &FtpConfig,
+    name: &str,
+) -> Result<Option<Vec<u8>>> {
+    let tls = tls(upload, config)?;
+    let connect_timeout = upload.upload.connect_timeout.map(Duration::from_secs);
+    let config = config.clone();
+    let name = name.to_string();
+
+    tokio::task::spawn_blocking(move || download_blocking(config, connect_timeout, tls, name))
+        .await?
+        .map_err(|e| e as Box<dyn std::error::Error>)
+}
@@ -0,0 +1,186 @@
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::config::ConfigError;
+use crate::export::ExportFile;
+use crate::Result;
+
+const API_URL: &str = "https://api.gofile.io";
+
+#[derive(Deserialize, Clone)]
+pub struct GofileConfig {
+    pub api_token: Option<String>,
+    /// File holding the token, read again on every use so it can be rotated without a
+    /// restart. Takes precedence over `api_token`.
+    pub token_file: Option<PathBuf>,
+}
+
+impl GofileConfig {
+    fn token(&self) -> Result<String> {
+        match (&self.token_file, &self.api_token) {
+            (Some(path), _) => {
+                let token = fs::read_to_string(path).map_err(|e| {
+                    ConfigError(format!(
+                        "Could not read the gofile token from {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                Ok(token.trim().to_string())
+            }
+            (None, Some(token)) => Ok(token.clone()),
+            (None, None) => Err(Box::new(ConfigError(
+                "The gofile provider needs api_token or token_file".to_string(),
+            ))),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GofileResponse<T> {
+    status: String,
+    data: Option<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GofileFile {
+    download_page: String,
+    #[serde(alias = "fileId")]
+    id: Option<String>,
+    size: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct AccountId {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountDetails {
+    tier: Option<String>,
+    #[serde(default)]
+    stats_current: AccountStats,
+    subscription_limit_storage: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct AccountStats {
+    file_count: Option<u64>,
+    storage: Option<u64>,
+}
+
+/// What the account behind the token has stored, as gofile reports it.
+#[derive(Serialize, Debug, Clone)]
+pub struct AccountInfo {
+    pub tier: Option<String>,
+    pub files: Option<u64>,
+    /// Bytes stored.
+    pub storage: Option<u64>,
+    /// Bytes the account's tier allows, when it has a limit.
+    pub storage_limit: Option<u64>,
+}
+
+impl fmt::Display for AccountInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "gofile {}", self.tier.as_deref().unwrap_or("account"))?;
+        if let Some(files) = self.files {
+            write!(f, ", {} files", files)?;
+        }
+        match (self.storage, self.storage_limit) {
+            (Some(storage), Some(limit)) => write!(f, ", {} of {} bytes used", storage, limit),
+            (Some(storage), None) => write!(f, ", {} bytes used", storage),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Sends an authorized GET to the accounts API and unwraps gofile's status envelope.
+async fn get<T: DeserializeOwned>(
+    http_client: &reqwest::Client,
+    token: &str,
+    path: &str,
+) -> Result<T> {
+    let body = http_client
+        .get(format!("{}{}", API_URL, path))
+        .bearer_auth(token)
+        .send()
+        .await?
+        .text()
+        .await?;
+    log::debug!("Response from remote: {}", body);
+    let response: GofileResponse<T> = serde_json::from_str(&body)
+        .map_err(|e| UploadError(format!("Unexpected response from gofile ({}): {}", e, body)))?;
+    match response.data {
+        Some(data) if response.status == "ok" => Ok(data),
+        _ => Err(Box::new(UploadError(format!(
+            "gofile rejected the token (status {}): {}",
+            response.status, body
+        )))),
+    }
+}
+
+/// Checks the token with `getAccountDetails`, so a revoked or mistyped one fails the run
+/// before anything is fetched, and returns the account's quota.
+pub async fn account(upload: &UploadContext<'_>, config: &GofileConfig) -> Result<AccountInfo> {
+    let http_client = upload.http_client()?;
+    let token = config.token()?;
+    let AccountId { id } = get(&http_client, &token, "/accounts/getid").await?;
+    let details: AccountDetails = get(&http_client, &token, &format!("/accounts/{}", id)).await?;
+    Ok(AccountInfo {
+        tier: details.tier,
+        files: details.stats_current.file_count,
+        storage: details.stats_current.storage,
+        storage_limit: details.subscription_limit_storage,
+    })
+}
+
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &GofileConfig,
+    file: ExportFile,
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+    let mut file_part_headers = reqwest::header::HeaderMap::new();
+    file_part_headers.insert(
+        reqwest::header::CONTENT_TYPE,
+        file.content_type.parse().unwrap(),
+    );
+
+    let length = file.bytes.len() as u64;
+    let file_part = reqwest::multipart::Part::stream_with_length(upload.body(file.bytes), length)
+        .file_name(file.name)
+        .headers(file_part_headers);
+
+    let form = reqwest::multipart::Form::new()
+        .part("file", file_part)
+        .text("folderId", "cf71f5f5-d849-4c80-94c7-eb73e5253c86");
+
+    let req = http_client
+        .post("https://store1.gofile.io/contents/uploadfile")
+        .bearer_auth(config.token()?)
+        .multipart(form);
+
+    let body = req.send().await?.text().await?;
+    log::debug!("Response from remote: {}", body);
+
+    let response: GofileResponse<GofileFile> = serde_json::from_str(&body)
+        .map_err(|e| UploadError(format!("Unexpected response from gofile ({}): {}", e, body)))?;
+    match response.data {
+        Some(file) if response.status == "ok" => Ok(UploadResult {
+            url: file.download_page,
+            id: file.id,
+            size: file.size,
+        }),
+        _ => Err(Box::new(UploadError(format!(
+            "gofile rejected the upload (status {}): {}",
+            response.status, body
+        )))),
+    }
+}
@@ -0,0 +1,185 @@
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::export::{self, Collision, ExportFile};
+use crate::{Message, Result};
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+#[derive(Deserialize, Clone)]
+pub struct HttpConfig {
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Extra request headers, e.g. `Authorization`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub send: HttpMode,
+}
+
+/// What each request carries.
+#[derive(Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpMode {
+    /// The whole export file, in the configured export format.
+    #[default]
+    Export,
+    /// One JSON request per message.
+    Message,
+}
+
+fn request(
+    config: &HttpConfig,
+    http_client: &reqwest::Client,
+    url: &str,
+) -> Result<reqwest::RequestBuilder> {
+    let method = reqwest::Method::from_bytes(config.method.to_uppercase().as_bytes())?;
+    let mut req = http_client
+        .request(method, url)
+        .header(reqwest::header::USER_AGENT, "telegram-pin-fetcher");
+    for (name, value) in &config.headers {
+        req = req.header(name, value);
+    }
+    Ok(req)
+}
+
+async fn send(req: reqwest::RequestBuilder) -> Result<()> {
+    let res = req.send().await?;
+    let status = res.status();
+    if !status.is_success() {
+        return Err(Box::new(UploadError(format!(
+            "Webhook answered {}: {}",
+            status,
+            res.text().await?
+        ))));
+    }
+    Ok(())
+}
+
+/// Whether something is already at `url`, asked with a `HEAD`.
+async fn exists(config: &HttpConfig, http_client: &reqwest::Client, url: &str) -> Result<bool> {
+    let mut req = http_client
+        .head(url)
+        .header(reqwest::header::USER_AGENT, "telegram-pin-fetcher");
+    for (name, value) in &config.headers {
+        req = req.header(name, value);
+    }
+    let status = req.send().await?.status();
+    match status {
+        reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE => Ok(false),
+        _ if status.is_success() => Ok(true),
+        _ => Err(Box::new(UploadError(format!(
+            "Could not tell whether {} exists, it answered {}",
+            url, status
+        )))),
+    }
+}
+
+/// The URL to send the export to, `url` itself or a suffixed one as `on_collision` says;
+/// `None` to skip the upload.
+async fn target(
+    upload: &UploadContext<'_>,
+    config: &HttpConfig,
+    http_client: &reqwest::Client,
+) -> Result<Option<String>> {
+    // The URL is the same for every run, so unset means replacing it too.
+    let collision = upload.collision.unwrap_or(Collision::Overwrite);
+    if collision == Collision::Overwrite {
+        return Ok(Some(config.url.clone()));
+    }
+    let mut url = config.url.clone();
+    let mut n = 0;
+    while exists(config, http_client, &url).await? {
+        if collision == Collision::Skip {
+            return Ok(None);
+        }
+        n += 1;
+        url = export::suffixed(&config.url, n);
+    }
+    Ok(Some(url))
+}
+
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &HttpConfig,
+    file: ExportFile,
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+    let Some(url) = target(upload, config, &http_client).await? else {
+        log::info!("{} already exists, skipped {}", config.url, file.name);
+        return Ok(UploadResult {
+            url: config.url.clone(),
+            id: None,
+            size: None,
+        });
+    };
+    let size = file.bytes.len() as u64;
+    let req = request(config, &http_client, &url)?
+        .header(reqwest::header::CONTENT_TYPE, &file.content_type)
+        .header("X-Export-Name", &file.name)
+        .header("X-Run-Id", upload.run_id.to_string())
+        .header(
+            "Idempotency-Key",
+            format!("{}-{}", upload.run_id, file.name),
+        )
+        .body(upload.body(file.bytes));
+    send(req).await?;
+
+    Ok(UploadResult {
+        url,
+        id: None,
+        size: Some(size),
+    })
+}
+
+/// Fetches the export at `url` with a GET, as a WebDAV server serves back what was PUT there.
+/// `None` if there is nothing there yet.
+pub async fn download(upload: &UploadContext<'_>, config: &HttpConfig) -> Result<Option<Vec<u8>>> {
+    let http_client = upload.http_client()?;
+    let mut req = http_client
+        .get(&config.url)
+        .header(reqwest::header::USER_AGENT, "telegram-pin-fetcher");
+    for (name, value) in &config.headers {
+        req = req.header(name, value);
+    }
+    let res = req.send().await?;
+    let status = res.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(Box::new(UploadError(format!(
+            "Fetching {} answered {}: {}",
+            config.url,
+            status,
+            res.text().await?
+        ))));
+    }
+    Ok(Some(res.bytes().await?.to_vec()))
+}
+
+pub async fn upload_messages(
+    upload: &UploadContext<'_>,
+    config: &HttpConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+    for message in messages {
+        let req = request(config, &http_client, &config.url)?
+            .header("X-Run-Id", upload.run_id.to_string())
+            .header("Idempotency-Key", upload.idempotency_key(message))
+            .json(&upload.record(message));
+        send(req).await?;
+    }
+    println!("Sent {} messages to {}", messages.len(), config.url);
+
+    Ok(UploadResult {
+        url: config.url.clone(),
+        id: None,
+        size: None,
+    })
+}
@@ -0,0 +1,63 @@
+use rskafka::client::partition::{Compression, UnknownTopicHandling};
+use rskafka::client::ClientBuilder;
+use rskafka::record::Record;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+
+use super::{UploadContext, UploadResult};
+use crate::{Message, Result};
+
+#[derive(Deserialize, Clone)]
+pub struct KafkaConfig {
+    /// Bootstrap brokers as `host:port`.
+    pub brokers: Vec<String>,
+    pub topic: String,
+    #[serde(default)]
+    pub partition: i32,
+}
+
+/// Publishes each message as a JSON record keyed by `<chat>/<id>`.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &KafkaConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let client = ClientBuilder::new(config.brokers.clone())
+        .client_id("telegram-pin-fetcher")
+        .build()
+        .await?;
+    let partition = client
+        .partition_client(
+            config.topic.as_str(),
+            config.partition,
+            UnknownTopicHandling::Error,
+        )
+        .await?;
+
+    let now = chrono::offset::Utc::now();
+    let records = messages
+        .iter()
+        .map(|message| {
+            Ok(Record {
+                key: Some(format!("{}/{}", message.chat, message.id).into_bytes()),
+                value: Some(serde_json::to_vec(&upload.record(message))?),
+                headers: BTreeMap::new(),
+                timestamp: now,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let offsets = partition
+        .produce(records, Compression::NoCompression)
+        .await?;
+    println!(
+        "Published {} messages to Kafka topic {}",
+        offsets.len(),
+        config.topic
+    );
+
+    Ok(UploadResult {
+        url: format!("kafka://{}/{}", config.brokers.join(","), config.topic),
+        id: offsets.last().map(|offset| offset.to_string()),
+        size: None,
+    })
+}
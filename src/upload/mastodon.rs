@@ -0,0 +1,171 @@
+use serde_derive::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::{Message, Result};
+
+/// Mastodon counts every link as this many characters, whatever its real length.
+const LINK_LENGTH: usize = 23;
+
+fn default_max_length() -> usize {
+    500
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MastodonConfig {
+    /// Base URL of the instance, e.g. `https://mastodon.social`.
+    pub instance: String,
+    /// Token of an application with the `write:statuses` and `write:media` scopes.
+    pub access_token: String,
+    /// `public`, `unlisted`, `private` or `direct`; the account default when unset.
+    pub visibility: Option<String>,
+    /// Status length limit of the instance.
+    #[serde(default = "default_max_length")]
+    pub max_length: usize,
+}
+
+#[derive(Deserialize)]
+struct Created {
+    id: String,
+    url: Option<String>,
+}
+
+/// The message text, cut at a word boundary and ended with `…` when the status would
+/// exceed `max_length` once the link is appended.
+fn status_text(message: &Message, max_length: usize) -> String {
+    let text = match (&message.sticker, message.text.is_empty()) {
+        (Some(sticker), true) => sticker.emoji.as_str(),
+        _ => message.text.as_str(),
+    };
+    let prefix = match message.sender.is_empty() {
+        true => String::new(),
+        false => format!("{}: ", message.sender),
+    };
+    let link = message.link();
+    let link_length = link.as_ref().map_or(0, |_| 2 + LINK_LENGTH);
+    let budget = max_length.saturating_sub(prefix.chars().count() + link_length);
+
+    let body = match text.chars().count() <= budget {
+        true => text.to_string(),
+        false => {
+            let cut: String = text.chars().take(budget.saturating_sub(1)).collect();
+            let cut = match cut.rfind(char::is_whitespace) {
+                Some(end) if end > cut.len() / 2 => cut[..end].trim_end().to_string(),
+                _ => cut,
+            };
+            format!("{}…", cut)
+        }
+    };
+    match link {
+        Some(link) => format!("{}{}\n\n{}", prefix, body, link),
+        None => format!("{}{}", prefix, body),
+    }
+}
+
+/// Re-uploads a downloaded sticker as an attachment. Animated `.tgs` stickers are skipped
+/// because Mastodon cannot display them.
+async fn upload_media(
+    http_client: &reqwest::Client,
+    config: &MastodonConfig,
+    file: &str,
+) -> Result<Option<String>> {
+    let path = Path::new(file);
+    let mime = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("webp") => "image/webp",
+        Some("webm") => "video/webm",
+        _ => return Ok(None),
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let part = reqwest::multipart::Part::bytes(fs::read(path)?)
+        .file_name(name)
+        .mime_str(mime)?;
+    let form = reqwest::multipart::Form::new().part("file", part);
+
+    let res = http_client
+        .post(format!(
+            "{}/api/v2/media",
+            config.instance.trim_end_matches('/')
+        ))
+        .bearer_auth(&config.access_token)
+        .multipart(form)
+        .send()
+        .await?;
+    let status = res.status();
+    if !status.is_success() {
+        log::warn!(
+            "Mastodon rejected {} ({}): {}",
+            file,
+            status,
+            res.text().await?
+        );
+        return Ok(None);
+    }
+    let media: Created = res.json().await?;
+    Ok(Some(media.id))
+}
+
+/// Posts a status per message, attaching downloaded sticker files where possible.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &MastodonConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+    let mut last_url = None;
+
+    for message in messages {
+        let mut form = vec![(
+            "status".to_string(),
+            status_text(message, config.max_length),
+        )];
+        if let Some(visibility) = &config.visibility {
+            form.push(("visibility".to_string(), visibility.clone()));
+        }
+        let file = message.sticker.as_ref().and_then(|s| s.file.as_deref());
+        if let Some(file) = file {
+            if let Some(id) = upload_media(&http_client, config, file).await? {
+                form.push(("media_ids[]".to_string(), id));
+            }
+        }
+
+        let res = http_client
+            .post(format!(
+                "{}/api/v1/statuses",
+                config.instance.trim_end_matches('/')
+            ))
+            .bearer_auth(&config.access_token)
+            // Lets the instance drop duplicates if a run is retried.
+            .header(
+                "Idempotency-Key",
+                format!(
+                    "pin-{}-{}",
+                    message.chat.trim_start_matches('@'),
+                    message.id
+                ),
+            )
+            .form(&form)
+            .send()
+            .await?;
+        let status = res.status();
+        if !status.is_success() {
+            return Err(Box::new(UploadError(format!(
+                "Mastodon answered {}: {}",
+                status,
+                res.text().await?
+            ))));
+        }
+        let created: Created = res.json().await?;
+        last_url = created.url.or(Some(created.id));
+    }
+    println!("Posted {} statuses to {}", messages.len(), config.instance);
+
+    Ok(UploadResult {
+        url: last_url.unwrap_or_else(|| config.instance.clone()),
+        id: None,
+        size: None,
+    })
+}
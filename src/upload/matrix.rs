@@ -0,0 +1,144 @@
+use serde_derive::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::{Message, Result};
+
+#[derive(Deserialize, Clone)]
+pub struct MatrixConfig {
+    /// Base URL of the homeserver, e.g. `https://matrix.org`.
+    pub homeserver: String,
+    pub access_token: String,
+    /// Room ID (`!abc:example.org`), not an alias.
+    pub room_id: String,
+}
+
+#[derive(Deserialize)]
+struct RateLimited {
+    retry_after_ms: Option<u64>,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn text_of(message: &Message) -> &str {
+    match (&message.sticker, message.text.is_empty()) {
+        (Some(sticker), true) => &sticker.emoji,
+        _ => &message.text,
+    }
+}
+
+fn content(message: &Message) -> serde_json::Value {
+    let text = text_of(message);
+    let link = message.link();
+    let chat = match &link {
+        Some(link) => format!(
+            "<a href=\"{}\">{}</a>",
+            escape_html(link),
+            escape_html(message.chat_label())
+        ),
+        None => escape_html(message.chat_label()),
+    };
+    let (sender, sender_html) = match message.sender.is_empty() {
+        true => (String::new(), String::new()),
+        false => (
+            format!("{} ", message.sender),
+            format!("<b>{}</b> ", escape_html(&message.sender)),
+        ),
+    };
+    let body = format!(
+        "{}in {} on {}:\n{}",
+        sender,
+        message.chat_label(),
+        message.date,
+        text
+    );
+    json!({
+        "msgtype": "m.text",
+        "body": match &link {
+            Some(link) => format!("{}\n{}", body, link),
+            None => body,
+        },
+        "format": "org.matrix.custom.html",
+        "formatted_body": format!(
+            "{}in {} on {}<br><blockquote>{}</blockquote>",
+            sender_html,
+            chat,
+            message.date,
+            escape_html(text).replace('\n', "<br>"),
+        ),
+    })
+}
+
+/// Sends each message to the room. Transaction IDs are derived from the pin, so a retried
+/// request is not delivered twice.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &MatrixConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+
+    for message in messages {
+        let txn_id = format!(
+            "pin-{}-{}",
+            message.chat.trim_start_matches('@'),
+            message.id
+        );
+        let mut url = reqwest::Url::parse(&config.homeserver)?;
+        url.path_segments_mut()
+            .map_err(|_| UploadError(format!("Invalid homeserver URL {}", config.homeserver)))?
+            .pop_if_empty()
+            .extend([
+                "_matrix",
+                "client",
+                "v3",
+                "rooms",
+                &config.room_id,
+                "send",
+                "m.room.message",
+                &txn_id,
+            ]);
+        let body = content(message);
+
+        loop {
+            let res = http_client
+                .put(url.clone())
+                .bearer_auth(&config.access_token)
+                .json(&body)
+                .send()
+                .await?;
+            let status = res.status();
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                let limit: RateLimited = res.json().await?;
+                let wait = limit.retry_after_ms.unwrap_or(1000);
+                tokio::time::sleep(Duration::from_millis(wait)).await;
+                continue;
+            }
+            if !status.is_success() {
+                return Err(Box::new(UploadError(format!(
+                    "Matrix homeserver answered {}: {}",
+                    status,
+                    res.text().await?
+                ))));
+            }
+            break;
+        }
+    }
+    println!(
+        "Sent {} messages to Matrix room {}",
+        messages.len(),
+        config.room_id
+    );
+
+    Ok(UploadResult {
+        url: format!("https://matrix.to/#/{}", config.room_id),
+        id: Some(config.room_id.clone()),
+        size: None,
+    })
+}
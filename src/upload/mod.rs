@@ -0,0 +1,323 @@
+mod bookmarks;
+mod discord;
+mod ftp;
+mod gofile;
+mod http;
+mod kafka;
+mod mastodon;
+mod matrix;
+mod mqtt;
+mod nats;
+mod notify;
+mod notion;
+mod paste;
+mod plugin;
+mod redis;
+mod telegraph;
+
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::time::Duration;
+
+pub use bookmarks::{LinkdingConfig, RaindropConfig, WallabagConfig};
+pub use discord::DiscordConfig;
+pub use ftp::FtpConfig;
+pub use gofile::{AccountInfo, GofileConfig};
+pub use http::{HttpConfig, HttpMode};
+pub use kafka::KafkaConfig;
+pub use mastodon::MastodonConfig;
+pub use matrix::MatrixConfig;
+pub use mqtt::MqttConfig;
+pub use nats::NatsConfig;
+pub use notify::{NtfyConfig, PushoverConfig};
+pub use notion::{NotionConfig, NotionProperties};
+pub use paste::PasteConfig;
+pub use plugin::PluginUploadConfig;
+pub use redis::{RedisConfig, RedisMode};
+pub use telegraph::TelegraphConfig;
+
+use paste::PasteService;
+
+use crate::config::FileConfig;
+use crate::config::{ProviderConfig, UploadConfig};
+use crate::export::{self, Collision, Envelope, ExportFile, Exporter, Field, Selected};
+use crate::plugin::Plugins;
+use crate::run::RunId;
+use crate::throttle::{self, Bandwidth};
+use crate::{Message, Result};
+
+/// What a provider hands back after a successful upload, normalized across providers.
+#[derive(Serialize, Debug, Clone)]
+pub struct UploadResult {
+    /// Shareable link to the uploaded export.
+    pub url: String,
+    pub id: Option<String>,
+    pub size: Option<u64>,
+}
+
+impl fmt::Display for UploadResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.url)?;
+        if let Some(size) = self.size {
+            write!(f, " ({} bytes)", size)?;
+        }
+        Ok(())
+    }
+}
+
+/// How export files are stored, `upload.mode`.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadMode {
+    /// A new file per run, named after its date.
+    #[default]
+    Replace,
+    /// A single archive, `archive_name`, that every run downloads, adds its pins to and
+    /// uploads again. Only providers that can read files back support it.
+    #[serde(alias = "append")]
+    Merge,
+}
+
+#[derive(Debug)]
+pub struct UploadError(pub String);
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+/// Everything providers need besides their own settings.
+pub struct UploadContext<'a> {
+    pub run_id: RunId,
+    pub upload: &'a UploadConfig,
+    /// How to handle an export file the provider already has, see `upload.on_collision`.
+    pub collision: Option<Collision>,
+    pub max_bandwidth: Option<Bandwidth>,
+    pub fields: &'a [Field],
+    /// For the `plugin` provider.
+    pub plugins: &'a Plugins,
+}
+
+impl UploadContext<'_> {
+    /// `message` as a JSON record, with the fields selected in `export.fields` and the run
+    /// that sent it.
+    pub fn record<'m>(&'m self, message: &'m Message) -> Record<'m> {
+        Record {
+            message: Selected {
+                message,
+                fields: self.fields,
+            },
+            run_id: self.run_id,
+        }
+    }
+
+    /// Key under which a provider can drop a repeated delivery of `message` within this run,
+    /// e.g. when a request is retried after its answer was lost.
+    pub fn idempotency_key(&self, message: &Message) -> String {
+        format!("{}-{}-{}", self.run_id, message.chat, message.id)
+    }
+
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        http_client(self.upload)
+    }
+
+    /// Request body for an upload, throttled to `max_bandwidth`.
+    pub fn body(&self, bytes: Vec<u8>) -> reqwest::Body {
+        throttle::body(bytes, self.max_bandwidth)
+    }
+}
+
+/// Builds the HTTP client shared by providers, honouring the timeout and TLS settings.
+fn http_client(config: &UploadConfig) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder();
+
+    if let Some(secs) = config.connect_timeout {
+        builder = builder.connect_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = config.timeout {
+        builder = builder.timeout(Duration::from_secs(secs));
+    }
+    if let Some(path) = &config.ca_certificate {
+        let pem = fs::read(path)?;
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+    }
+    if config.danger_accept_invalid_certs {
+        log::warn!("TLS certificate validation is disabled for uploads");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// A message as providers that take messages send it.
+#[derive(Serialize)]
+pub struct Record<'a> {
+    #[serde(flatten)]
+    message: Selected<'a>,
+    run_id: RunId,
+}
+
+fn context<'a>(
+    creds_toml: &'a FileConfig,
+    destination: &'a UploadConfig,
+    plugins: &'a Plugins,
+    run_id: RunId,
+) -> UploadContext<'a> {
+    UploadContext {
+        run_id,
+        upload: destination,
+        collision: destination.on_collision,
+        max_bandwidth: creds_toml.config.max_bandwidth,
+        fields: &creds_toml.export.fields,
+        plugins,
+    }
+}
+
+pub async fn upload(
+    creds_toml: &FileConfig,
+    destination: &UploadConfig,
+    plugins: &Plugins,
+    run_id: RunId,
+    file: ExportFile,
+) -> Result<UploadResult> {
+    let upload = &context(creds_toml, destination, plugins, run_id);
+    match &destination.provider {
+        ProviderConfig::Gofile(gofile) => gofile::upload(upload, gofile, file).await,
+        ProviderConfig::Ftp(ftp) => ftp::upload(upload, ftp, file).await,
+        ProviderConfig::ZeroXZero(paste) => {
+            paste::upload(upload, paste, PasteService::ZeroXZero, file).await
+        }
+        ProviderConfig::PasteRs(paste) => {
+            paste::upload(upload, paste, PasteService::PasteRs, file).await
+        }
+        ProviderConfig::Dpaste(paste) => {
+            paste::upload(upload, paste, PasteService::Dpaste, file).await
+        }
+        ProviderConfig::Http(http) if http.send == HttpMode::Export => {
+            http::upload(upload, http, file).await
+        }
+        ProviderConfig::Plugin(plugin) => plugin::upload(upload, plugin, file).await,
+        _ => Err(Box::new(UploadError(
+            "This provider takes messages, not export files".to_string(),
+        ))),
+    }
+}
+
+/// Checks the provider's credentials before a run and returns what it reports about the
+/// account, for providers that have an account API.
+pub async fn check_account(
+    creds_toml: &FileConfig,
+    destination: &UploadConfig,
+    plugins: &Plugins,
+    run_id: RunId,
+) -> Result<Option<AccountInfo>> {
+    let upload = &context(creds_toml, destination, plugins, run_id);
+    match &destination.provider {
+        ProviderConfig::Gofile(gofile) => Ok(Some(gofile::account(upload, gofile).await?)),
+        _ => Ok(None),
+    }
+}
+
+/// Hands the messages themselves to providers that store them individually rather than as an
+/// export file, see [`ProviderConfig::takes_messages`].
+pub async fn upload_messages(
+    creds_toml: &FileConfig,
+    destination: &UploadConfig,
+    plugins: &Plugins,
+    run_id: RunId,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let upload = &context(creds_toml, destination, plugins, run_id);
+    match &destination.provider {
+        ProviderConfig::Notion(notion) => notion::upload(upload, notion, messages).await,
+        ProviderConfig::Discord(discord) => discord::upload(upload, discord, messages).await,
+        ProviderConfig::Matrix(matrix) => matrix::upload(upload, matrix, messages).await,
+        ProviderConfig::Mastodon(mastodon) => mastodon::upload(upload, mastodon, messages).await,
+        ProviderConfig::Kafka(kafka) => kafka::upload(upload, kafka, messages).await,
+        ProviderConfig::Nats(nats) => nats::upload(upload, nats, messages).await,
+        ProviderConfig::Mqtt(mqtt) => mqtt::upload(upload, mqtt, messages).await,
+        ProviderConfig::Redis(redis) => redis::upload(upload, redis, messages).await,
+        ProviderConfig::Ntfy(ntfy) => notify::ntfy(upload, ntfy, messages).await,
+        ProviderConfig::Pushover(pushover) => notify::pushover(upload, pushover, messages).await,
+        ProviderConfig::Telegraph(telegraph) => {
+            telegraph::upload(upload, telegraph, messages).await
+        }
+        ProviderConfig::Linkding(linkding) => bookmarks::linkding(upload, linkding, messages).await,
+        ProviderConfig::Wallabag(wallabag) => bookmarks::wallabag(upload, wallabag, messages).await,
+        ProviderConfig::Raindrop(raindrop) => bookmarks::raindrop(upload, raindrop, messages).await,
+        ProviderConfig::Http(http) if http.send == HttpMode::Message => {
+            http::upload_messages(upload, http, messages).await
+        }
+        _ => Err(Box::new(UploadError(
+            "This provider takes export files, not messages".to_string(),
+        ))),
+    }
+}
+
+/// Merges the export into the archive kept on the provider, see [`UploadMode::Merge`]. The
+/// archive is always a single JSON file, whatever `export.max_size` says, so it can be read
+/// back on the next run. `format` is the name `exporter` is registered under.
+pub async fn upload_merged(
+    creds_toml: &FileConfig,
+    destination: &UploadConfig,
+    plugins: &Plugins,
+    format: &str,
+    exporter: &dyn Exporter,
+    envelope: &Envelope<'_>,
+) -> Result<UploadResult> {
+    // Going by the name, as other formats such as tdesktop also write `.json` files.
+    if !format.eq_ignore_ascii_case("json") {
+        return Err(Box::new(UploadError(
+            "mode = \"merge\" needs the json format, the only one it reads back".to_string(),
+        )));
+    }
+    let fields = &creds_toml.export.fields;
+    if !fields.contains(&Field::Id) || !fields.contains(&Field::Chat) {
+        return Err(Box::new(UploadError(
+            "mode = \"merge\" tells pins apart by chat and ID, keep both in export.fields"
+                .to_string(),
+        )));
+    }
+
+    let upload = &context(creds_toml, destination, plugins, envelope.run_id);
+    let name = format!("{}.{}", destination.archive_name, exporter.extension());
+    let existing = match &destination.provider {
+        ProviderConfig::Ftp(ftp) => ftp::download(upload, ftp, &name).await?,
+        ProviderConfig::Http(http) if http.send == HttpMode::Export => {
+            http::download(upload, http).await?
+        }
+        _ => {
+            return Err(Box::new(UploadError(
+                "This provider cannot read the archive back for mode = \"merge\"".to_string(),
+            )))
+        }
+    };
+    let (mut chats, mut messages) = match existing {
+        Some(bytes) => export::read_json(&bytes)?,
+        None => Default::default(),
+    };
+    export::merge(
+        &mut chats,
+        &mut messages,
+        &envelope.chats,
+        &envelope.messages,
+    );
+    log::info!("Archive {} now holds {} pins", name, messages.len());
+
+    let mut file = ExportFile::render(exporter, &envelope.with_contents(chats, &messages))?;
+    file.name = name;
+    // The archive is meant to be replaced, whatever `on_collision` says.
+    let upload = &UploadContext {
+        collision: Some(Collision::Overwrite),
+        ..context(creds_toml, destination, plugins, envelope.run_id)
+    };
+    match &destination.provider {
+        ProviderConfig::Ftp(ftp) => ftp::upload(upload, ftp, file).await,
+        ProviderConfig::Http(http) => http::upload(upload, http, file).await,
+        _ => unreachable!("checked before downloading"),
+    }
+}
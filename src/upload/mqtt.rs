@@ -0,0 +1,159 @@
+use serde_derive::Deserialize;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::TcpStream;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::{Message, Result};
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_qos() -> u8 {
+    1
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Plain-text MQTT 3.1.1 settings; TLS is not supported.
+#[derive(Deserialize, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub topic: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// 0 (fire and forget) or 1 (wait for the broker to acknowledge each pin).
+    #[serde(default = "default_qos")]
+    pub qos: u8,
+    /// Keep the latest pin on the topic for clients that subscribe later.
+    #[serde(default = "default_true")]
+    pub retain: bool,
+}
+
+fn put_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+/// Fixed header byte followed by the variable-length encoded size of `body`.
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+async fn read_packet(stream: &mut TcpStream) -> Result<(u8, Vec<u8>)> {
+    let kind = stream.read_u8().await?;
+    let mut len = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = stream.read_u8().await?;
+        len |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    let mut body = vec![0; len];
+    stream.read_exact(&mut body).await?;
+    Ok((kind, body))
+}
+
+fn connect_packet(config: &MqttConfig) -> Vec<u8> {
+    let mut flags = 0x02; // Clean session.
+    if config.username.is_some() {
+        flags |= 0x80;
+    }
+    if config.password.is_some() {
+        flags |= 0x40;
+    }
+
+    let mut body = Vec::new();
+    put_string(&mut body, b"MQTT");
+    body.push(4); // Protocol level 3.1.1.
+    body.push(flags);
+    body.extend_from_slice(&60u16.to_be_bytes());
+    put_string(&mut body, b"telegram-pin-fetcher");
+    if let Some(username) = &config.username {
+        put_string(&mut body, username.as_bytes());
+    }
+    if let Some(password) = &config.password {
+        put_string(&mut body, password.as_bytes());
+    }
+    packet(0x10, &body)
+}
+
+/// Publishes each message as a JSON payload on the topic.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &MqttConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    if config.qos > 1 {
+        return Err(Box::new(UploadError("MQTT qos must be 0 or 1".to_string())));
+    }
+
+    let mut stream = TcpStream::connect((config.host.as_str(), config.port)).await?;
+    stream.write_all(&connect_packet(config)).await?;
+    match read_packet(&mut stream).await? {
+        (0x20, body) if body.get(1) == Some(&0) => {}
+        (_, body) => {
+            return Err(Box::new(UploadError(format!(
+                "MQTT broker refused the connection (code {:?})",
+                body.get(1)
+            ))))
+        }
+    }
+
+    for (i, message) in messages.iter().enumerate() {
+        let packet_id = (i % u16::MAX as usize) as u16 + 1;
+        let mut body = Vec::new();
+        put_string(&mut body, config.topic.as_bytes());
+        if config.qos == 1 {
+            body.extend_from_slice(&packet_id.to_be_bytes());
+        }
+        body.extend_from_slice(&serde_json::to_vec(&upload.record(message))?);
+        let kind = 0x30 | (config.qos << 1) | config.retain as u8;
+        stream.write_all(&packet(kind, &body)).await?;
+
+        if config.qos == 1 {
+            match read_packet(&mut stream).await? {
+                (0x40, ack) if ack == packet_id.to_be_bytes() => {}
+                (kind, _) => {
+                    return Err(Box::new(UploadError(format!(
+                        "Expected PUBACK from the MQTT broker, got packet type {}",
+                        kind >> 4
+                    ))))
+                }
+            }
+        }
+    }
+    stream.write_all(&packet(0xe0, &[])).await?;
+    println!(
+        "Published {} messages to MQTT topic {}",
+        messages.len(),
+        config.topic
+    );
+
+    Ok(UploadResult {
+        url: format!("mqtt://{}:{}/{}", config.host, config.port, config.topic),
+        id: None,
+        size: None,
+    })
+}
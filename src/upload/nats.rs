@@ -0,0 +1,122 @@
+use serde_derive::Deserialize;
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::net::TcpStream;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::{Message, Result};
+
+fn default_server() -> String {
+    "127.0.0.1:4222".to_string()
+}
+
+/// Plain-text NATS connection settings; TLS is not supported.
+#[derive(Deserialize, Clone)]
+pub struct NatsConfig {
+    /// Server as `host:port`.
+    #[serde(default = "default_server")]
+    pub server: String,
+    pub subject: String,
+    pub token: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+async fn read_line(reader: &mut BufReader<TcpStream>) -> Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Err(Box::new(UploadError(
+            "NATS server closed the connection".to_string(),
+        )));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+/// Publishes each message as a JSON payload on the subject, then waits for the server to
+/// answer a `PING` so every `PUB` is known to have been processed. Servers that take headers
+/// get a `Nats-Msg-Id` with each message, which JetStream streams use to drop duplicates.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &NatsConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let mut conn = BufReader::new(TcpStream::connect(&config.server).await?);
+
+    let info = read_line(&mut conn).await?;
+    if !info.starts_with("INFO") {
+        return Err(Box::new(UploadError(format!(
+            "Unexpected greeting from NATS server: {}",
+            info
+        ))));
+    }
+
+    let headers = serde_json::from_str::<serde_json::Value>(info.trim_start_matches("INFO"))
+        .is_ok_and(|info| info["headers"] == true);
+
+    let mut connect = json!({
+        "verbose": false,
+        "headers": headers,
+        "pedantic": false,
+        "name": "telegram-pin-fetcher",
+        "lang": "rust",
+        "version": env!("CARGO_PKG_VERSION"),
+        "auth_token": config.token,
+        "user": config.username,
+        "pass": config.password,
+    });
+    if let Some(fields) = connect.as_object_mut() {
+        fields.retain(|_, value| !value.is_null());
+    }
+    let mut out = format!("CONNECT {}\r\n", connect).into_bytes();
+    for message in messages {
+        let payload = serde_json::to_vec(&upload.record(message))?;
+        match headers {
+            true => {
+                let header = format!(
+                    "NATS/1.0\r\nNats-Msg-Id: {}\r\n\r\n",
+                    upload.idempotency_key(message)
+                );
+                out.extend_from_slice(
+                    format!(
+                        "HPUB {} {} {}\r\n{}",
+                        config.subject,
+                        header.len(),
+                        header.len() + payload.len(),
+                        header
+                    )
+                    .as_bytes(),
+                );
+            }
+            false => out.extend_from_slice(
+                format!("PUB {} {}\r\n", config.subject, payload.len()).as_bytes(),
+            ),
+        }
+        out.extend_from_slice(&payload);
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(b"PING\r\n");
+    conn.get_mut().write_all(&out).await?;
+
+    loop {
+        let line = read_line(&mut conn).await?;
+        match line.as_str() {
+            "PONG" => break,
+            "PING" => conn.get_mut().write_all(b"PONG\r\n").await?,
+            _ if line.starts_with("-ERR") => {
+                return Err(Box::new(UploadError(format!("NATS server: {}", line))));
+            }
+            _ => {}
+        }
+    }
+    println!(
+        "Published {} messages to NATS subject {}",
+        messages.len(),
+        config.subject
+    );
+
+    Ok(UploadResult {
+        url: format!("nats://{}/{}", config.server, config.subject),
+        id: None,
+        size: None,
+    })
+}
@@ -0,0 +1,134 @@
+use serde_derive::Deserialize;
+use serde_json::json;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::{Message, Result};
+
+/// Pushover rejects messages longer than this many characters.
+const PUSHOVER_MAX_MESSAGE: usize = 1024;
+
+fn default_ntfy_server() -> String {
+    "https://ntfy.sh".to_string()
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NtfyConfig {
+    /// Base URL of the ntfy server, for self-hosted instances.
+    #[serde(default = "default_ntfy_server")]
+    pub server: String,
+    pub topic: String,
+    /// Access token for protected topics.
+    pub token: Option<String>,
+    /// 1 (min) to 5 (max); the server default of 3 when unset.
+    pub priority: Option<u8>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct PushoverConfig {
+    /// Application API token.
+    pub token: String,
+    /// User or group key to notify.
+    pub user: String,
+    pub device: Option<String>,
+}
+
+fn title(message: &Message) -> String {
+    match message.sender.is_empty() {
+        true => format!("Pinned in {}", message.chat_label()),
+        false => format!("{} pinned in {}", message.sender, message.chat_label()),
+    }
+}
+
+fn body(message: &Message) -> &str {
+    match (&message.sticker, message.text.is_empty()) {
+        (Some(sticker), true) => &sticker.emoji,
+        _ => &message.text,
+    }
+}
+
+async fn check(res: reqwest::Response, service: &str) -> Result<()> {
+    let status = res.status();
+    if !status.is_success() {
+        return Err(Box::new(UploadError(format!(
+            "{} answered {}: {}",
+            service,
+            status,
+            res.text().await?
+        ))));
+    }
+    Ok(())
+}
+
+pub async fn ntfy(
+    upload: &UploadContext<'_>,
+    config: &NtfyConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+    let server = config.server.trim_end_matches('/');
+
+    for message in messages {
+        let mut notification = json!({
+            "topic": config.topic,
+            "title": title(message),
+            "message": body(message),
+            "tags": ["pushpin"],
+        });
+        if let Some(link) = message.link() {
+            notification["click"] = json!(link);
+        }
+        if let Some(priority) = config.priority {
+            notification["priority"] = json!(priority);
+        }
+        let mut req = http_client.post(server).json(&notification);
+        if let Some(token) = &config.token {
+            req = req.bearer_auth(token);
+        }
+        check(req.send().await?, "ntfy").await?;
+    }
+    println!("Sent {} notifications to ntfy", messages.len());
+
+    Ok(UploadResult {
+        url: format!("{}/{}", server, config.topic),
+        id: None,
+        size: None,
+    })
+}
+
+pub async fn pushover(
+    upload: &UploadContext<'_>,
+    config: &PushoverConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+
+    for message in messages {
+        let text: String = body(message).chars().take(PUSHOVER_MAX_MESSAGE).collect();
+        let mut form = vec![
+            ("token", config.token.clone()),
+            ("user", config.user.clone()),
+            ("title", title(message)),
+            ("message", text),
+        ];
+        if let Some(link) = message.link() {
+            form.push(("url", link));
+            form.push(("url_title", "Open in Telegram".to_string()));
+        }
+        if let Some(device) = &config.device {
+            form.push(("device", device.clone()));
+        }
+        let res = http_client
+            .post("https://api.pushover.net/1/messages.json")
+            .form(&form)
+            .send()
+            .await?;
+        check(res, "Pushover").await?;
+    }
+    println!("Sent {} notifications to Pushover", messages.len());
+
+    Ok(UploadResult {
+        url: "https://pushover.net".to_string(),
+        id: None,
+        size: None,
+    })
+}
@@ -0,0 +1,151 @@
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::{Message, Result};
+
+const API_URL: &str = "https://api.notion.com/v1";
+const API_VERSION: &str = "2022-06-28";
+/// Notion rejects rich text objects longer than this many characters.
+const MAX_TEXT: usize = 2000;
+
+#[derive(Deserialize, Clone)]
+pub struct NotionConfig {
+    /// Token of an internal integration that has been shared with the database.
+    pub token: String,
+    pub database_id: String,
+    #[serde(default)]
+    pub properties: NotionProperties,
+}
+
+/// Names of the database properties each field is written to.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct NotionProperties {
+    /// Title property, receives the message text.
+    pub text: String,
+    /// Rich text property.
+    pub sender: String,
+    /// Date property.
+    pub date: String,
+    /// URL property, also used to skip messages added by earlier runs.
+    pub link: String,
+}
+
+impl Default for NotionProperties {
+    fn default() -> Self {
+        NotionProperties {
+            text: "Name".to_string(),
+            sender: "Sender".to_string(),
+            date: "Date".to_string(),
+            link: "Link".to_string(),
+        }
+    }
+}
+
+fn truncate(text: &str) -> String {
+    text.chars().take(MAX_TEXT).collect()
+}
+
+async fn request(
+    http_client: &reqwest::Client,
+    config: &NotionConfig,
+    path: &str,
+    body: &Value,
+) -> Result<Value> {
+    let res = http_client
+        .post(format!("{}/{}", API_URL, path))
+        .bearer_auth(&config.token)
+        .header("Notion-Version", API_VERSION)
+        .json(body)
+        .send()
+        .await?;
+    let status = res.status();
+    let body = res.text().await?;
+    if !status.is_success() {
+        return Err(Box::new(UploadError(format!(
+            "Notion answered {}: {}",
+            status, body
+        ))));
+    }
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Links of every row already in the database.
+async fn existing_links(
+    http_client: &reqwest::Client,
+    config: &NotionConfig,
+) -> Result<HashSet<String>> {
+    let path = format!("databases/{}/query", config.database_id);
+    let mut links = HashSet::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = json!({ "page_size": 100 });
+        if let Some(cursor) = &cursor {
+            query["start_cursor"] = json!(cursor);
+        }
+        let page = request(http_client, config, &path, &query).await?;
+        for row in page["results"].as_array().into_iter().flatten() {
+            if let Some(link) = row["properties"][&config.properties.link]["url"].as_str() {
+                links.insert(link.to_string());
+            }
+        }
+        match page["next_cursor"].as_str() {
+            Some(next) if page["has_more"].as_bool() == Some(true) => {
+                cursor = Some(next.to_string())
+            }
+            _ => return Ok(links),
+        }
+    }
+}
+
+fn row(config: &NotionConfig, message: &Message) -> Value {
+    let properties = &config.properties;
+    let text = match (&message.sticker, message.text.is_empty()) {
+        (Some(sticker), true) => sticker.emoji.clone(),
+        _ => truncate(&message.text),
+    };
+    json!({
+        "parent": { "database_id": config.database_id },
+        "properties": {
+            (&properties.text): { "title": [{ "text": { "content": text } }] },
+            (&properties.sender): { "rich_text": [{ "text": { "content": message.sender } }] },
+            (&properties.date): { "date": { "start": message.date } },
+            (&properties.link): { "url": message.link() },
+        },
+    })
+}
+
+/// Appends every message not yet in the database as a new row.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &NotionConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+    let existing = existing_links(&http_client, config).await?;
+
+    let mut added = 0;
+    for message in messages {
+        // Pins without a link can't be told apart from those already added.
+        if message.link().is_some_and(|link| existing.contains(&link)) {
+            continue;
+        }
+        request(&http_client, config, "pages", &row(config, message)).await?;
+        added += 1;
+        // Stay below Notion's average of three requests per second.
+        tokio::time::sleep(Duration::from_millis(350)).await;
+    }
+    println!("Added {} new rows to Notion", added);
+
+    Ok(UploadResult {
+        url: format!(
+            "https://www.notion.so/{}",
+            config.database_id.replace('-', "")
+        ),
+        id: Some(config.database_id.clone()),
+        size: None,
+    })
+}
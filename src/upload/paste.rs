@@ -0,0 +1,116 @@
+use serde_derive::Deserialize;
+use std::path::Path;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::export::ExportFile;
+use crate::units::ByteSize;
+use crate::Result;
+
+fn default_max_size() -> ByteSize {
+    ByteSize(512 * 1024)
+}
+
+/// Settings shared by the paste services; each one only uses what it supports.
+#[derive(Deserialize, Clone)]
+pub struct PasteConfig {
+    /// Base URL of a self-hosted instance, instead of the public service.
+    pub url: Option<String>,
+    /// How long the paste should be kept before the service deletes it.
+    pub expiry_days: Option<u32>,
+    /// Exports bigger than this are not pasted; pastes are for a quick look, not archives.
+    #[serde(default = "default_max_size")]
+    pub max_size: ByteSize,
+}
+
+pub enum PasteService {
+    ZeroXZero,
+    PasteRs,
+    Dpaste,
+}
+
+impl PasteService {
+    fn default_url(&self) -> &'static str {
+        match self {
+            PasteService::ZeroXZero => "https://0x0.st",
+            PasteService::PasteRs => "https://paste.rs",
+            PasteService::Dpaste => "https://dpaste.com/api/v2/",
+        }
+    }
+}
+
+/// dpaste's highlighting for an export with `extension`, plain text for formats it has none
+/// for.
+fn syntax(extension: &str) -> &'static str {
+    match extension {
+        "json" => "json",
+        "yaml" => "yaml",
+        "toml" => "toml",
+        "md" => "md",
+        "html" => "html",
+        _ => "text",
+    }
+}
+
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &PasteConfig,
+    service: PasteService,
+    file: ExportFile,
+) -> Result<UploadResult> {
+    let http_client = upload.http_client()?;
+    let url = config.url.as_deref().unwrap_or(service.default_url());
+    let size = file.bytes.len() as u64;
+    if size > config.max_size.0 {
+        return Err(Box::new(UploadError(format!(
+            "{} is {}, bigger than the max_size of {} for pastes",
+            file.name,
+            ByteSize(size),
+            config.max_size
+        ))));
+    }
+
+    let req = match service {
+        PasteService::ZeroXZero => {
+            let part = reqwest::multipart::Part::stream_with_length(upload.body(file.bytes), size)
+                .file_name(file.name)
+                .mime_str(&file.content_type)?;
+            let mut form = reqwest::multipart::Form::new().part("file", part);
+            if let Some(days) = config.expiry_days {
+                form = form.text("expires", (days * 24).to_string());
+            }
+            http_client.post(url).multipart(form)
+        }
+        PasteService::PasteRs => http_client.post(url).body(upload.body(file.bytes)),
+        PasteService::Dpaste => {
+            let extension = Path::new(&file.name)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase());
+            let syntax = syntax(extension.as_deref().unwrap_or_default());
+            let content = String::from_utf8(file.bytes)?;
+            let mut form = vec![("content", content), ("syntax", syntax.to_string())];
+            if let Some(days) = config.expiry_days {
+                form.push(("expiry_days", days.to_string()));
+            }
+            http_client.post(url).form(&form)
+        }
+    };
+
+    let res = req
+        .header(reqwest::header::USER_AGENT, "telegram-pin-fetcher")
+        .send()
+        .await?;
+    let status = res.status();
+    let body = res.text().await?;
+    if !status.is_success() {
+        return Err(Box::new(UploadError(format!(
+            "Paste service answered {}: {}",
+            status, body
+        ))));
+    }
+
+    Ok(UploadResult {
+        url: body.trim().to_string(),
+        id: None,
+        size: Some(size),
+    })
+}
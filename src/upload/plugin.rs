@@ -0,0 +1,129 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::export::ExportFile;
+use crate::run::RunId;
+use crate::Result;
+
+#[derive(Deserialize, Clone)]
+pub struct PluginUploadConfig {
+    /// Name of the plugin, its file name without `.wasm`.
+    pub plugin: String,
+    /// Handed to the plugin as they are, e.g. a bucket name.
+    #[serde(default)]
+    pub settings: Map<String, Value>,
+}
+
+/// What `upload` is called with.
+#[derive(Serialize)]
+struct File<'a> {
+    name: &'a str,
+    content_type: &'a str,
+    size: usize,
+    run_id: RunId,
+    settings: &'a Map<String, Value>,
+}
+
+/// The request `upload` asks the host to send.
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Base64 body; the export file itself when left out.
+    body: Option<String>,
+}
+
+fn default_method() -> String {
+    "PUT".to_string()
+}
+
+/// What `upload_response` is called with.
+#[derive(Serialize)]
+struct Response<'a> {
+    status: u16,
+    headers: BTreeMap<&'a str, &'a str>,
+    body: &'a str,
+    settings: &'a Map<String, Value>,
+}
+
+/// What `upload_response` returns.
+#[derive(Deserialize)]
+struct Outcome {
+    url: Option<String>,
+    id: Option<String>,
+    error: Option<String>,
+}
+
+/// Uploads through a plugin without giving it the network: its `upload` describes the HTTP
+/// request for the file, which is sent from here, and its `upload_response` reads the answer.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &PluginUploadConfig,
+    file: ExportFile,
+) -> Result<UploadResult> {
+    let plugin = upload.plugins.get(&config.plugin)?;
+    let request: Request = plugin.call_json(
+        "upload",
+        &File {
+            name: &file.name,
+            content_type: &file.content_type,
+            size: file.bytes.len(),
+            run_id: upload.run_id,
+            settings: &config.settings,
+        },
+    )?;
+    let method = reqwest::Method::from_bytes(request.method.to_uppercase().as_bytes())?;
+    let body = match request.body {
+        Some(body) => STANDARD.decode(body)?,
+        None => file.bytes,
+    };
+    let size = body.len() as u64;
+    let mut req = upload
+        .http_client()?
+        .request(method, &request.url)
+        .header(reqwest::header::CONTENT_TYPE, &file.content_type)
+        .header(reqwest::header::CONTENT_LENGTH, size);
+    for (name, value) in &request.headers {
+        req = req.header(name, value);
+    }
+    let res = req.body(upload.body(body)).send().await?;
+
+    let status = res.status().as_u16();
+    let headers = res.headers().clone();
+    let text = res.text().await?;
+    log::debug!("Response from remote: {}", text);
+    let outcome: Outcome = plugin.call_json(
+        "upload_response",
+        &Response {
+            status,
+            headers: headers
+                .iter()
+                .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+                .collect(),
+            body: &text,
+            settings: &config.settings,
+        },
+    )?;
+    match (outcome.url, outcome.error) {
+        (_, Some(error)) => Err(Box::new(UploadError(format!(
+            "Plugin {}: {}",
+            config.plugin, error
+        )))),
+        (Some(url), None) => Ok(UploadResult {
+            url,
+            id: outcome.id,
+            size: Some(size),
+        }),
+        (None, None) => Err(Box::new(UploadError(format!(
+            "Plugin {} returned neither url nor error (status {})",
+            config.plugin, status
+        )))),
+    }
+}
@@ -0,0 +1,134 @@
+use serde_derive::Deserialize;
+use tokio::io::{AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::net::TcpStream;
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::{Message, Result};
+
+fn default_address() -> String {
+    "127.0.0.1:6379".to_string()
+}
+
+/// Plain-text Redis settings; TLS is not supported.
+#[derive(Deserialize, Clone)]
+pub struct RedisConfig {
+    /// Server as `host:port`.
+    #[serde(default = "default_address")]
+    pub address: String,
+    pub key: String,
+    #[serde(default)]
+    pub mode: RedisMode,
+    /// Database number to `SELECT` first.
+    pub db: Option<u32>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisMode {
+    /// `LPUSH` each message onto a list.
+    #[default]
+    List,
+    /// `XADD` each message to a stream, with `chat`, `id` and `message` fields.
+    Stream,
+}
+
+struct Connection {
+    stream: BufReader<TcpStream>,
+}
+
+impl Connection {
+    /// Sends a command and returns its reply as text, failing on error replies.
+    async fn command(&mut self, args: &[&[u8]]) -> Result<String> {
+        let mut out = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            out.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            out.extend_from_slice(arg);
+            out.extend_from_slice(b"\r\n");
+        }
+        self.stream.get_mut().write_all(&out).await?;
+
+        let mut line = String::new();
+        if self.stream.read_line(&mut line).await? == 0 {
+            return Err(Box::new(UploadError(
+                "Redis server closed the connection".to_string(),
+            )));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Err(Box::new(UploadError("Empty reply from Redis".to_string())));
+        }
+        match line.split_at(1) {
+            ("-", error) => Err(Box::new(UploadError(format!("Redis: {}", error)))),
+            ("$", len) => {
+                let len: usize = len.parse()?;
+                let mut value = vec![0; len + 2];
+                self.stream.read_exact(&mut value).await?;
+                value.truncate(len);
+                Ok(String::from_utf8_lossy(&value).into_owned())
+            }
+            (_, value) => Ok(value.to_string()),
+        }
+    }
+}
+
+/// Pushes each message onto the configured list or stream, oldest first.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &RedisConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let mut conn = Connection {
+        stream: BufReader::new(TcpStream::connect(&config.address).await?),
+    };
+
+    if let Some(password) = &config.password {
+        let mut auth: Vec<&[u8]> = vec![b"AUTH"];
+        if let Some(username) = &config.username {
+            auth.push(username.as_bytes());
+        }
+        auth.push(password.as_bytes());
+        conn.command(&auth).await?;
+    }
+    if let Some(db) = config.db {
+        conn.command(&[b"SELECT", db.to_string().as_bytes()])
+            .await?;
+    }
+
+    let key = config.key.as_bytes();
+    let mut last = None;
+    for message in messages {
+        let json = serde_json::to_vec(&upload.record(message))?;
+        let reply = match config.mode {
+            RedisMode::List => conn.command(&[b"LPUSH", key, &json]).await?,
+            RedisMode::Stream => {
+                let id = message.id.to_string();
+                conn.command(&[
+                    b"XADD",
+                    key,
+                    b"*",
+                    b"chat",
+                    message.chat.as_bytes(),
+                    b"id",
+                    id.as_bytes(),
+                    b"message",
+                    &json,
+                ])
+                .await?
+            }
+        };
+        last = Some(reply);
+    }
+    println!(
+        "Pushed {} messages to Redis key {}",
+        messages.len(),
+        config.key
+    );
+
+    Ok(UploadResult {
+        url: format!("redis://{}/{}", config.address, config.key),
+        id: last,
+        size: None,
+    })
+}
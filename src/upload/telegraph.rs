@@ -0,0 +1,166 @@
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+
+use super::{UploadContext, UploadError, UploadResult};
+use crate::export;
+use crate::media;
+use crate::{Message, Result};
+
+/// Telegraph rejects page content bigger than 64 KB; this leaves room for the closing note.
+const MAX_CONTENT: usize = 63 * 1024;
+
+fn default_url() -> String {
+    "https://api.telegra.ph".to_string()
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TelegraphConfig {
+    /// Token of a Telegraph account, as returned by its `createAccount` method.
+    pub access_token: String,
+    /// Page title; `Pinned messages, <date>` when unset.
+    pub title: Option<String>,
+    pub author_name: Option<String>,
+    /// Where the author name links to, e.g. the chat's `t.me` link.
+    pub author_url: Option<String>,
+    #[serde(default = "default_url")]
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    ok: bool,
+    result: Option<Page>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    path: String,
+    url: String,
+}
+
+fn element(tag: &str, children: Vec<Value>) -> Value {
+    json!({"tag": tag, "children": children})
+}
+
+/// A pin as Telegraph nodes: who pinned what and when, the text with its line breaks, and a
+/// link back to the message.
+fn nodes(message: &Message) -> Vec<Value> {
+    let text = match (&message.sticker, &message.media, message.text.is_empty()) {
+        (Some(sticker), _, true) => sticker.emoji.clone(),
+        (_, Some(media), true) => media::placeholder(media),
+        _ => message.text.clone(),
+    };
+    let mut lines = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 {
+            lines.push(json!({"tag": "br"}));
+        }
+        lines.push(Value::from(line));
+    }
+
+    let mut nodes = vec![element(
+        "h4",
+        vec![Value::from(match message.sender.is_empty() {
+            true => message.date.clone(),
+            false => format!("{} · {}", message.sender, message.date),
+        })],
+    )];
+    if !lines.is_empty() {
+        nodes.push(element("p", lines));
+    }
+    if let Some(link) = message.link() {
+        let link = json!({
+            "tag": "a",
+            "attrs": {"href": link},
+            "children": ["Open in Telegram"],
+        });
+        nodes.push(element("p", vec![link]));
+    }
+    nodes
+}
+
+/// The page content: pins grouped by chat under a heading each, in the order the chats first
+/// appear. Pins that would push it past Telegraph's size limit are left out with a note.
+fn content(messages: &[Message]) -> Vec<Value> {
+    let mut chats: Vec<&Message> = Vec::new();
+    for message in messages {
+        if !chats.iter().any(|first| first.chat == message.chat) {
+            chats.push(message);
+        }
+    }
+
+    let mut content = Vec::new();
+    let mut size = 2;
+    let mut left_out = 0;
+    for first in chats {
+        let mut heading = Some(element("h3", vec![Value::from(first.chat_label())]));
+        for message in messages.iter().filter(|m| m.chat == first.chat) {
+            let block: Vec<_> = heading.take().into_iter().chain(nodes(message)).collect();
+            let block_size: usize = block.iter().map(|node| node.to_string().len() + 1).sum();
+            // Once one pin is left out, so are all later ones, keeping the page in order.
+            if left_out > 0 || size + block_size > MAX_CONTENT {
+                left_out += 1;
+                continue;
+            }
+            size += block_size;
+            content.extend(block);
+        }
+    }
+    if left_out > 0 {
+        let note = format!("{} more pins did not fit on this page.", left_out);
+        content.push(element("p", vec![element("em", vec![Value::from(note)])]));
+    }
+    content
+}
+
+/// Publishes all pins as one new Telegraph page and returns its URL.
+pub async fn upload(
+    upload: &UploadContext<'_>,
+    config: &TelegraphConfig,
+    messages: &[Message],
+) -> Result<UploadResult> {
+    let title = config
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("Pinned messages, {}", export::today_stem()));
+    let mut page = json!({
+        "access_token": config.access_token,
+        "title": title,
+        "content": content(messages),
+        "return_content": false,
+    });
+    if let Some(author_name) = &config.author_name {
+        page["author_name"] = Value::from(author_name.as_str());
+    }
+    if let Some(author_url) = &config.author_url {
+        page["author_url"] = Value::from(author_url.as_str());
+    }
+
+    let res = upload
+        .http_client()?
+        .post(format!("{}/createPage", config.url.trim_end_matches('/')))
+        .json(&page)
+        .send()
+        .await?;
+    let status = res.status();
+    if !status.is_success() {
+        return Err(Box::new(UploadError(format!(
+            "Telegraph answered {}: {}",
+            status,
+            res.text().await?
+        ))));
+    }
+    let response: Response = res.json().await?;
+    match (response.ok, response.result) {
+        (true, Some(page)) => Ok(UploadResult {
+            url: page.url,
+            id: Some(page.path),
+            size: None,
+        }),
+        _ => Err(Box::new(UploadError(format!(
+            "Telegraph could not create the page: {}",
+            response.error.unwrap_or_default()
+        )))),
+    }
+}